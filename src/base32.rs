@@ -11,7 +11,6 @@
 use alloc::vec::Vec;
 
 /// RFC4648 "extended hex" encoding table
-#[cfg(test)]
 const RFC4648_ALPHABET: &'static [u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUV";
 
 /// RFC4648 "extended hex" decoding table
@@ -21,7 +20,6 @@ const RFC4648_INV_ALPHABET: [i8; 39] = [
 ];
 
 /// Encode bytes into a base32 string.
-#[cfg(test)]
 pub fn encode(data: &[u8]) -> alloc::string::String {
 	// output_length is calculated as follows:
 	// / 5 divides the data length by the number of bits per chunk (5),
@@ -47,7 +45,6 @@ pub fn decode(data: &str) -> Result<Vec<u8>, ()> {
 }
 
 /// Encode a byte slice into a base32 string.
-#[cfg(test)]
 fn encode_data(data: &[u8], alphabet: &'static [u8]) -> Vec<u8> {
 	// cap is calculated as follows:
 	// / 5 divides the data length by the number of bits per chunk (5),
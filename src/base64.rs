@@ -0,0 +1,140 @@
+//! Base64 encoding and decoding as described in RFC 4648, as used in the DNS presentation format
+//! for [`crate::rr::DnsKey`] and [`crate::rr::RRSig`] records, as well as the URL-safe base64url
+//! variant, e.g. for embedding proofs in URIs.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// RFC 4648 base64 encoding table
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+/// RFC 4648 base64url encoding table
+const URL_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+fn decode_char(c: u8, url: bool) -> Result<u8, ()> {
+	match c {
+		b'A'..=b'Z' => Ok(c - b'A'),
+		b'a'..=b'z' => Ok(c - b'a' + 26),
+		b'0'..=b'9' => Ok(c - b'0' + 52),
+		b'+' if !url => Ok(62),
+		b'/' if !url => Ok(63),
+		b'-' if url => Ok(62),
+		b'_' if url => Ok(63),
+		_ => Err(()),
+	}
+}
+
+/// Encode bytes into a padded base64 string.
+pub fn encode(data: &[u8]) -> String {
+	encode_with(data, ALPHABET, true)
+}
+
+/// Encode bytes into an unpadded base64url string, which is safe to use in URIs.
+pub fn encode_url(data: &[u8]) -> String {
+	encode_with(data, URL_ALPHABET, false)
+}
+
+fn encode_with(data: &[u8], alphabet: &[u8; 64], pad: bool) -> String {
+	let mut ret = String::with_capacity((data.len() + 2) / 3 * 4);
+	for chunk in data.chunks(3) {
+		let mut buf = [0u8; 3];
+		buf[..chunk.len()].copy_from_slice(chunk);
+		let chars = [
+			buf[0] >> 2,
+			((buf[0] & 0x03) << 4) | (buf[1] >> 4),
+			((buf[1] & 0x0f) << 2) | (buf[2] >> 6),
+			buf[2] & 0x3f,
+		];
+		for (idx, c) in chars.iter().enumerate() {
+			if idx <= chunk.len() {
+				ret.push(alphabet[*c as usize] as char);
+			} else if pad {
+				ret.push('=');
+			}
+		}
+	}
+	ret
+}
+
+/// Decode a padded base64 string into a byte vector.
+pub fn decode(data: &str) -> Result<Vec<u8>, ()> {
+	decode_with(data.as_bytes(), false)
+}
+
+/// Decode a base64url string, with or without padding, into a byte vector.
+pub fn decode_url(data: &str) -> Result<Vec<u8>, ()> {
+	if data.len() % 4 == 0 { return decode_with(data.as_bytes(), true); }
+	if data.len() % 4 == 1 || data.ends_with('=') { return Err(()); }
+	let mut padded = String::with_capacity(data.len() + 2);
+	padded += data;
+	while padded.len() % 4 != 0 { padded.push('='); }
+	decode_with(padded.as_bytes(), true)
+}
+
+fn decode_with(data: &[u8], url: bool) -> Result<Vec<u8>, ()> {
+	if data.len() % 4 != 0 { return Err(()); }
+	let mut ret = Vec::with_capacity(data.len() / 4 * 3);
+	for (chunk_idx, chunk) in data.chunks(4).enumerate() {
+		let padding = chunk.iter().rev().take_while(|c| **c == b'=').count();
+		if padding > 2 { return Err(()); }
+		if padding != 0 && chunk_idx != data.len() / 4 - 1 { return Err(()); }
+		let mut buf = [0u8; 4];
+		for (i, c) in chunk[..4 - padding].iter().enumerate() {
+			buf[i] = decode_char(*c, url)?;
+		}
+		ret.push((buf[0] << 2) | (buf[1] >> 4));
+		if padding < 2 { ret.push((buf[1] << 4) | (buf[2] >> 2)); }
+		if padding < 1 { ret.push((buf[2] << 6) | buf[3]); }
+		// If the original string had any bits set at positions outside of the encoded data,
+		// treat the string as invalid.
+		if (padding == 1 && buf[2] & 0x03 != 0) || (padding == 2 && buf[1] & 0x0f != 0) {
+			return Err(());
+		}
+	}
+	Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_encode_decode() {
+		// Test vectors from RFC 4648 section 10
+		let vectors = [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foob", "Zm9vYg=="),
+			("fooba", "Zm9vYmE="), ("foobar", "Zm9vYmFy")];
+		for (plain, encoded) in vectors {
+			assert_eq!(encode(plain.as_bytes()), encoded);
+			assert_eq!(decode(encoded).unwrap(), plain.as_bytes());
+		}
+
+		let bytes = (0..=255).collect::<Vec<u8>>();
+		for len in 0..bytes.len() {
+			assert_eq!(decode(&encode(&bytes[..len])).unwrap(), &bytes[..len]);
+		}
+
+		assert!(decode("Zg=").is_err());
+		assert!(decode("Zh==").is_err());
+		assert!(decode("Zg==Zg==").is_err());
+		assert!(decode("Z===").is_err());
+		assert!(decode("Zm9v-A==").is_err());
+	}
+
+	#[test]
+	fn test_encode_decode_url() {
+		let vectors = [("", ""), ("f", "Zg"), ("fo", "Zm8"), ("foo", "Zm9v"), ("foob", "Zm9vYg"),
+			("fooba", "Zm9vYmE"), ("foobar", "Zm9vYmFy"), ("\u{fb}\u{ff}", "w7vDvw")];
+		for (plain, encoded) in vectors {
+			assert_eq!(encode_url(plain.as_bytes()), encoded);
+			assert_eq!(decode_url(encoded).unwrap(), plain.as_bytes());
+		}
+		assert_eq!(encode_url(&[0xfb, 0xff]), "-_8");
+		assert_eq!(decode_url("-_8").unwrap(), [0xfb, 0xff]);
+		assert_eq!(decode_url("-_8=").unwrap(), [0xfb, 0xff]);
+		assert_eq!(decode_url("Zg==").unwrap(), b"f");
+
+		assert!(decode_url("+/8=").is_err());
+		assert!(decode_url("Zg=").is_err());
+		assert!(decode_url("Zm9vY").is_err());
+		assert!(decode("-_8=").is_err());
+	}
+}
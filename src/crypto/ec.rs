@@ -123,6 +123,12 @@ impl<C: Curve + ?Sized> Point<C> {
 		Ok(self.x.mul(&m.square()))
 	}
 
+	#[cfg(test)]
+	fn normalize_y(&self) -> Result<C::CurveField, ()> {
+		let m = C::CurveField::from_modinv_of(self.z.clone().into_i())?;
+		Ok(self.y.mul(&m.square().mul(&m)))
+	}
+
 	fn from_xy(x: C::Int, y: C::Int) -> Result<Self, ()> {
 		Self::check_curve_conditions();
 
@@ -305,6 +311,59 @@ fn add_two_mul<C: Curve>(i: C::ScalarField, I: &Point<C>, j: C::ScalarField, J:
 	res_opt
 }
 
+/// Calculates k * G with a simple double-and-add. This is not constant-time, so is only usable
+/// for signing in tests.
+#[cfg(test)]
+fn mul_g<C: Curve>(k: &C::Int) -> Result<Point<C>, ()> {
+	let mut res: Option<Point<C>> = None;
+	for limb in k.limbs() {
+		for b in 0..64 {
+			if let Some(res) = res.as_mut() { *res = res.double()?; }
+			if limb & (1 << (63 - b)) != 0 {
+				res = Some(if let Some(res) = res { res.add(&C::G)? } else { C::G.clone() });
+			}
+		}
+	}
+	res.ok_or(())
+}
+
+#[cfg(test)]
+fn int_to_be_bytes<I: Int>(i: &I) -> alloc::vec::Vec<u8> {
+	i.limbs().iter().flat_map(|limb| limb.to_be_bytes()).collect()
+}
+
+/// Gets the (uncompressed, without a prefix byte) public key for the given private key.
+#[cfg(test)]
+pub(super) fn public_key<C: Curve>(priv_key: &[u8]) -> Result<alloc::vec::Vec<u8>, ()> {
+	let d = C::ScalarField::from_i(C::Int::from_be_bytes(priv_key)?).into_i();
+	let pk = mul_g::<C>(&d)?;
+	let mut res = int_to_be_bytes(&pk.normalize_x()?.into_i());
+	res.extend_from_slice(&int_to_be_bytes(&pk.normalize_y()?.into_i()));
+	Ok(res)
+}
+
+/// Signs the given message digest with the given private key and nonce, which must be random.
+///
+/// This is not constant-time and is only used to build signed records in tests.
+#[cfg(test)]
+pub(super) fn sign_ecdsa<C: Curve>(priv_key: &[u8], nonce: &[u8], hash_input: &[u8])
+-> Result<alloc::vec::Vec<u8>, ()> {
+	#![allow(non_snake_case)]
+
+	let d = C::ScalarField::from_i(C::Int::from_be_bytes(priv_key)?);
+	let k = C::ScalarField::from_i(C::Int::from_be_bytes(nonce)?).into_i();
+	let k_inv = C::ScalarField::from_modinv_of(k.clone())?;
+	let R = mul_g::<C>(&k)?;
+	let r = C::ScalarField::from_i(R.normalize_x()?.into_i());
+	let z = C::ScalarField::from_i(C::Int::from_be_bytes(hash_input)?);
+	let s = k_inv.mul(&z.add(&r.mul(&d)));
+	if r == C::ScalarField::ZERO || s == C::ScalarField::ZERO { return Err(()); }
+
+	let mut sig = int_to_be_bytes(&r.into_i());
+	sig.extend_from_slice(&int_to_be_bytes(&s.into_i()));
+	Ok(sig)
+}
+
 /// Validates the given signature against the given public key and message digest.
 pub(super) fn validate_ecdsa<C: Curve>(pk: &[u8], sig: &[u8], hash_input: &[u8]) -> Result<(), ()> {
 	#![allow(non_snake_case)]
@@ -52,3 +52,15 @@ impl ec::Curve for P256 {
 pub fn validate_ecdsa(pk: &[u8], sig: &[u8], hash_input: &[u8]) -> Result<(), ()> {
 	ec::validate_ecdsa::<P256>(pk, sig, hash_input)
 }
+
+/// Gets the public key for the given private key.
+#[cfg(test)]
+pub fn public_key(priv_key: &[u8]) -> Result<alloc::vec::Vec<u8>, ()> {
+	ec::public_key::<P256>(priv_key)
+}
+
+/// Signs the given message digest with the given private key and (random) nonce.
+#[cfg(test)]
+pub fn sign_ecdsa(priv_key: &[u8], nonce: &[u8], hash_input: &[u8]) -> Result<alloc::vec::Vec<u8>, ()> {
+	ec::sign_ecdsa::<P256>(priv_key, nonce, hash_input)
+}
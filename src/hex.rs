@@ -0,0 +1,48 @@
+//! Hex encoding and decoding, e.g. for displaying proofs or embedding them in JSON.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Encode bytes into a lowercase hex string.
+pub fn encode(data: &[u8]) -> String {
+	const ALPHABET: &[u8; 16] = b"0123456789abcdef";
+	let mut ret = String::with_capacity(data.len() * 2);
+	for b in data {
+		ret.push(ALPHABET[(b >> 4) as usize] as char);
+		ret.push(ALPHABET[(b & 0xf) as usize] as char);
+	}
+	ret
+}
+
+/// Decode a hex string, in either case, into a byte vector.
+pub fn decode(data: &str) -> Result<Vec<u8>, ()> {
+	if data.len() % 2 != 0 { return Err(()); }
+	let mut ret = Vec::with_capacity(data.len() / 2);
+	for pair in data.as_bytes().chunks(2) {
+		let hi = (pair[0] as char).to_digit(16).ok_or(())?;
+		let lo = (pair[1] as char).to_digit(16).ok_or(())?;
+		ret.push((hi << 4 | lo) as u8);
+	}
+	Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_encode_decode() {
+		assert_eq!(encode(&[]), "");
+		assert_eq!(encode(&[0x00, 0x1f, 0xab, 0xff]), "001fabff");
+		assert_eq!(decode("001fabff").unwrap(), [0x00, 0x1f, 0xab, 0xff]);
+		assert_eq!(decode("001FABFF").unwrap(), [0x00, 0x1f, 0xab, 0xff]);
+
+		let bytes = (0..=255).collect::<Vec<u8>>();
+		assert_eq!(decode(&encode(&bytes)).unwrap(), bytes);
+
+		assert!(decode("0").is_err());
+		assert!(decode("0g").is_err());
+		assert!(decode("+1").is_err());
+		assert!(decode("\u{e9}").is_err());
+	}
+}
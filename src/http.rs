@@ -24,10 +24,17 @@ pub const MAX_PROOF_STEPS: usize = 20;
 
 pub mod rr;
 pub mod ser;
+pub mod message;
+pub mod multipart;
 pub mod query;
+pub mod zonefile;
 
-#[cfg(feature = "validation")]
 mod base32;
+pub mod base64;
+pub mod hex;
+mod idna;
+#[cfg(feature = "serde")]
+mod serde_utils;
 #[cfg(feature = "validation")]
 mod crypto;
 #[cfg(feature = "validation")]
@@ -57,7 +64,7 @@ fn main() { panic!("You need to enable the `build_server` feature to use the bui
 mod imp {
 	use super::*;
 
-	use rr::Name;
+	use rr::*;
 	use query::*;
 
 	use std::net::SocketAddr;
@@ -134,13 +141,14 @@ mod imp {
 						response.1 = "Failed to parse domain, make sure it ends with .";
 						break 'ret_err;
 					};
-					let proof_res = match t.to_ascii_uppercase().as_str() {
-						"TXT" => build_txt_proof_async(resolver_sockaddr, &query_name).await,
-						"TLSA" => build_tlsa_proof_async(resolver_sockaddr, &query_name).await,
-						"A" => build_a_proof_async(resolver_sockaddr, &query_name).await,
-						"AAAA" => build_aaaa_proof_async(resolver_sockaddr, &query_name).await,
+					let ty = match t.to_ascii_uppercase().as_str() {
+						"TXT" => TXT_TYPE,
+						"TLSA" => TLSA_TYPE,
+						"A" => A_TYPE,
+						"AAAA" => AAAA_TYPE,
 						_ => break 'ret_err,
 					};
+					let proof_res = build_proof_async(resolver_sockaddr, &[(query_name, ty)]).await;
 					let (proof, cache_ttl) = if let Ok(proof) = proof_res { proof } else {
 						response = ("404 Not Found", "Failed to generate proof for given domain");
 						break 'ret_err;
@@ -0,0 +1,252 @@
+//! Conversion between Unicode domain names and their ASCII form, per IDNA (RFC 5891) using the
+//! Punycode encoding from RFC 3492.
+//!
+//! We implement the subset of UTS #46 processing which can be done without large Unicode
+//! tables: labels are lowercased, the ideographic and fullwidth full stops are treated as label
+//! separators and labels are checked for hyphen placement and control or whitespace characters.
+//! Input is expected to already be in Unicode Normalization Form C, as is the case for nearly all
+//! user-entered text.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::rr::split_labels;
+
+const BASE: u32 = 36;
+const TMIN: u32 = 1;
+const TMAX: u32 = 26;
+const SKEW: u32 = 38;
+const DAMP: u32 = 700;
+const INITIAL_BIAS: u32 = 72;
+const INITIAL_N: u32 = 128;
+
+const ACE_PREFIX: &str = "xn--";
+
+fn adapt(mut delta: u32, num_points: u32, first_time: bool) -> u32 {
+	delta /= if first_time { DAMP } else { 2 };
+	delta += delta / num_points;
+	let mut k = 0;
+	while delta > ((BASE - TMIN) * TMAX) / 2 {
+		delta /= BASE - TMIN;
+		k += BASE;
+	}
+	k + (BASE - TMIN + 1) * delta / (delta + SKEW)
+}
+
+fn threshold(k: u32, bias: u32) -> u32 {
+	if k <= bias { TMIN } else if k >= bias + TMAX { TMAX } else { k - bias }
+}
+
+fn encode_digit(d: u32) -> char {
+	debug_assert!(d < BASE);
+	if d < 26 { (b'a' + d as u8) as char } else { (b'0' + (d - 26) as u8) as char }
+}
+
+fn decode_digit(c: u8) -> Option<u32> {
+	match c {
+		b'a'..=b'z' => Some((c - b'a') as u32),
+		b'A'..=b'Z' => Some((c - b'A') as u32),
+		b'0'..=b'9' => Some((c - b'0') as u32 + 26),
+		_ => None,
+	}
+}
+
+/// Encodes the given characters with Punycode (without the `xn--` prefix).
+fn punycode_encode(input: &[char]) -> Option<String> {
+	let mut out: String = input.iter().filter(|c| c.is_ascii()).collect();
+	let basic_len = out.len() as u32;
+	if basic_len > 0 { out.push('-'); }
+
+	let mut n = INITIAL_N;
+	let mut delta: u32 = 0;
+	let mut bias = INITIAL_BIAS;
+	let mut handled = basic_len;
+	while (handled as usize) < input.len() {
+		let m = input.iter().map(|c| *c as u32).filter(|c| *c >= n).min()?;
+		delta = delta.checked_add((m - n).checked_mul(handled + 1)?)?;
+		n = m;
+		for c in input.iter().map(|c| *c as u32) {
+			if c < n { delta = delta.checked_add(1)?; }
+			if c == n {
+				let mut q = delta;
+				let mut k = BASE;
+				loop {
+					let t = threshold(k, bias);
+					if q < t { break; }
+					out.push(encode_digit(t + (q - t) % (BASE - t)));
+					q = (q - t) / (BASE - t);
+					k += BASE;
+				}
+				out.push(encode_digit(q));
+				bias = adapt(delta, handled + 1, handled == basic_len);
+				delta = 0;
+				handled += 1;
+			}
+		}
+		delta = delta.checked_add(1)?;
+		n += 1;
+	}
+	Some(out)
+}
+
+/// Decodes the given Punycode string (without the `xn--` prefix).
+fn punycode_decode(input: &str) -> Option<Vec<char>> {
+	let (basic, encoded) = match input.rfind('-') {
+		Some(idx) => (&input[..idx], &input[idx + 1..]),
+		None => ("", input),
+	};
+	if !basic.is_ascii() { return None; }
+	let mut out: Vec<char> = basic.chars().collect();
+
+	let mut n = INITIAL_N;
+	let mut i: u32 = 0;
+	let mut bias = INITIAL_BIAS;
+	let mut encoded = encoded.bytes().peekable();
+	while encoded.peek().is_some() {
+		let old_i = i;
+		let mut w: u32 = 1;
+		let mut k = BASE;
+		loop {
+			let digit = decode_digit(encoded.next()?)?;
+			i = i.checked_add(digit.checked_mul(w)?)?;
+			let t = threshold(k, bias);
+			if digit < t { break; }
+			w = w.checked_mul(BASE - t)?;
+			k += BASE;
+		}
+		let len = out.len() as u32 + 1;
+		bias = adapt(i - old_i, len, old_i == 0);
+		n = n.checked_add(i / len)?;
+		i %= len;
+		out.insert(i as usize, char::from_u32(n)?);
+		i += 1;
+	}
+	Some(out)
+}
+
+/// Checks a (mapped) Unicode label is valid, per the UTS #46 checks we support.
+fn check_unicode_label(label: &[char]) -> Result<(), ()> {
+	if label.is_empty() { return Err(()); }
+	if label[0] == '-' || label[label.len() - 1] == '-' { return Err(()); }
+	if label.len() >= 4 && label[2] == '-' && label[3] == '-' { return Err(()); }
+	if label.iter().any(|c| c.is_control() || c.is_whitespace() || *c == '.') { return Err(()); }
+	Ok(())
+}
+
+/// Converts a Unicode domain name to its ASCII form, with a trailing `.`.
+///
+/// The input may or may not have a trailing `.`.
+pub(crate) fn to_ascii(name: &str) -> Result<String, ()> {
+	let mut mapped = String::with_capacity(name.len());
+	for c in name.chars() {
+		match c {
+			'\u{3002}'|'\u{ff0e}'|'\u{ff61}' => mapped.push('.'),
+			_ => mapped.extend(c.to_lowercase()),
+		}
+	}
+	if mapped == "." { return Ok(mapped); }
+	let labels = mapped.strip_suffix('.').unwrap_or(&mapped);
+
+	let mut res = String::with_capacity(labels.len() + 1);
+	for label in labels.split('.') {
+		if label.is_empty() { return Err(()); }
+		if label.is_ascii() {
+			if let Some(encoded) = label.strip_prefix(ACE_PREFIX) {
+				// Already-encoded labels must round-trip and decode to a valid Unicode label.
+				let decoded = punycode_decode(encoded).ok_or(())?;
+				if decoded.iter().all(|c| c.is_ascii()) { return Err(()); }
+				check_unicode_label(&decoded)?;
+				if punycode_encode(&decoded).as_deref() != Some(encoded) { return Err(()); }
+			}
+			res += label;
+		} else {
+			let chars: Vec<char> = label.chars().collect();
+			check_unicode_label(&chars)?;
+			res += ACE_PREFIX;
+			res += &punycode_encode(&chars).ok_or(())?;
+		}
+		res.push('.');
+	}
+	Ok(res)
+}
+
+/// Converts an ASCII domain name to Unicode, decoding any valid `xn--` labels.
+///
+/// Labels which fail to decode are left as-is.
+pub(crate) fn to_unicode(name: &str) -> String {
+	if name == "." { return name.into(); }
+	let mut res = String::with_capacity(name.len());
+	for label in split_labels(name) {
+		let decoded = label.strip_prefix(ACE_PREFIX)
+			.and_then(punycode_decode)
+			.filter(|decoded| check_unicode_label(decoded).is_ok());
+		match decoded {
+			Some(chars) => res.extend(chars),
+			None => res += label,
+		}
+		res.push('.');
+	}
+	res
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_punycode() {
+		// Test vectors from RFC 3492 section 7.1, plus a few common examples
+		let vectors = [
+			("\u{0644}\u{064A}\u{0647}\u{0645}\u{0627}\u{0628}\u{062A}\u{0643}\u{0644}\u{0645}\u{0648}\u{0634}\u{0639}\u{0631}\u{0628}\u{064A}\u{061F}",
+				"egbpdaj6bu4bxfgehfvwxn"),
+			("\u{4ED6}\u{4EEC}\u{4E3A}\u{4EC0}\u{4E48}\u{4E0D}\u{8BF4}\u{4E2D}\u{6587}", "ihqwcrb4cv8a8dqg056pqjye"),
+			("Pro\u{010D}prost\u{011B}nemluv\u{00ED}\u{010D}esky", "Proprostnemluvesky-uyb24dma41a"),
+			("\u{3072}\u{3068}\u{3064}\u{5C4B}\u{6839}\u{306E}\u{4E0B}2", "2-u9tlzr9756bt3uc0v"),
+			("-> $1.00 <-", "-> $1.00 <--"),
+			("b\u{00FC}cher", "bcher-kva"),
+			("m\u{00FC}nchen", "mnchen-3ya"),
+			("\u{20BF}", "4zg"),
+		];
+		for (decoded, encoded) in vectors {
+			let chars: Vec<char> = decoded.chars().collect();
+			assert_eq!(punycode_encode(&chars).unwrap(), encoded);
+			assert_eq!(punycode_decode(encoded).unwrap(), chars);
+		}
+		assert!(punycode_decode("bcher-kv!").is_none());
+		assert!(punycode_decode("99999999999999").is_none());
+	}
+
+	#[test]
+	fn test_to_ascii() {
+		assert_eq!(to_ascii("B\u{00FC}cher.de").unwrap(), "xn--bcher-kva.de.");
+		assert_eq!(to_ascii("b\u{00FC}cher.de.").unwrap(), "xn--bcher-kva.de.");
+		assert_eq!(to_ascii("alice.user._bitcoin-payment.m\u{00FC}nchen\u{3002}de").unwrap(),
+			"alice.user._bitcoin-payment.xn--mnchen-3ya.de.");
+		assert_eq!(to_ascii("xn--bcher-kva.de").unwrap(), "xn--bcher-kva.de.");
+		assert_eq!(to_ascii("example.com").unwrap(), "example.com.");
+		assert_eq!(to_ascii(".").unwrap(), ".");
+
+		assert!(to_ascii("").is_err());
+		assert!(to_ascii("b\u{00FC}cher..de").is_err());
+		assert!(to_ascii("-b\u{00FC}cher.de").is_err());
+		assert!(to_ascii("b\u{00FC}cher-.de").is_err());
+		assert!(to_ascii("b\u{00FC}--cher.de").is_err());
+		assert!(to_ascii("b\u{00FC}\u{00A0}cher.de").is_err());
+		// ACE labels must decode to non-ASCII and round-trip.
+		assert!(to_ascii("xn--abc-.de").is_err());
+		assert!(to_ascii("xn--bcher-kv!.de").is_err());
+	}
+
+	#[test]
+	fn test_to_unicode() {
+		assert_eq!(to_unicode("xn--bcher-kva.de."), "b\u{00FC}cher.de.");
+		assert_eq!(to_unicode("alice.user._bitcoin-payment.xn--mnchen-3ya.de."),
+			"alice.user._bitcoin-payment.m\u{00FC}nchen.de.");
+		assert_eq!(to_unicode("xn--invalid!.de."), "xn--invalid!.de.");
+		assert_eq!(to_unicode("."), ".");
+
+		let name = crate::rr::Name::from_unicode("B\u{00FC}cher.de").unwrap();
+		assert_eq!(name.as_str(), "xn--bcher-kva.de.");
+		assert_eq!(name.to_unicode(), "b\u{00FC}cher.de.");
+	}
+}
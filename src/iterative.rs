@@ -0,0 +1,737 @@
+//! This module exposes utilities for building DNSSEC proofs by iteratively querying authoritative
+//! servers, starting from the root servers, rather than relying on a recursive resolver.
+
+use core::cmp;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::io::{Error, ErrorKind};
+
+use crate::message::*;
+use crate::query::{build_query_with_flags, write_proof_records, QueryBuf, Transport, UdpTransport, TXID};
+#[cfg(feature = "tokio")]
+use crate::query::{AsyncTransport, TokioUdpTransport};
+use crate::rr::*;
+use crate::MAX_PROOF_STEPS;
+
+/// The addresses of the root servers (`a.root-servers.net.` through `m.root-servers.net.`), with
+/// all IPv4 addresses listed before the IPv6 ones.
+pub const ROOT_HINTS: [IpAddr; 26] = [
+	IpAddr::V4(Ipv4Addr::new(198, 41, 0, 4)),
+	IpAddr::V4(Ipv4Addr::new(170, 247, 170, 2)),
+	IpAddr::V4(Ipv4Addr::new(192, 33, 4, 12)),
+	IpAddr::V4(Ipv4Addr::new(199, 7, 91, 13)),
+	IpAddr::V4(Ipv4Addr::new(192, 203, 230, 10)),
+	IpAddr::V4(Ipv4Addr::new(192, 5, 5, 241)),
+	IpAddr::V4(Ipv4Addr::new(192, 112, 36, 4)),
+	IpAddr::V4(Ipv4Addr::new(198, 97, 190, 53)),
+	IpAddr::V4(Ipv4Addr::new(192, 36, 148, 17)),
+	IpAddr::V4(Ipv4Addr::new(192, 58, 128, 30)),
+	IpAddr::V4(Ipv4Addr::new(193, 0, 14, 129)),
+	IpAddr::V4(Ipv4Addr::new(199, 7, 83, 42)),
+	IpAddr::V4(Ipv4Addr::new(202, 12, 27, 33)),
+	IpAddr::V6(Ipv6Addr::new(0x2001, 0x503, 0xba3e, 0, 0, 0, 2, 0x30)),
+	IpAddr::V6(Ipv6Addr::new(0x2801, 0x1b8, 0x10, 0, 0, 0, 0, 0xb)),
+	IpAddr::V6(Ipv6Addr::new(0x2001, 0x500, 2, 0, 0, 0, 0, 0xc)),
+	IpAddr::V6(Ipv6Addr::new(0x2001, 0x500, 0x2d, 0, 0, 0, 0, 0xd)),
+	IpAddr::V6(Ipv6Addr::new(0x2001, 0x500, 0xa8, 0, 0, 0, 0, 0xe)),
+	IpAddr::V6(Ipv6Addr::new(0x2001, 0x500, 0x2f, 0, 0, 0, 0, 0xf)),
+	IpAddr::V6(Ipv6Addr::new(0x2001, 0x500, 0x12, 0, 0, 0, 0, 0xd0d)),
+	IpAddr::V6(Ipv6Addr::new(0x2001, 0x500, 1, 0, 0, 0, 0, 0x53)),
+	IpAddr::V6(Ipv6Addr::new(0x2001, 0x7fe, 0, 0, 0, 0, 0, 0x53)),
+	IpAddr::V6(Ipv6Addr::new(0x2001, 0x503, 0xc27, 0, 0, 0, 2, 0x30)),
+	IpAddr::V6(Ipv6Addr::new(0x2001, 0x7fd, 0, 0, 0, 0, 0, 1)),
+	IpAddr::V6(Ipv6Addr::new(0x2001, 0x500, 0x9f, 0, 0, 0, 0, 0x42)),
+	IpAddr::V6(Ipv6Addr::new(0x2001, 0xdc3, 0, 0, 0, 0, 0, 0x35)),
+];
+
+/// The maximum number of queries we'll send when building a proof iteratively. This is rather
+/// higher than [`MAX_PROOF_STEPS`] as each step may require following several referrals and
+/// some servers may not respond.
+const MAX_QUERIES: usize = MAX_PROOF_STEPS * 4;
+
+#[derive(Clone)]
+enum LookupPurpose {
+	/// The records being looked up will be included in the proof.
+	Proof,
+	/// The records being looked up are the address of a name server for the zone which the lookup
+	/// below this one on the stack was referred to. Contains the other name servers for the zone
+	/// to try if this one cannot be resolved.
+	NsAddress(Vec<Name>),
+}
+
+#[derive(Clone)]
+struct Lookup {
+	name: Name,
+	ty: u16,
+	purpose: LookupPurpose,
+	/// The zone which we believe `servers` are authoritative for.
+	zone: Name,
+	servers: Vec<IpAddr>,
+	next_server: usize,
+}
+
+/// Finds the NS records in a response which refer us to a zone below `lookup.zone` that is closer
+/// to the name being looked up, returning the new zone, its name servers and any usable glue.
+fn find_referral(msg: &Message, lookup: &Lookup) -> Option<(Name, Vec<Name>, Vec<IpAddr>)> {
+	let mut zone: Option<Name> = None;
+	let mut ns_names = Vec::new();
+	for (rr, _) in msg.authorities.iter() {
+		if let RR::NS(ns) = rr {
+			// Only accept referrals down towards the name being looked up, which also ensures we
+			// can't loop. DS records live above their zone cut, so we never descend to them.
+			if ns.name == lookup.zone || !ns.name.is_subdomain_of(&lookup.zone) { continue; }
+			if !lookup.name.is_subdomain_of(&ns.name) { continue; }
+			if lookup.ty == DS::TYPE && ns.name == lookup.name { continue; }
+			if *zone.get_or_insert_with(|| ns.name.clone()) != ns.name { continue; }
+			ns_names.push(ns.name_server.clone());
+		}
+	}
+	let zone = zone?;
+
+	// Only accept glue which the referring server is authoritative for.
+	let glue = msg.additionals.iter().filter_map(|(rr, _)| {
+		let (name, address) = match rr {
+			RR::A(a) => (&a.name, IpAddr::from(a.address)),
+			RR::AAAA(aaaa) => (&aaaa.name, IpAddr::from(aaaa.address)),
+			_ => return None,
+		};
+		if ns_names.contains(name) && name.is_subdomain_of(&lookup.zone) { Some(address) } else { None }
+	}).collect();
+	Some((zone, ns_names, glue))
+}
+
+/// A simple state machine which will generate a series of queries to authoritative servers and
+/// process the responses until it has built a DNSSEC proof, following referrals from the root
+/// servers down to the zones which hold the records.
+///
+/// The resulting proof is in the same format as that built by [`crate::query::ProofBuilder`].
+///
+/// A [`IterativeProofBuilder`] driver starts with [`IterativeProofBuilder::new`]. As long as
+/// [`IterativeProofBuilder::next_query`] returns a query, it should be sent to the given server
+/// and the response passed to [`IterativeProofBuilder::process_response`] or, if no response
+/// could be received, [`IterativeProofBuilder::server_failed`] should be called. Once
+/// [`IterativeProofBuilder::next_query`] returns `None`, [`IterativeProofBuilder::finish_proof`]
+/// should be called to fetch the resulting proof.
+#[derive(Clone)]
+pub struct IterativeProofBuilder {
+	zone_cuts: Vec<(Name, Vec<IpAddr>)>,
+	lookups: Vec<Lookup>,
+	pending: Vec<(Name, u16)>,
+	requested: Vec<(Name, u16)>,
+	proof: Vec<u8>,
+	min_ttl: u32,
+	queries_made: usize,
+	failed: bool,
+}
+
+impl IterativeProofBuilder {
+	/// Constructs a new [`IterativeProofBuilder`] which will build a proof for records with the
+	/// given `ty`pe at the given `name`, starting from the root servers at `root_hints` (e.g.
+	/// [`ROOT_HINTS`]).
+	///
+	/// You can find constants for supported standard types in the [`crate::rr`] module.
+	pub fn new(name: &Name, ty: u16, root_hints: &[IpAddr]) -> IterativeProofBuilder {
+		Self::new_multi(&[(name.clone(), ty)], root_hints)
+	}
+
+	/// Constructs a new [`IterativeProofBuilder`] which will build one combined proof for records
+	/// of each of the given (name, type) pairs, starting from the root servers at `root_hints`
+	/// (e.g. [`ROOT_HINTS`]).
+	///
+	/// Any DNSKEY and DS records required by more than one of the queries are only fetched once.
+	pub fn new_multi(queries: &[(Name, u16)], root_hints: &[IpAddr]) -> IterativeProofBuilder {
+		let root = ".".try_into().expect("The root is a valid name");
+		let mut builder = IterativeProofBuilder {
+			zone_cuts: vec![(root, root_hints.to_vec())],
+			lookups: Vec::new(),
+			pending: Vec::new(),
+			requested: Vec::with_capacity(MAX_PROOF_STEPS),
+			proof: Vec::new(),
+			min_ttl: u32::MAX,
+			queries_made: 0,
+			failed: false,
+		};
+		// Queue the queries in reverse as lookups are popped from the end of `pending`.
+		for (name, ty) in queries.iter().rev() { builder.queue(name.clone(), *ty); }
+		builder
+	}
+
+	fn queue(&mut self, name: Name, ty: u16) {
+		if self.requested.iter().any(|(n, t)| *n == name && *t == ty) { return; }
+		self.requested.push((name.clone(), ty));
+		self.pending.push((name, ty));
+	}
+
+	fn add_zone_cut(&mut self, zone: Name, servers: Vec<IpAddr>) {
+		match self.zone_cuts.iter_mut().find(|(cut, _)| *cut == zone) {
+			Some(cut) => cut.1 = servers,
+			None => self.zone_cuts.push((zone, servers)),
+		}
+	}
+
+	fn push_lookup(&mut self, name: Name, ty: u16, purpose: LookupPurpose) {
+		// DS records live in the parent zone, so have to be looked up from above the zone cut.
+		let (zone, servers) = self.zone_cuts.iter()
+			.filter(|(zone, _)| name.is_subdomain_of(zone))
+			.filter(|(zone, _)| ty != DS::TYPE || *zone != name || zone.as_str() == ".")
+			.max_by_key(|(zone, _)| zone.labels())
+			.map(|(zone, servers)| (zone.clone(), servers.clone()))
+			.expect("The root zone cut is always present");
+		self.lookups.push(Lookup { name, ty, purpose, zone, servers, next_server: 0 });
+	}
+
+	/// Gives up on the current lookup. If it was looking up a name server's IPv4 address we try its
+	/// IPv6 address instead, and otherwise fall back to the next name server.
+	fn fail_lookup(&mut self) {
+		match self.lookups.pop().map(|lookup| (lookup.name, lookup.ty, lookup.purpose)) {
+			Some((name, ty, LookupPurpose::NsAddress(remaining))) if ty == A::TYPE => {
+				self.push_lookup(name, AAAA::TYPE, LookupPurpose::NsAddress(remaining));
+			},
+			Some((_, _, LookupPurpose::NsAddress(mut remaining))) => {
+				if remaining.is_empty() {
+					// The lookup which needed the name server cannot proceed.
+					self.fail_lookup();
+				} else {
+					let name = remaining.remove(0);
+					self.push_lookup(name, A::TYPE, LookupPurpose::NsAddress(remaining));
+				}
+			},
+			_ => self.failed = true,
+		}
+	}
+
+	/// Returns the next query to send and the server to send it to, or `None` once the proof is
+	/// complete or cannot be built.
+	///
+	/// Each query returned must be answered with a call to [`Self::process_response`] or
+	/// [`Self::server_failed`] before this is called again.
+	pub fn next_query(&mut self) -> Option<(QueryBuf, IpAddr)> {
+		loop {
+			if self.failed || self.queries_made >= MAX_QUERIES { return None; }
+			if self.lookups.is_empty() {
+				let (name, ty) = self.pending.pop()?;
+				self.push_lookup(name, ty, LookupPurpose::Proof);
+			}
+			let lookup = self.lookups.last().expect("Pushed above if it was empty");
+			let query = lookup.servers.get(lookup.next_server)
+				.map(|server| (build_query_with_flags(&lookup.name, lookup.ty, 0), *server));
+			if let Some(query) = query {
+				self.queries_made += 1;
+				return Some(query);
+			}
+			// We've run out of servers to try for this lookup.
+			self.fail_lookup();
+		}
+	}
+
+	/// Notes that no response could be received for the last query returned by
+	/// [`Self::next_query`], so that the next query will go to a different server.
+	pub fn server_failed(&mut self) {
+		if let Some(lookup) = self.lookups.last_mut() { lookup.next_server += 1; }
+	}
+
+	/// Processes a response to the last query returned by [`Self::next_query`].
+	///
+	/// If the response is malformed or otherwise unusable, an error is returned and the next query
+	/// will go to a different server.
+	pub fn process_response(&mut self, resp: &QueryBuf) -> Result<(), ()> {
+		let res = self.handle_response(resp);
+		if res.is_err() { self.server_failed(); }
+		res
+	}
+
+	fn handle_response(&mut self, resp: &[u8]) -> Result<(), ()> {
+		let lookup = self.lookups.last().ok_or(())?;
+		let msg = Message::parse(resp)?;
+		if msg.id != TXID { return Err(()); }
+		if !msg.is_response() { return Err(()); }
+		// Require a standard query (opcode 0) which was not truncated and did not fail.
+		if msg.flags & 0b0111_1010_0000_0000 != 0 { return Err(()); }
+		if msg.rcode != RCODE_NOERROR && msg.rcode != RCODE_NXDOMAIN { return Err(()); }
+		if msg.questions.len() != 1 { return Err(()); }
+		if msg.questions[0].name != lookup.name || msg.questions[0].ty != lookup.ty { return Err(()); }
+
+		if msg.answers.iter().any(|(rr, _)| *rr.name() == lookup.name) {
+			self.handle_answer(msg);
+		} else if let Some((zone, ns_names, glue)) = find_referral(&msg, lookup) {
+			self.follow_referral(zone, ns_names, glue);
+		} else if msg.flags & FLAG_AUTHORITATIVE != 0 {
+			// The server is authoritative for the name, but the records do not exist. If it
+			// included a (hopefully signed) proof of that, we can include it in our proof.
+			let has_denial = msg.authorities.iter()
+				.any(|(rr, _)| matches!(rr, RR::NSec(_) | RR::NSec3(_)));
+			if has_denial && matches!(lookup.purpose, LookupPurpose::Proof) {
+				self.lookups.pop();
+				self.write_records(msg);
+			} else {
+				self.fail_lookup();
+			}
+		} else {
+			return Err(());
+		}
+		Ok(())
+	}
+
+	fn follow_referral(&mut self, zone: Name, mut ns_names: Vec<Name>, glue: Vec<IpAddr>) {
+		let lookup = self.lookups.last_mut().expect("Checked by the caller");
+		lookup.zone = zone.clone();
+		lookup.servers = glue.clone();
+		lookup.next_server = 0;
+		if !glue.is_empty() {
+			self.add_zone_cut(zone, glue);
+			return;
+		}
+		// Without glue we have to look up a name server's address first. Name servers within the
+		// zone itself can't be resolved without glue, so we leave the lookup without servers,
+		// failing it, if there are no others.
+		ns_names.retain(|ns| !ns.is_subdomain_of(&zone));
+		if !ns_names.is_empty() {
+			let name = ns_names.remove(0);
+			self.push_lookup(name, A::TYPE, LookupPurpose::NsAddress(ns_names));
+		}
+	}
+
+	fn handle_answer(&mut self, msg: Message) {
+		let lookup = self.lookups.last().expect("Checked by the caller");
+		if let LookupPurpose::NsAddress(_) = lookup.purpose {
+			let addresses: Vec<IpAddr> = msg.answers.iter().filter_map(|(rr, _)| match rr {
+				RR::A(a) if a.name == lookup.name => Some(IpAddr::from(a.address)),
+				RR::AAAA(aaaa) if aaaa.name == lookup.name => Some(IpAddr::from(aaaa.address)),
+				_ => None,
+			}).collect();
+			if addresses.is_empty() {
+				self.fail_lookup();
+				return;
+			}
+			self.lookups.pop();
+			let referred = self.lookups.last_mut().expect("NsAddress lookups always have a parent");
+			referred.servers = addresses.clone();
+			referred.next_server = 0;
+			let zone = referred.zone.clone();
+			self.add_zone_cut(zone, addresses);
+			return;
+		}
+
+		let lookup = self.lookups.pop().expect("Checked above");
+		// Follow any CNAMEs to the final name, which we need to look up separately if the server
+		// didn't include it (e.g. because it is in a different zone).
+		let mut target = lookup.name.clone();
+		if lookup.ty != CName::TYPE {
+			for _ in 0..msg.answers.len() {
+				let next = msg.answers.iter().find_map(|(rr, _)| match rr {
+					RR::CName(cname) if cname.name == target => Some(cname.canonical_name.clone()),
+					_ => None,
+				});
+				if let Some(next) = next { target = next; } else { break; }
+			}
+		}
+		let have_target = msg.answers.iter()
+			.any(|(rr, _)| *rr.name() == target && Record::ty(rr) == lookup.ty);
+
+		if !have_target { self.queue(target, lookup.ty); }
+		self.write_records(msg);
+	}
+
+	/// Adds the relevant records from `msg` to the proof and queues lookups for the DNSKEY and DS
+	/// records of their signers.
+	fn write_records(&mut self, msg: Message) {
+		let mut rrsig_key_names = Vec::new();
+		let min_ttl = write_proof_records(msg, &mut self.proof, &mut rrsig_key_names);
+		self.min_ttl = cmp::min(self.min_ttl, min_ttl);
+
+		rrsig_key_names.sort_unstable();
+		rrsig_key_names.dedup();
+		for key_name in rrsig_key_names {
+			self.queue(key_name.clone(), DnsKey::TYPE);
+			if key_name.as_str() != "." {
+				self.queue(key_name, DS::TYPE);
+			}
+		}
+	}
+
+	/// Finalizes the proof, if one is available, and returns it as well as the TTL that should be
+	/// used to cache the proof (i.e. the lowest TTL of all records which were used to build the
+	/// proof).
+	pub fn finish_proof(self) -> Result<(Vec<u8>, u32), ()> {
+		if self.failed || !self.lookups.is_empty() || !self.pending.is_empty() {
+			Err(())
+		} else {
+			Ok((self.proof, self.min_ttl))
+		}
+	}
+}
+
+fn exchange(server: SocketAddr, query: &QueryBuf) -> Result<QueryBuf, Error> {
+	let mut transport = UdpTransport::connect(server)?;
+	transport.send_query(query)?;
+	transport.read_response()
+}
+
+#[cfg(feature = "tokio")]
+async fn exchange_async(server: SocketAddr, query: &QueryBuf) -> Result<QueryBuf, Error> {
+	let mut transport = TokioUdpTransport::connect(server).await?;
+	transport.send_query(query).await?;
+	transport.read_response().await
+}
+
+/// Builds one combined DNSSEC proof for records of each of the given (name, type) pairs by
+/// iteratively querying authoritative servers over UDP, starting from the root servers at
+/// `root_hints` (e.g. [`ROOT_HINTS`]), returning the proof as well as the TTL for the proof.
+///
+/// Any DNSKEY and DS records required by more than one of the queries are only fetched once.
+///
+/// All servers are queried on the given `port`, which should generally be 53. Any query for
+/// which a server's response is truncated is retried over TCP.
+///
+/// You can find constants for supported standard types in the [`crate::rr`] module.
+///
+/// Note that this proof is NOT verified in any way, you need to use the [`crate::validation`]
+/// module to validate the records contained.
+pub fn build_proof_iterative(root_hints: &[IpAddr], port: u16, queries: &[(Name, u16)])
+-> Result<(Vec<u8>, u32), Error> {
+	build_proof_iterative_with(root_hints, queries, |server| SocketAddr::new(server, port))
+}
+
+/// Builds a proof as [`build_proof_iterative`] does, sending each query to the socket address
+/// `server_addr` maps the server's address to.
+fn build_proof_iterative_with<F>(root_hints: &[IpAddr], queries: &[(Name, u16)], server_addr: F)
+-> Result<(Vec<u8>, u32), Error>
+where F: Fn(IpAddr) -> SocketAddr {
+	let mut builder = IterativeProofBuilder::new_multi(queries, root_hints);
+	while let Some((query, server)) = builder.next_query() {
+		match exchange(server_addr(server), &query) {
+			Ok(response) => { let _ = builder.process_response(&response); },
+			Err(_) => builder.server_failed(),
+		}
+	}
+	builder.finish_proof()
+		.map_err(|()| Error::new(ErrorKind::Other, "Failed to resolve the records required"))
+}
+
+/// Builds one combined DNSSEC proof for records of each of the given (name, type) pairs by
+/// iteratively querying authoritative servers over UDP, starting from the root servers at
+/// `root_hints` (e.g. [`ROOT_HINTS`]), returning the proof as well as the TTL for the proof.
+///
+/// Any DNSKEY and DS records required by more than one of the queries are only fetched once.
+///
+/// All servers are queried on the given `port`, which should generally be 53. Any query for
+/// which a server's response is truncated is retried over TCP.
+///
+/// You can find constants for supported standard types in the [`crate::rr`] module.
+///
+/// Note that this proof is NOT verified in any way, you need to use the [`crate::validation`]
+/// module to validate the records contained.
+#[cfg(feature = "tokio")]
+pub async fn build_proof_iterative_async(root_hints: &[IpAddr], port: u16, queries: &[(Name, u16)])
+-> Result<(Vec<u8>, u32), Error> {
+	build_proof_iterative_with_async(root_hints, queries, |server| SocketAddr::new(server, port)).await
+}
+
+/// Builds a proof as [`build_proof_iterative_async`] does, sending each query to the socket
+/// address `server_addr` maps the server's address to.
+#[cfg(feature = "tokio")]
+async fn build_proof_iterative_with_async<F>(root_hints: &[IpAddr], queries: &[(Name, u16)], server_addr: F)
+-> Result<(Vec<u8>, u32), Error>
+where F: Fn(IpAddr) -> SocketAddr {
+	let mut builder = IterativeProofBuilder::new_multi(queries, root_hints);
+	while let Some((query, server)) = builder.next_query() {
+		match exchange_async(server_addr(server), &query).await {
+			Ok(response) => { let _ = builder.process_response(&response); },
+			Err(_) => builder.server_failed(),
+		}
+	}
+	builder.finish_proof()
+		.map_err(|()| Error::new(ErrorKind::Other, "Failed to resolve the records required"))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	#[cfg(feature = "validation")]
+	use crate::ser::parse_rr_stream;
+	#[cfg(feature = "validation")]
+	use crate::validation::{verify_rr_stream_with_anchors, verify_non_existence_with_anchors};
+	#[cfg(feature = "validation")]
+	use crate::validation::test_utils::{TestZoneKey, test_anchors};
+
+	#[cfg(feature = "validation")]
+	use std::net::UdpSocket;
+	#[cfg(feature = "validation")]
+	use std::string::{String, ToString};
+
+	/// The zones served by our fake authoritative servers, with the address of each server and the
+	/// zone's records other than its keys and signatures, which are added by [`signed_zones`]. The
+	/// servers actually all run on localhost, with queries to these (documentation) addresses
+	/// redirected to them by [`fake_server_addr`].
+	///
+	/// The root delegates `com.` and `org.` with glue, while `com.` delegates `example.com.` to a
+	/// name server in `org.` without glue which only has an IPv6 address. `alias.example.com.` is a
+	/// CNAME to a name in `org.`. Only `example.com.` has NSEC records, allowing its servers to
+	/// deny names.
+	#[cfg(feature = "validation")]
+	const ZONES: [(&str, &str, &[&str]); 4] = [
+		(".", "192.0.2.1", &[
+			"com. IN NS ns.com.",
+			"ns.com. IN A 192.0.2.2",
+			"org. IN NS ns.org.",
+			"ns.org. IN A 192.0.2.3",
+		]),
+		("com.", "192.0.2.2", &[
+			"example.com. IN NS ns.example.org.",
+		]),
+		("org.", "192.0.2.3", &[
+			"ns.example.org. IN AAAA 2001:db8::4",
+			"target.example.org. IN TXT \"target\"",
+		]),
+		("example.com.", "2001:db8::4", &[
+			"example.com. IN TXT \"stand-in\"",
+			"alias.example.com. IN CNAME target.example.org.",
+			"example.com. IN NSEC alias.example.com. TXT RRSIG NSEC DNSKEY",
+			"alias.example.com. IN NSEC example.com. CNAME RRSIG NSEC",
+		]),
+	];
+
+	/// Signs each of the [`ZONES`] with a new [`TestZoneKey`] (or, for the root, the
+	/// [`TestZoneKey::root`] trusted by the [`test_anchors`]), adding the zone's `DNSKEY`, the `DS`
+	/// records for its children and `RRSIG`s over all its authoritative records.
+	#[cfg(feature = "validation")]
+	fn signed_zones() -> Vec<(&'static str, IpAddr, Vec<RR>)> {
+		let keys: Vec<TestZoneKey> = ZONES.iter().map(|(zone, _, _)| {
+			if *zone == "." { TestZoneKey::root() } else { TestZoneKey::generate(zone) }
+		}).collect();
+		ZONES.iter().zip(keys.iter()).map(|((zone, addr, records), key)| {
+			let records: Vec<RR> = records.iter().map(|rr| rr.parse().unwrap()).collect();
+			let delegations: Vec<&Name> = records.iter()
+				.filter(|rr| matches!(rr, RR::NS(_)) && rr.name().as_str() != *zone)
+				.map(|rr| rr.name()).collect();
+			let mut signed = key.dnskey_rrs();
+			for child in delegations.iter() {
+				signed.extend(key.delegate(keys.iter().find(|k| k.dnskey.name == **child).unwrap()));
+			}
+			for rr in records.iter() {
+				signed.push(rr.clone());
+				// Each of our RRSets is a single record, and records at or below a delegation (the
+				// NS records themselves and glue) aren't authoritative, so aren't signed.
+				if !delegations.iter().any(|child| rr.name().is_subdomain_of(child)) {
+					signed.push(key.sign(core::slice::from_ref(rr)));
+				}
+			}
+			(*zone, addr.parse().unwrap(), signed)
+		}).collect()
+	}
+
+	#[cfg(feature = "validation")]
+	fn is_nsec(rr: &RR) -> bool {
+		matches!(rr, RR::NSec(_)) || matches!(rr, RR::RRSig(sig) if sig.ty == NSec::TYPE)
+	}
+
+	/// Builds a fake authoritative server's response to `query` given its zone and records.
+	#[cfg(feature = "validation")]
+	fn respond(zone: &str, records: &[RR], query: &[u8]) -> Vec<u8> {
+		let query = Message::parse(query).unwrap();
+		assert_eq!(query.flags & FLAG_RECURSION_DESIRED, 0);
+		assert!(query.edns.as_ref().unwrap().dnssec_ok);
+		let (name, ty) = (&query.questions[0].name, query.questions[0].ty);
+		let zone: Name = zone.try_into().unwrap();
+
+		let mut resp = Message {
+			id: query.id, flags: FLAG_RESPONSE | FLAG_AUTHORITATIVE, rcode: RCODE_NOERROR,
+			questions: query.questions.clone(), answers: Vec::new(), authorities: Vec::new(),
+			additionals: Vec::new(), edns: query.edns.clone(),
+		};
+		let covers = |rr: &RR, ty: u16| match rr {
+			RR::RRSig(rrsig) => rrsig.ty == ty,
+			_ => Record::ty(rr) == ty,
+		};
+		let is_delegation = |rr: &RR| matches!(rr, RR::NS(ns) if ns.name != zone);
+		for rr in records.iter().filter(|rr| rr.name() == name && !is_delegation(rr)) {
+			if covers(rr, ty) || covers(rr, CName::TYPE) {
+				resp.answers.push((rr.clone(), 3600));
+			}
+		}
+		if resp.answers.is_empty() {
+			for rr in records.iter().filter(|rr| is_delegation(rr) && name.is_subdomain_of(rr.name())) {
+				let ns = if let RR::NS(ns) = rr { ns } else { unreachable!() };
+				resp.flags &= !FLAG_AUTHORITATIVE;
+				resp.authorities.push((rr.clone(), 3600));
+				for glue in records.iter().filter(|glue| *glue.name() == ns.name_server) {
+					resp.additionals.push((glue.clone(), 3600));
+				}
+			}
+		}
+		if resp.answers.is_empty() && resp.flags & FLAG_AUTHORITATIVE != 0 {
+			// Our zones are small enough that we can simply include all NSEC records as proof.
+			for rr in records.iter().filter(|rr| is_nsec(rr)) {
+				resp.authorities.push((rr.clone(), 3600));
+			}
+			if !records.iter().any(|rr| rr.name() == name) { resp.rcode = RCODE_NXDOMAIN; }
+		}
+		resp.serialize().unwrap()
+	}
+
+	/// Spawns fake authoritative servers for the given `zones` on UDP, each on its own port on
+	/// localhost, returning the address each fake server actually listens on.
+	#[cfg(feature = "validation")]
+	fn spawn_fake_authoritative_servers(zones: &[(&'static str, IpAddr, Vec<RR>)]) -> Vec<(IpAddr, SocketAddr)> {
+		let mut servers = Vec::new();
+		for (zone, addr, records) in zones.iter() {
+			let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+			servers.push((*addr, socket.local_addr().unwrap()));
+			let (zone, records) = (*zone, records.clone());
+			std::thread::spawn(move || {
+				let mut buf = [0; 1232];
+				loop {
+					let (len, peer) = socket.recv_from(&mut buf).unwrap();
+					socket.send_to(&respond(zone, &records, &buf[..len]), peer).unwrap();
+				}
+			});
+		}
+		servers
+	}
+
+	/// Maps the address of one of our fake servers to the address it actually listens on.
+	#[cfg(feature = "validation")]
+	fn fake_server_addr(servers: &[(IpAddr, SocketAddr)], server: IpAddr) -> SocketAddr {
+		servers.iter().find(|(addr, _)| *addr == server).expect("Only our fake servers are queried").1
+	}
+
+	#[cfg(feature = "validation")]
+	fn proof_records(proof: &[u8]) -> Vec<String> {
+		let mut records: Vec<String> =
+			parse_rr_stream(proof).unwrap().iter().map(|rr| rr.to_string()).collect();
+		records.sort_unstable();
+		records.dedup();
+		records
+	}
+
+	#[cfg(feature = "validation")]
+	fn expected_records(zones: &[(&str, IpAddr, Vec<RR>)], names: &[(&str, &str)]) -> Vec<String> {
+		let mut records = Vec::new();
+		for (zone, name) in names {
+			let zone = zones.iter().find(|(z, _, _)| z == zone).unwrap();
+			for rr in zone.2.iter().filter(|rr| rr.name().as_str() == *name) {
+				if !matches!(rr, RR::NS(_) | RR::A(_)) && !is_nsec(rr) { records.push(rr.to_string()); }
+			}
+		}
+		records.sort_unstable();
+		records
+	}
+
+	/// Checks that `proof` validates from the [`test_anchors`] and that `name` resolves to exactly
+	/// the `expected` record.
+	#[cfg(feature = "validation")]
+	fn assert_proof_resolves(proof: &[u8], name: &Name, expected: &str) {
+		let rrs = parse_rr_stream(proof).unwrap();
+		let verified = verify_rr_stream_with_anchors(&rrs, &test_anchors()).unwrap();
+		let expected: RR = expected.parse().unwrap();
+		assert_eq!(verified.resolve_name(name), [&expected]);
+	}
+
+	#[test]
+	#[cfg(feature = "validation")]
+	fn test_iterative_stand_in() {
+		let zones = signed_zones();
+		let servers = spawn_fake_authoritative_servers(&zones);
+		let root_hints = [IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))];
+		let build = |queries: &[(Name, u16)]| {
+			build_proof_iterative_with(&root_hints, queries, |server| fake_server_addr(&servers, server))
+		};
+
+		let query_name: Name = "example.com.".try_into().unwrap();
+		let (proof, ttl) = build(&[(query_name.clone(), Txt::TYPE)]).unwrap();
+		assert_eq!(ttl, 3600);
+		assert_eq!(proof_records(&proof),
+			expected_records(&zones, &[(".", "."), (".", "com."), ("com.", "com."),
+				("com.", "example.com."), ("example.com.", "example.com.")]));
+		assert_proof_resolves(&proof, &query_name, "example.com. IN TXT \"stand-in\"");
+
+		// A CNAME into another zone is followed, requiring the other zone's keys.
+		let query_name: Name = "alias.example.com.".try_into().unwrap();
+		let (proof, _) = build(&[(query_name.clone(), Txt::TYPE)]).unwrap();
+		let mut expected =
+			expected_records(&zones, &[(".", "."), (".", "com."), ("com.", "com."),
+				("com.", "example.com."), ("example.com.", "example.com."),
+				("example.com.", "alias.example.com."), (".", "org."), ("org.", "org."),
+				("org.", "target.example.org.")]);
+		expected.retain(|rr| {
+			!rr.starts_with("example.com. IN TXT") && !rr.starts_with("example.com. IN RRSIG TXT")
+		});
+		assert_eq!(proof_records(&proof), expected);
+		assert_proof_resolves(&proof, &query_name, "target.example.org. IN TXT \"target\"");
+
+		// Records which don't exist are proven not to exist by the zone's NSEC records.
+		let query_name: Name = "missing.example.com.".try_into().unwrap();
+		let (proof, _) = build(&[(query_name.clone(), Txt::TYPE)]).unwrap();
+		let mut expected =
+			expected_records(&zones, &[(".", "."), (".", "com."), ("com.", "com."),
+				("com.", "example.com."), ("example.com.", "example.com.")]);
+		expected.retain(|rr| {
+			!rr.starts_with("example.com. IN TXT") && !rr.starts_with("example.com. IN RRSIG TXT")
+		});
+		let zone = zones.iter().find(|(z, _, _)| *z == "example.com.").unwrap();
+		expected.extend(zone.2.iter().filter(|rr| is_nsec(rr)).map(|rr| rr.to_string()));
+		expected.sort_unstable();
+		assert_eq!(proof_records(&proof), expected);
+		let rrs = parse_rr_stream(&proof).unwrap();
+		verify_non_existence_with_anchors(&rrs, &query_name, Txt::TYPE, &test_anchors()).unwrap();
+
+		// ...but without NSEC records, they can't be proven.
+		let query_name: Name = "missing.example.org.".try_into().unwrap();
+		assert!(build(&[(query_name.clone(), Txt::TYPE)]).is_err());
+
+		// Several queries are combined into one proof, sharing the keys they have in common.
+		let queries: [(Name, u16); 2] = [
+			("example.com.".try_into().unwrap(), Txt::TYPE),
+			("target.example.org.".try_into().unwrap(), Txt::TYPE),
+		];
+		let (proof, _) = build(&queries).unwrap();
+		let proof_rrs = parse_rr_stream(&proof).unwrap();
+		let root_dnskeys = proof_rrs.iter()
+			.filter(|rr| matches!(rr, RR::DnsKey(_)) && rr.name().as_str() == ".").count();
+		assert_eq!(root_dnskeys, 1);
+		assert_eq!(proof_records(&proof),
+			expected_records(&zones, &[(".", "."), (".", "com."), ("com.", "com."),
+				("com.", "example.com."), ("example.com.", "example.com."), (".", "org."),
+				("org.", "org."), ("org.", "target.example.org.")]));
+		assert_proof_resolves(&proof, &queries[0].0, "example.com. IN TXT \"stand-in\"");
+		assert_proof_resolves(&proof, &queries[1].0, "target.example.org. IN TXT \"target\"");
+	}
+
+	#[cfg(feature = "tokio")]
+	use tokio_crate as tokio;
+
+	#[cfg(all(feature = "tokio", feature = "validation"))]
+	#[tokio::test]
+	async fn test_iterative_stand_in_async() {
+		let zones = signed_zones();
+		let servers = spawn_fake_authoritative_servers(&zones);
+		let root_hints = [IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))];
+		let query_name: Name = "example.com.".try_into().unwrap();
+		let (proof, _) =
+			build_proof_iterative_with_async(&root_hints, &[(query_name.clone(), Txt::TYPE)], |server| {
+				fake_server_addr(&servers, server)
+			}).await.unwrap();
+		assert_eq!(proof_records(&proof),
+			expected_records(&zones, &[(".", "."), (".", "com."), ("com.", "com."),
+				("com.", "example.com."), ("example.com.", "example.com.")]));
+		assert_proof_resolves(&proof, &query_name, "example.com. IN TXT \"stand-in\"");
+	}
+
+	#[test]
+	fn test_server_failover() {
+		let hints = [IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2))];
+		let mut builder = IterativeProofBuilder::new(&"example.com.".try_into().unwrap(), Txt::TYPE, &hints);
+		let (query, server) = builder.next_query().unwrap();
+		assert_eq!(server, hints[0]);
+		builder.server_failed();
+		let (retry, server) = builder.next_query().unwrap();
+		assert_eq!((&retry[..], server), (&query[..], hints[1]));
+
+		// Garbage and responses to other queries are rejected, moving on to the next server.
+		assert!(builder.process_response(&query).is_err());
+		assert!(builder.next_query().is_none());
+		assert!(builder.finish_proof().is_err());
+	}
+}
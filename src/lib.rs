@@ -18,9 +18,16 @@
 //!  * By default, the `validate` feature is set, using `ring` to validate DNSSEC signatures and
 //!    proofs using the [`validation`] module.
 //!  * The `std` feature enables the [`query`] module, allowing for the building of proofs by
-//!    querying a recursive resolver over TCP.
+//!    querying a recursive resolver over TCP, and the [`iterative`] module, building proofs by
+//!    querying authoritative servers directly, starting from the root servers.
 //!  * The `tokio` feature further enables async versions of the [`query`] methods, doing the same
 //!    querying async using `tokio`'s TCP streams.
+//!  * The `dot` feature enables querying recursive resolvers over DNS-over-TLS using `rustls` in
+//!    the [`query`] module.
+//!  * The `doh` feature further enables querying recursive resolvers over DNS-over-HTTPS.
+//!  * The `serde` feature implements `serde`'s `Serialize` and `Deserialize` for [`rr::Name`],
+//!    all resource record types and `validation::OwnedVerifiedRRStream`, using the same JSON
+//!    shape as [`rr::RR::json`].
 //!  * Finally, the crate can be built as a binary using the `build_server` feature, responding to
 //!    queries over HTTP GET calls to `/dnssecproof?d=domain.name.&t=RecordType` with DNSSEC
 //!    proofs.
@@ -28,6 +35,9 @@
 //! Note that this library's MSRV is 1.64 for normal building, however builds fine on 1.63 (and
 //! possibly earlier) when `RUSTC_BOOTSTRAP=1` is set, as it relies on the
 //! `const_slice_from_raw_parts` feature.
+//!
+//! The `dot` and `doh` features are exempt from this MSRV, as the `rustls` versions they rely on
+//! require rustc 1.71 or later.
 
 #![deny(missing_docs)]
 #![deny(rustdoc::broken_intra_doc_links)]
@@ -54,8 +64,12 @@ extern crate alloc;
 // Note that this is duplicated exactly in src/http.rs
 pub const MAX_PROOF_STEPS: usize = 20;
 
-#[cfg(feature = "validation")]
 mod base32;
+pub mod base64;
+pub mod hex;
+mod idna;
+#[cfg(feature = "serde")]
+mod serde_utils;
 
 #[cfg(all(feature = "validation", any(fuzzing, dnssec_validate_bench)))]
 pub mod crypto;
@@ -64,7 +78,12 @@ mod crypto;
 
 pub mod rr;
 pub mod ser;
+pub mod message;
+pub mod multipart;
 pub mod query;
+#[cfg(feature = "std")]
+pub mod iterative;
+pub mod zonefile;
 
 #[cfg(feature = "validation")]
 pub mod validation;
@@ -0,0 +1,262 @@
+//! A model of full DNS messages, allowing queries and responses to be inspected and built when
+//! using custom transports.
+
+use alloc::vec::Vec;
+
+use crate::rr::*;
+use crate::ser::*;
+
+/// The record type of the EDNS OPT pseudo-record, from RFC 6891.
+pub(crate) const OPT_TYPE: u16 = 41;
+
+/// The "QR" header flag, set in responses.
+pub const FLAG_RESPONSE: u16 = 0x8000;
+/// The "AA" header flag, set in responses from a server authoritative for the queried name.
+pub const FLAG_AUTHORITATIVE: u16 = 0x0400;
+/// The "TC" header flag, set when a response was truncated to fit in a UDP packet.
+pub const FLAG_TRUNCATED: u16 = 0x0200;
+/// The "RD" header flag, set in queries to request recursive resolution.
+pub const FLAG_RECURSION_DESIRED: u16 = 0x0100;
+/// The "RA" header flag, set in responses from a server which supports recursion.
+pub const FLAG_RECURSION_AVAILABLE: u16 = 0x0080;
+/// The "AD" header flag, set in responses when the resolver validated the answer with DNSSEC.
+pub const FLAG_AUTHENTICATED_DATA: u16 = 0x0020;
+/// The "CD" header flag, set in queries to disable DNSSEC validation by the resolver.
+pub const FLAG_CHECKING_DISABLED: u16 = 0x0010;
+
+/// The response code indicating success.
+pub const RCODE_NOERROR: u16 = 0;
+/// The response code indicating the server failed to process the query.
+pub const RCODE_SERVFAIL: u16 = 2;
+/// The response code indicating the queried name does not exist.
+pub const RCODE_NXDOMAIN: u16 = 3;
+
+/// A single option in an EDNS OPT pseudo-record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EdnsOption {
+	/// The option code, as maintained by IANA.
+	pub code: u16,
+	/// The option's data.
+	pub data: Vec<u8>,
+}
+
+/// The EDNS data carried in a message's OPT pseudo-record, as described in RFC 6891.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edns {
+	/// The largest UDP payload the sender can receive.
+	pub udp_payload_size: u16,
+	/// The EDNS version, currently always 0.
+	pub version: u8,
+	/// The "DO" flag, set to request DNSSEC records in responses.
+	pub dnssec_ok: bool,
+	/// Any options included in the OPT record.
+	pub options: Vec<EdnsOption>,
+}
+
+/// A question in a DNS message, which is always in the INternet class.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Question {
+	/// The name being queried.
+	pub name: Name,
+	/// The type of records being queried.
+	pub ty: u16,
+}
+
+/// A full DNS message, i.e. a query or a response.
+///
+/// Records in the answer, authority and additional sections are stored alongside their TTLs.
+/// Records of types which are not supported by this crate (or which are not in the INternet
+/// class) cannot be represented and are dropped when parsing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+	/// The transaction ID.
+	pub id: u16,
+	/// The header flags (i.e. the `FLAG_*` constants in this module, as well as the opcode),
+	/// excluding the response code.
+	pub flags: u16,
+	/// The full response code, including the upper 8 bits carried in the OPT record when EDNS is
+	/// used.
+	pub rcode: u16,
+	/// The questions in the message, generally exactly one.
+	pub questions: Vec<Question>,
+	/// The records in the answer section.
+	pub answers: Vec<(RR, u32)>,
+	/// The records in the authority section.
+	pub authorities: Vec<(RR, u32)>,
+	/// The records in the additional section, excluding the OPT pseudo-record.
+	pub additionals: Vec<(RR, u32)>,
+	/// The EDNS data, if the message included an OPT pseudo-record.
+	pub edns: Option<Edns>,
+}
+
+/// Reads a record, returning `None` if it is of an unsupported type or class.
+fn read_record(inp: &mut &[u8], msg: &[u8]) -> Result<Option<(RR, u32)>, ()> {
+	let mut header = *inp;
+	read_wire_packet_name(&mut header, msg)?;
+	let ty = read_u16(&mut header)?;
+	let class = read_u16(&mut header)?;
+	if class == 1 && RR::ty_to_rr_name(ty).is_some() {
+		return parse_wire_packet_rr(inp, msg).map(Some);
+	}
+	read_u32(&mut header)?; // TTL
+	let data_len = read_u16(&mut header)? as usize;
+	if header.len() < data_len { return Err(()); }
+	*inp = &header[data_len..];
+	Ok(None)
+}
+
+fn read_section(inp: &mut &[u8], msg: &[u8], count: u16) -> Result<Vec<(RR, u32)>, ()> {
+	let mut res = Vec::with_capacity(count as usize);
+	for _ in 0..count {
+		if let Some(rr) = read_record(inp, msg)? { res.push(rr); }
+	}
+	Ok(res)
+}
+
+impl Message {
+	/// Parses a DNS message.
+	///
+	/// Fails if the message is malformed, has trailing bytes, contains a question not in the
+	/// INternet class or contains multiple OPT records.
+	pub fn parse(msg: &[u8]) -> Result<Message, ()> {
+		let mut read = msg;
+		let id = read_u16(&mut read)?;
+		let header_flags = read_u16(&mut read)?;
+		let questions = read_u16(&mut read)?;
+		let answers = read_u16(&mut read)?;
+		let authorities = read_u16(&mut read)?;
+		let additionals = read_u16(&mut read)?;
+
+		let mut res = Message {
+			id, flags: header_flags & !0xf, rcode: header_flags & 0xf,
+			questions: Vec::with_capacity(questions as usize), answers: Vec::new(),
+			authorities: Vec::new(), additionals: Vec::new(), edns: None,
+		};
+		for _ in 0..questions {
+			let name = read_wire_packet_name(&mut read, msg)?;
+			let ty = read_u16(&mut read)?;
+			if read_u16(&mut read)? != 1 { return Err(()); }
+			res.questions.push(Question { name, ty });
+		}
+		res.answers = read_section(&mut read, msg, answers)?;
+		res.authorities = read_section(&mut read, msg, authorities)?;
+
+		for _ in 0..additionals {
+			let mut header = read;
+			let name = read_wire_packet_name(&mut header, msg)?;
+			if read_u16(&mut header)? != OPT_TYPE {
+				if let Some(rr) = read_record(&mut read, msg)? { res.additionals.push(rr); }
+				continue;
+			}
+			if res.edns.is_some() || name.as_str() != "." { return Err(()); }
+			let udp_payload_size = read_u16(&mut header)?;
+			let extended_rcode = read_u8(&mut header)?;
+			let version = read_u8(&mut header)?;
+			let edns_flags = read_u16(&mut header)?;
+			let data_len = read_u16(&mut header)? as usize;
+			if header.len() < data_len { return Err(()); }
+			let mut data = &header[..data_len];
+			read = &header[data_len..];
+
+			let mut options = Vec::new();
+			while !data.is_empty() {
+				let code = read_u16(&mut data)?;
+				let len = read_u16(&mut data)? as usize;
+				if data.len() < len { return Err(()); }
+				options.push(EdnsOption { code, data: data[..len].to_vec() });
+				data = &data[len..];
+			}
+			res.rcode |= (extended_rcode as u16) << 4;
+			res.edns = Some(Edns { udp_payload_size, version, dnssec_ok: edns_flags & 0x8000 != 0, options });
+		}
+		if !read.is_empty() { return Err(()); }
+		Ok(res)
+	}
+
+	/// Serializes this message, using name compression.
+	///
+	/// Fails if the response code does not fit in 4 bits and no EDNS data is set, or if the
+	/// message is too large.
+	pub fn serialize(&self) -> Result<Vec<u8>, ()> {
+		if self.rcode > 0xf && self.edns.is_none() || self.rcode > 0xfff { return Err(()); }
+		let mut writer = MessageWriter::new(self.id, (self.flags & !0xf) | (self.rcode & 0xf));
+		for question in self.questions.iter() {
+			writer.write_question(&question.name, question.ty)?;
+		}
+		for (rr, ttl) in self.answers.iter() { writer.write_answer(rr, *ttl)?; }
+		for (rr, ttl) in self.authorities.iter() { writer.write_authority(rr, *ttl)?; }
+		for (rr, ttl) in self.additionals.iter() { writer.write_additional(rr, *ttl)?; }
+		if let Some(edns) = &self.edns {
+			writer.write_edns(edns, (self.rcode >> 4) as u8)?;
+		}
+		let res = writer.into_vec();
+		if res.len() > u16::MAX as usize { return Err(()); }
+		Ok(res)
+	}
+
+	/// Returns true if this message is a response rather than a query.
+	pub fn is_response(&self) -> bool { self.flags & FLAG_RESPONSE != 0 }
+
+	/// Returns true if this response was truncated and should be retried over TCP.
+	pub fn is_truncated(&self) -> bool { self.flags & FLAG_TRUNCATED != 0 }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use alloc::vec;
+
+	#[test]
+	fn test_message_round_trip() {
+		let rrs: Vec<RR> = [
+			"example.com. IN A 192.0.2.1",
+			"example.com. IN NS ns1.example.com.",
+			"ns1.example.com. IN AAAA 2001:db8::1",
+		].iter().map(|s| s.parse().unwrap()).collect();
+		let msg = Message {
+			id: 0x4242,
+			flags: FLAG_RESPONSE | FLAG_RECURSION_DESIRED | FLAG_RECURSION_AVAILABLE,
+			rcode: 0x123,
+			questions: vec![Question { name: "example.com.".try_into().unwrap(), ty: A::TYPE }],
+			answers: vec![(rrs[0].clone(), 300)],
+			authorities: vec![(rrs[1].clone(), 3600)],
+			additionals: vec![(rrs[2].clone(), 3600)],
+			edns: Some(Edns {
+				udp_payload_size: 1232, version: 0, dnssec_ok: true,
+				options: vec![EdnsOption { code: 10, data: vec![1, 2, 3, 4, 5, 6, 7, 8] }],
+			}),
+		};
+		let bytes = msg.serialize().unwrap();
+		assert_eq!(&bytes[..12], &[0x42, 0x42, 0x81, 0x83, 0, 1, 0, 1, 0, 1, 0, 2]);
+		let parsed = Message::parse(&bytes).unwrap();
+		assert_eq!(parsed, msg);
+		assert!(parsed.is_response());
+		assert!(!parsed.is_truncated());
+
+		let mut no_edns = msg.clone();
+		no_edns.edns = None;
+		assert!(no_edns.serialize().is_err());
+		no_edns.rcode = RCODE_NXDOMAIN;
+		assert_eq!(Message::parse(&no_edns.serialize().unwrap()).unwrap(), no_edns);
+
+		assert!(Message::parse(&bytes[..bytes.len() - 1]).is_err());
+		let mut trailing = bytes.clone();
+		trailing.push(0);
+		assert!(Message::parse(&trailing).is_err());
+	}
+
+	#[test]
+	fn test_unsupported_records() {
+		let mut msg = vec![0, 0, 0x81, 0x80, 0, 1, 0, 2, 0, 0, 0, 0];
+		msg.extend_from_slice(b"\x07example\x03com\x00\x00\x0f\x00\x01");
+		// An MX record, which we don't support, using a compressed name
+		msg.extend_from_slice(&[0xc0, 12, 0, 15, 0, 1, 0, 0, 0, 60, 0, 4, 0, 10, 0xc0, 12]);
+		msg.extend_from_slice(&[0xc0, 12, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 192, 0, 2, 1]);
+		let parsed = Message::parse(&msg).unwrap();
+		assert_eq!(parsed.questions, [Question { name: "example.com.".try_into().unwrap(), ty: 15 }]);
+		assert_eq!(parsed.answers, [("example.com. IN A 192.0.2.1".parse().unwrap(), 60)]);
+		assert_eq!(parsed.rcode, RCODE_NOERROR);
+		assert!(parsed.edns.is_none());
+	}
+}
@@ -0,0 +1,225 @@
+//! A chunked, checksummed text format allowing a large proof to be split across several frames
+//! (e.g. animated QR codes) and reassembled before being passed to [`crate::ser::parse_rr_stream`].
+//!
+//! Each frame has the form `DNSSEC:<index>/<total>:<checksum>:<data>`, where `index` and `total`
+//! are decimal, with `index` starting at 1, `checksum` is the CRC-32 of the full payload in eight
+//! uppercase hex characters and `data` is this frame's chunk of the payload in unpadded base32hex.
+//! Every character used is in the QR alphanumeric character set, allowing for compact QR codes.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use crate::base32;
+
+const FRAME_PREFIX: &str = "DNSSEC:";
+
+/// The maximum number of frames a payload may be split into.
+///
+/// At 256 bytes per frame, this is enough for the largest proof which fits in an RFC 9102 TLS
+/// extension (64 KiB), while bounding the memory a [`Reassembler`] allocates for an untrusted
+/// frame's `total`.
+pub const MAX_FRAMES: usize = 256;
+
+/// The CRC-32 (as used by e.g. Ethernet and zlib) of `data`.
+fn crc32(data: &[u8]) -> u32 {
+	let mut crc = 0xffff_ffffu32;
+	for b in data {
+		crc ^= *b as u32;
+		for _ in 0..8 {
+			crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+		}
+	}
+	!crc
+}
+
+fn decimal_len(mut v: usize) -> usize {
+	let mut len = 1;
+	while v >= 10 { v /= 10; len += 1; }
+	len
+}
+
+/// Splits `data` into frames which are each at most `max_frame_len` characters long.
+///
+/// Fails if `max_frame_len` is too short to fit the frame header and at least some data, or if
+/// more than [`MAX_FRAMES`] frames would be required.
+pub fn split(data: &[u8], max_frame_len: usize) -> Result<Vec<String>, ()> {
+	let mut total = 1;
+	let bytes_per_frame = loop {
+		// Prefix, index and total, two separators, a slash and the checksum
+		let header_len = FRAME_PREFIX.len() + decimal_len(total) * 2 + 3 + 8;
+		// Only use whole base32 groups so that each chunk can be decoded independently.
+		let groups = max_frame_len.saturating_sub(header_len) / 8;
+		if groups == 0 { return Err(()); }
+		let bytes_per_frame = groups * 5;
+		let needed = core::cmp::max(1, (data.len() + bytes_per_frame - 1) / bytes_per_frame);
+		if decimal_len(needed) <= decimal_len(total) { total = needed; break bytes_per_frame; }
+		total = needed;
+	};
+	if total > MAX_FRAMES { return Err(()); }
+
+	let checksum = crc32(data);
+	let mut res = Vec::with_capacity(total);
+	for idx in 0..total {
+		let start = core::cmp::min(idx * bytes_per_frame, data.len());
+		let end = core::cmp::min(start + bytes_per_frame, data.len());
+		let mut frame = String::with_capacity(max_frame_len);
+		write!(&mut frame, "{}{}/{}:{:08X}:", FRAME_PREFIX, idx + 1, total, checksum)
+			.expect("Writing to a String cannot fail");
+		frame += &base32::encode(&data[start..end]);
+		debug_assert!(frame.len() <= max_frame_len);
+		res.push(frame);
+	}
+	Ok(res)
+}
+
+/// Reassembles the payload from frames generated by [`split`], which may be added in any order.
+pub struct Reassembler {
+	checksum: u32,
+	parts: Vec<Option<Vec<u8>>>,
+}
+
+impl Reassembler {
+	/// Creates a new, empty, reassembler.
+	pub fn new() -> Self {
+		Reassembler { checksum: 0, parts: Vec::new() }
+	}
+
+	/// Adds a frame.
+	///
+	/// Fails if the frame is malformed, claims the payload has more than [`MAX_FRAMES`] frames or
+	/// does not belong to the same payload as previously-added frames. Frames which were already
+	/// added are ignored.
+	pub fn add_frame(&mut self, frame: &str) -> Result<(), ()> {
+		let frame = frame.strip_prefix(FRAME_PREFIX).ok_or(())?;
+		let mut fields = frame.splitn(3, ':');
+		let (pos, checksum, data) =
+			(fields.next().ok_or(())?, fields.next().ok_or(())?, fields.next().ok_or(())?);
+		let (idx, total) = pos.split_once('/').ok_or(())?;
+		let idx: usize = idx.parse().map_err(|_| ())?;
+		let total: usize = total.parse().map_err(|_| ())?;
+		if idx == 0 || idx > total || total > MAX_FRAMES { return Err(()); }
+		if checksum.len() != 8 { return Err(()); }
+		let checksum = u32::from_str_radix(checksum, 16).map_err(|_| ())?;
+		let data = base32::decode(data)?;
+
+		if self.parts.is_empty() {
+			self.checksum = checksum;
+			self.parts.resize(total, None);
+		} else if self.parts.len() != total || self.checksum != checksum {
+			return Err(());
+		}
+		match &self.parts[idx - 1] {
+			Some(existing) if *existing != data => Err(()),
+			Some(_) => Ok(()),
+			None => { self.parts[idx - 1] = Some(data); Ok(()) },
+		}
+	}
+
+	/// Returns true once all frames have been added.
+	pub fn is_complete(&self) -> bool {
+		!self.parts.is_empty() && self.parts.iter().all(|part| part.is_some())
+	}
+
+	/// Returns the number of distinct frames added so far and the total number of frames, if any
+	/// frame has been added.
+	pub fn progress(&self) -> (usize, usize) {
+		(self.parts.iter().filter(|part| part.is_some()).count(), self.parts.len())
+	}
+
+	/// Returns the reassembled payload.
+	///
+	/// Fails if some frames are missing or the payload does not match its checksum.
+	pub fn finish(self) -> Result<Vec<u8>, ()> {
+		if !self.is_complete() { return Err(()); }
+		let mut res = Vec::new();
+		for part in self.parts {
+			res.extend_from_slice(&part.expect("Checked in is_complete"));
+		}
+		if crc32(&res) != self.checksum { return Err(()); }
+		Ok(res)
+	}
+}
+
+impl Default for Reassembler {
+	fn default() -> Self { Self::new() }
+}
+
+/// Reassembles the payload from a full set of frames generated by [`split`], in any order.
+pub fn join<'a, I: IntoIterator<Item = &'a str>>(frames: I) -> Result<Vec<u8>, ()> {
+	let mut reassembler = Reassembler::new();
+	for frame in frames { reassembler.add_frame(frame)?; }
+	reassembler.finish()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_crc32() {
+		assert_eq!(crc32(b""), 0);
+		assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+	}
+
+	#[test]
+	fn test_split_join() {
+		let data = (0..1000).map(|i| (i * 7) as u8).collect::<Vec<_>>();
+		let frames = split(&data, 100).unwrap();
+		assert_eq!(frames.len(), 23);
+		assert!(frames.iter().all(|frame| frame.len() <= 100));
+		assert!(frames[0].starts_with("DNSSEC:1/23:"));
+		assert_eq!(join(frames.iter().map(|f| f.as_str())).unwrap(), data);
+
+		// Frames can be added in any order, with duplicates
+		let mut reassembler = Reassembler::new();
+		for frame in frames.iter().rev().chain(frames.iter()) {
+			reassembler.add_frame(frame).unwrap();
+		}
+		assert!(reassembler.is_complete());
+		assert_eq!(reassembler.progress(), (23, 23));
+		assert_eq!(reassembler.finish().unwrap(), data);
+
+		// Missing frames
+		let mut reassembler = Reassembler::new();
+		for frame in frames.iter().skip(1) { reassembler.add_frame(frame).unwrap(); }
+		assert_eq!(reassembler.progress(), (22, 23));
+		assert!(!reassembler.is_complete());
+		assert!(reassembler.finish().is_err());
+
+		// Frames from a different payload
+		let other = split(&data[1..], 100).unwrap();
+		let mut reassembler = Reassembler::new();
+		reassembler.add_frame(&frames[0]).unwrap();
+		assert!(reassembler.add_frame(&other[1]).is_err());
+
+		// A corrupted chunk fails the checksum
+		let mut corrupted = frames.clone();
+		let last = corrupted[22].pop().unwrap();
+		corrupted[22].push(if last == 'A' { 'B' } else { 'A' });
+		assert!(join(corrupted.iter().map(|f| f.as_str())).is_err());
+
+		assert_eq!(split(&[], 100).unwrap(), ["DNSSEC:1/1:00000000:"]);
+		assert!(join(["DNSSEC:1/1:00000000:"]).unwrap().is_empty());
+		assert!(split(&data, 30).is_err());
+		assert!(join(["DNSSEC:0/1:00000000:"]).is_err());
+		assert!(join(["DNSSEC:2/1:00000000:"]).is_err());
+		assert!(join(["PROOF:1/1:00000000:"]).is_err());
+	}
+
+	#[test]
+	fn test_frame_limit() {
+		// A frame claiming a huge total is rejected without allocating for it
+		let mut reassembler = Reassembler::default();
+		assert!(reassembler.add_frame("DNSSEC:1/18446744073709551615:00000000:").is_err());
+		assert!(reassembler.add_frame("DNSSEC:1/257:00000000:").is_err());
+		assert_eq!(reassembler.progress(), (0, 0));
+		reassembler.add_frame("DNSSEC:1/256:00000000:").unwrap();
+		assert_eq!(reassembler.progress(), (1, 256));
+
+		// And we won't split a payload into more frames than can be reassembled
+		let data = [0; 5 * MAX_FRAMES];
+		assert_eq!(split(&data, 32).unwrap().len(), MAX_FRAMES);
+		assert!(split(&[0; 5 * MAX_FRAMES + 1], 32).is_err());
+	}
+}
@@ -6,17 +6,40 @@ use alloc::vec;
 use alloc::vec::Vec;
 
 #[cfg(feature = "std")]
-use std::net::{SocketAddr, TcpStream};
+use std::net::{SocketAddr, TcpStream, UdpSocket};
 #[cfg(feature = "std")]
 use std::io::{Read, Write, Error, ErrorKind};
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+#[cfg(feature = "std")]
+use std::boxed::Box;
+#[cfg(feature = "std")]
+use std::future::Future;
+#[cfg(feature = "std")]
+use std::pin::Pin;
+#[cfg(feature = "dot")]
+use std::borrow::ToOwned;
+#[cfg(feature = "dot")]
+use std::sync::Arc;
+#[cfg(feature = "doh")]
+use std::collections::VecDeque;
+#[cfg(feature = "doh")]
+use std::fmt::Write as _;
+#[cfg(feature = "doh")]
+use std::string::String;
 
 #[cfg(feature = "tokio")]
-use tokio_crate::net::TcpStream as TokioTcpStream;
+use tokio_crate::net::{TcpStream as TokioTcpStream, UdpSocket as TokioUdpSocket};
 #[cfg(feature = "tokio")]
 use tokio_crate::io::{AsyncReadExt, AsyncWriteExt};
+#[cfg(feature = "tokio")]
+use std::task::{Context, Poll};
 
+use crate::message::*;
 use crate::rr::*;
 use crate::ser::*;
+#[cfg(feature = "validation")]
+use crate::validation::{IncrementalValidator, ValidationError};
 use crate::MAX_PROOF_STEPS;
 
 // In testing use a rather small buffer to ensure we hit the allocation paths sometimes. In
@@ -96,18 +119,38 @@ impl ops::DerefMut for QueryBuf {
 
 // We don't care about transaction IDs as we're only going to accept signed data.
 // Further, if we're querying over DoH, the RFC says we SHOULD use a transaction ID of 0 here.
-const TXID: u16 = 0;
+// The UDP transports replace this with a random ID on the wire, see `UdpTransport`.
+pub(crate) const TXID: u16 = 0;
+
+/// The UDP payload size we advertise in EDNS, as recommended by DNS Flag Day 2020 to avoid IP
+/// fragmentation.
+const EDNS_UDP_PAYLOAD_SIZE: u16 = 1232;
+/// The length of the OPT record at the end of each query built by [`build_query`].
+#[cfg(feature = "std")]
+const QUERY_OPT_LEN: usize = 11;
+
+/// How long we initially wait for a UDP response before retransmitting our outstanding queries.
+#[cfg(feature = "std")]
+const UDP_RETRANSMIT_TIMEOUT: Duration = Duration::from_secs(1);
+/// How many times we retransmit outstanding UDP queries (doubling the timeout each time) before
+/// giving up.
+#[cfg(feature = "std")]
+const UDP_RETRANSMITS: u32 = 2;
 
 fn build_query(domain: &Name, ty: u16) -> QueryBuf {
+	build_query_with_flags(domain, ty, FLAG_RECURSION_DESIRED | FLAG_AUTHENTICATED_DATA)
+}
+
+pub(crate) fn build_query_with_flags(domain: &Name, ty: u16, flags: u16) -> QueryBuf {
 	let mut query = QueryBuf::new_zeroed(0);
 	query.extend_from_slice(&TXID.to_be_bytes());
-	query.extend_from_slice(&[0x01, 0x20]); // Flags: Recursive, Authenticated Data
+	query.extend_from_slice(&flags.to_be_bytes());
 	query.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 1]); // One question, One additional
 	write_name(&mut query, domain);
 	query.extend_from_slice(&ty.to_be_bytes());
 	query.extend_from_slice(&1u16.to_be_bytes()); // INternet class
 	query.extend_from_slice(&[0, 0, 0x29]); // . OPT
-	query.extend_from_slice(&0u16.to_be_bytes()); // 0 UDP payload size
+	query.extend_from_slice(&EDNS_UDP_PAYLOAD_SIZE.to_be_bytes()); // UDP payload size
 	query.extend_from_slice(&[0, 0]); // EDNS version 0
 	query.extend_from_slice(&0x8000u16.to_be_bytes()); // Accept DNSSEC RRs
 	query.extend_from_slice(&0u16.to_be_bytes()); // No additional data
@@ -122,52 +165,50 @@ pub fn fuzz_response(response: &[u8]) {
 }
 
 fn handle_response(resp: &[u8], proof: &mut Vec<u8>, rrsig_key_names: &mut Vec<Name>) -> Result<u32, ()> {
-	let mut read: &[u8] = resp;
-	if read_u16(&mut read)? != TXID { return Err(()); }
-	// 2 byte transaction ID
-	let flags = read_u16(&mut read)?;
-	if flags & 0b1000_0000_0000_0000 == 0 {
+	let msg = Message::parse(resp)?;
+	if msg.id != TXID { return Err(()); }
+	if !msg.is_response() { return Err(()); }
+	// Require a standard query (opcode 0) which was not truncated and did not fail (though the name
+	// may not exist).
+	if msg.flags & 0b0111_1010_0000_0000 != 0 {
 		return Err(());
 	}
-	if flags & 0b0111_1010_0000_0111 != 0 {
+	if msg.rcode != RCODE_NOERROR && msg.rcode != RCODE_NXDOMAIN { return Err(()); }
+	if msg.flags & FLAG_AUTHENTICATED_DATA == 0 {
 		return Err(());
 	}
-	if flags & 0b10_0000 == 0 {
-		return Err(());
+	if msg.questions.len() != 1 { return Err(()); }
+	if msg.answers.is_empty() {
+		// A negative response is only useful if it contains a signed proof of non-existence.
+		let has_denial = msg.authorities.iter()
+			.any(|(rr, _)| matches!(rr, RR::NSec(_) | RR::NSec3(_)));
+		if !has_denial { return Err(()); }
 	}
-	let questions = read_u16(&mut read)?;
-	if questions != 1 { return Err(()); }
-	let answers = read_u16(&mut read)?;
-	if answers == 0 { return Err(()); }
-	let authorities = read_u16(&mut read)?;
-	let _additional = read_u16(&mut read)?;
 
-	for _ in 0..questions {
-		read_wire_packet_name(&mut read, resp)?;
-		read_u16(&mut read)?; // type
-		read_u16(&mut read)?; // class
-	}
+	Ok(write_proof_records(msg, proof, rrsig_key_names))
+}
 
-	// Only read the answers and NSEC records in authorities, skipping additional entirely.
+/// Writes the records from a response which should be included in a proof to `proof`, pushing
+/// the signers of any signatures to `rrsig_key_names` and returning the minimum TTL written.
+pub(crate) fn write_proof_records(msg: Message, proof: &mut Vec<u8>, rrsig_key_names: &mut Vec<Name>) -> u32 {
+	// Only read the answers and SOA and NSEC records in authorities, skipping additional entirely.
 	let mut min_ttl = u32::MAX;
-	for _ in 0..answers {
-		let (rr, ttl) = parse_wire_packet_rr(&mut read, resp)?;
+	for (rr, ttl) in msg.answers {
 		write_rr(&rr, ttl, proof);
 		min_ttl = cmp::min(min_ttl, ttl);
 		if let RR::RRSig(rrsig) = rr { rrsig_key_names.push(rrsig.key_name); }
 	}
 
-	for _ in 0..authorities {
-		// Only include records from the authority section if they are NSEC/3 (or signatures
+	for (rr, ttl) in msg.authorities {
+		// Only include records from the authority section if they are SOA or NSEC/3 (or signatures
 		// thereover). We don't care about NS records here.
-		let (rr, ttl) = parse_wire_packet_rr(&mut read, resp)?;
 		match &rr {
 			RR::RRSig(rrsig) => {
-				if rrsig.ty != NSec::TYPE && rrsig.ty != NSec3::TYPE {
+				if rrsig.ty != SOA::TYPE && rrsig.ty != NSec::TYPE && rrsig.ty != NSec3::TYPE {
 					continue;
 				}
 			},
-			RR::NSec(_)|RR::NSec3(_) => {},
+			RR::SOA(_)|RR::NSec(_)|RR::NSec3(_) => {},
 			_ => continue,
 		}
 		write_rr(&rr, ttl, proof);
@@ -175,7 +216,7 @@ fn handle_response(resp: &[u8], proof: &mut Vec<u8>, rrsig_key_names: &mut Vec<N
 		if let RR::RRSig(rrsig) = rr { rrsig_key_names.push(rrsig.key_name); }
 	}
 
-	Ok(min_ttl)
+	min_ttl
 }
 
 #[cfg(fuzzing)]
@@ -196,24 +237,39 @@ pub fn fuzz_proof_builder(mut response_stream: &[u8]) {
 /// A simple state machine which will generate a series of queries and process the responses until
 /// it has built a DNSSEC proof.
 ///
-/// A [`ProofBuilder`] driver starts with [`ProofBuilder::new`], fetching the state machine and
-/// initial query. As long as [`ProofBuilder::awaiting_responses`] returns true, responses should
-/// be read from the resolver. For each query response read from the DNS resolver,
+/// A [`ProofBuilder`] driver starts with [`ProofBuilder::new`] (or [`ProofBuilder::new_multi`] to
+/// build a combined proof for several queries), fetching the state machine and initial query.
+/// As long as [`ProofBuilder::awaiting_responses`] returns true, responses should be read from
+/// the resolver. For each query response read from the DNS resolver,
 /// [`ProofBuilder::process_response`] should be called, and each fresh query returned should be
 /// sent to the resolver. Once [`ProofBuilder::awaiting_responses`] returns false,
 /// [`ProofBuilder::finish_proof`] should be called to fetch the resulting proof.
 ///
+/// If the requested records do not exist, the proof will instead contain the signed NSEC or
+/// NSEC3 records (and the zone's SOA) from the resolver's response, which can be checked with
+/// [`crate::validation::verify_non_existence`].
+///
+/// By default the responses are not checked at all until the final proof is validated. With the
+/// `validation` feature, [`ProofBuilder::enable_validation`] can be used to check signatures as
+/// the proof is built, giving up as soon as the resolver provides bogus data.
+///
 /// To build a DNSSEC proof using a DoH server, take each [`QueryBuf`], encode it as base64url, and
 /// make a query to `https://doh-server/endpoint?dns=base64url_encoded_query` with an `Accept`
 /// header of `application/dns-message`. Each response, in raw binary, can be fed directly into
-/// [`ProofBuilder::process_response`].
+/// [`ProofBuilder::process_response`]. With the `doh` feature enabled, `DohTransport` implements
+/// this for you.
 #[derive(Clone)]
 pub struct ProofBuilder {
+	queries: Vec<(Name, u16)>,
 	proof: Vec<u8>,
 	min_ttl: u32,
 	dnskeys_requested: Vec<Name>,
 	pending_queries: usize,
 	queries_made: usize,
+	#[cfg(feature = "validation")]
+	validator: Option<IncrementalValidator>,
+	#[cfg(feature = "validation")]
+	bogus_zone: Option<(Name, ValidationError)>,
 }
 
 impl ProofBuilder {
@@ -226,33 +282,98 @@ impl ProofBuilder {
 	///
 	/// You can find constants for supported standard types in the [`crate::rr`] module.
 	pub fn new(name: &Name, ty: u16) -> (ProofBuilder, QueryBuf) {
-		let initial_query = build_query(name, ty);
+		let (builder, mut initial_queries) = Self::new_multi(&[(name.clone(), ty)]);
+		(builder, initial_queries.pop().expect("One query was provided"))
+	}
+
+	/// Constructs a new [`ProofBuilder`] which will build one combined proof for all of the given
+	/// (name, type) pairs, and the initial queries to send to the recursive resolver to begin the
+	/// proof building process.
+	///
+	/// Any DNSKEY and DS records required by more than one of the queries are only fetched once.
+	pub fn new_multi(queries: &[(Name, u16)]) -> (ProofBuilder, Vec<QueryBuf>) {
+		let mut unique_queries: Vec<(Name, u16)> = Vec::with_capacity(queries.len());
+		for query in queries {
+			if !unique_queries.contains(query) { unique_queries.push(query.clone()); }
+		}
+		let initial_queries: Vec<QueryBuf> =
+			unique_queries.iter().map(|(name, ty)| build_query(name, *ty)).collect();
 		(ProofBuilder {
+			pending_queries: initial_queries.len(),
+			queries_made: initial_queries.len(),
+			queries: unique_queries,
 			proof: Vec::new(),
 			min_ttl: u32::MAX,
 			dnskeys_requested: Vec::with_capacity(MAX_PROOF_STEPS),
-			pending_queries: 1,
-			queries_made: 1,
-		}, initial_query)
+			#[cfg(feature = "validation")]
+			validator: None,
+			#[cfg(feature = "validation")]
+			bogus_zone: None,
+		}, initial_queries)
+	}
+
+	/// Enables checking the signatures in each response as the proof is built.
+	///
+	/// Signatures are checked as soon as the signer's DNSKEYs arrive, and each zone's DNSKEYs are
+	/// checked against its DS records. As soon as any of these checks fail,
+	/// [`Self::process_response`] will fail, no further queries will be made (including for the
+	/// parents of the bogus zone) and [`Self::bogus_zone`] will return the zone which failed.
+	///
+	/// Note that this does not check that the records are currently valid or that they answer
+	/// the queries, so the final proof must still be checked with the [`crate::validation`]
+	/// module.
+	#[cfg(feature = "validation")]
+	pub fn enable_validation(&mut self) {
+		if self.validator.is_some() { return; }
+		let mut validator = IncrementalValidator::new();
+		if let Ok(rrs) = parse_rr_stream(&self.proof) {
+			if let Err(bogus) = validator.add_records(rrs) { self.bogus_zone = Some(bogus); }
+		}
+		self.validator = Some(validator);
+	}
+
+	/// Returns the zone which provided bogus data and the validation error it caused, if
+	/// validation was enabled with [`Self::enable_validation`] and has failed.
+	#[cfg(feature = "validation")]
+	pub fn bogus_zone(&self) -> Option<(&Name, &ValidationError)> {
+		self.bogus_zone.as_ref().map(|(zone, err)| (zone, err))
 	}
 
+	#[cfg(feature = "validation")]
+	fn is_bogus(&self) -> bool { self.bogus_zone.is_some() }
+	#[cfg(not(feature = "validation"))]
+	fn is_bogus(&self) -> bool { false }
+
 	/// Returns true as long as further responses are expected from the resolver.
 	///
 	/// As long as this returns true, responses should be read from the resolver and passed to
 	/// [`Self::process_response`]. Once this returns false, [`Self::finish_proof`] should be used
 	/// to (possibly) get the final proof.
 	pub fn awaiting_responses(&self) -> bool {
-		self.pending_queries > 0 && self.queries_made <= MAX_PROOF_STEPS
+		self.pending_queries > 0 && self.queries_made <= MAX_PROOF_STEPS && !self.is_bogus()
 	}
 
 	/// Processes a query response from the recursive resolver, returning a list of new queries to
 	/// send to the resolver.
 	pub fn process_response(&mut self, resp: &QueryBuf) -> Result<Vec<QueryBuf>, ()> {
-		if self.pending_queries == 0 { return Err(()); }
+		if self.pending_queries == 0 || self.is_bogus() { return Err(()); }
 
 		let mut rrsig_key_names = Vec::new();
+		#[cfg(feature = "validation")]
+		let response_start = self.proof.len();
 		let min_ttl = handle_response(resp, &mut self.proof, &mut rrsig_key_names)?;
 		self.min_ttl = cmp::min(self.min_ttl, min_ttl);
+
+		#[cfg(feature = "validation")]
+		if let Some(validator) = &mut self.validator {
+			// Check the new records before requesting any of their signers' keys, so that we
+			// never go on to request the parents of a bogus zone.
+			let rrs = parse_rr_stream(&self.proof[response_start..])?;
+			if let Err(bogus) = validator.add_records(rrs) {
+				self.bogus_zone = Some(bogus);
+				return Err(());
+			}
+		}
 		self.pending_queries -= 1;
 
 		rrsig_key_names.sort_unstable();
@@ -284,19 +405,66 @@ impl ProofBuilder {
 	/// used to cache the proof (i.e. the lowest TTL of all records which were used to build the
 	/// proof).
 	pub fn finish_proof(self) -> Result<(Vec<u8>, u32), ()> {
-		if self.pending_queries > 0 || self.queries_made > MAX_PROOF_STEPS {
+		if self.pending_queries > 0 || self.queries_made > MAX_PROOF_STEPS || self.is_bogus() {
 			Err(())
 		} else {
 			Ok((self.proof, self.min_ttl))
 		}
 	}
+
+	/// Finalizes the proof, if one is available, and returns it wrapped in a [`ProofEnvelope`]
+	/// which describes the query it answers.
+	///
+	/// `created_at` should be the current time, in seconds since the UNIX epoch.
+	///
+	/// Fails if this builder was constructed for more than one query, as a [`ProofEnvelope`] can
+	/// only describe one.
+	pub fn finish_proof_envelope(self, created_at: u64) -> Result<ProofEnvelope, ()> {
+		if self.queries.len() != 1 { return Err(()); }
+		let (query_name, query_type) = self.queries[0].clone();
+		let (authentication_chain, ttl) = self.finish_proof()?;
+		Ok(ProofEnvelope { query_name, query_type, created_at, ttl, authentication_chain })
+	}
+}
+
+/// A boxed future returned by [`AsyncTransport`] methods.
+#[cfg(feature = "std")]
+pub type AsyncTransportFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, Error>> + Send + 'a>>;
+
+/// A transport over which queries can be sent to a recursive resolver, e.g. a [`TcpStream`].
+///
+/// Implement this to build proofs using [`build_proof_with`] over transports other than the ones
+/// provided by this crate, e.g. DoH or mock transports in tests.
+#[cfg(feature = "std")]
+pub trait Transport {
+	/// Sends a query to the resolver.
+	///
+	/// Several queries may be sent before their responses are read.
+	fn send_query(&mut self, query: &QueryBuf) -> Result<(), Error>;
+	/// Reads the next response from the resolver, which may be to any outstanding query.
+	fn read_response(&mut self) -> Result<QueryBuf, Error>;
+}
+
+/// An async transport over which queries can be sent to a recursive resolver.
+///
+/// This is the async version of [`Transport`] for use with [`build_proof_with_async`]. It is
+/// not tied to any specific runtime, though is implemented for `tokio`'s `TcpStream` when the
+/// `tokio` feature is enabled.
+#[cfg(feature = "std")]
+pub trait AsyncTransport {
+	/// Sends a query to the resolver.
+	///
+	/// Several queries may be sent before their responses are read.
+	fn send_query<'a>(&'a mut self, query: &'a QueryBuf) -> AsyncTransportFuture<'a, ()>;
+	/// Reads the next response from the resolver, which may be to any outstanding query.
+	fn read_response<'a>(&'a mut self) -> AsyncTransportFuture<'a, QueryBuf>;
 }
 
 #[cfg(feature = "std")]
-fn send_query(stream: &mut TcpStream, query: &[u8]) -> Result<(), Error> {
+fn send_query<W: Write>(stream: &mut W, query: &[u8]) -> Result<(), Error> {
 	stream.write_all(&(query.len() as u16).to_be_bytes())?;
 	stream.write_all(&query)?;
-	Ok(())
+	stream.flush()
 }
 
 #[cfg(feature = "tokio")]
@@ -307,7 +475,7 @@ async fn send_query_async(stream: &mut TokioTcpStream, query: &[u8]) -> Result<(
 }
 
 #[cfg(feature = "std")]
-fn read_response(stream: &mut TcpStream) -> Result<QueryBuf, Error> {
+fn read_response<R: Read>(stream: &mut R) -> Result<QueryBuf, Error> {
 	let mut len_bytes = [0; 2];
 	stream.read_exact(&mut len_bytes)?;
 	let mut buf = QueryBuf::new_zeroed(u16::from_be_bytes(len_bytes));
@@ -325,217 +493,2242 @@ async fn read_response_async(stream: &mut TokioTcpStream) -> Result<QueryBuf, Er
 }
 
 #[cfg(feature = "std")]
-macro_rules! build_proof_impl {
-	($stream: ident, $send_query: ident, $read_response: ident, $domain: expr, $ty: expr $(, $async_ok: tt)?) => { {
-		// We require the initial query to have already gone out, and assume our resolver will
-		// return any CNAMEs all the way to the final record in the response. From there, we just
-		// have to take any RRSIGs in the response and walk them up to the root. We do so
-		// iteratively, sending DNSKEY and DS lookups after every response, deduplicating requests
-		// using `dnskeys_requested`.
-		let (mut builder, initial_query) = ProofBuilder::new($domain, $ty);
-		$send_query(&mut $stream, &initial_query)
-			$(.await?; $async_ok)??; // Either await?; Ok(())?, or just ?
-		while builder.awaiting_responses() {
-			let response = $read_response(&mut $stream)
-				$(.await?; $async_ok)??; // Either await?; Ok(())?, or just ?
-			let new_queries = builder.process_response(&response)
-				.map_err(|()| Error::new(ErrorKind::Other, "Bad response"))?;
-			for query in new_queries {
-				$send_query(&mut $stream, &query)
-					$(.await?; $async_ok)??; // Either await?; Ok(())?, or just ?
-			}
-		}
+impl Transport for TcpStream {
+	fn send_query(&mut self, query: &QueryBuf) -> Result<(), Error> { send_query(self, query) }
+	fn read_response(&mut self) -> Result<QueryBuf, Error> { read_response(self) }
+}
 
-		builder.finish_proof()
-			.map_err(|()| Error::new(ErrorKind::Other, "Too many requests required"))
-	} }
+#[cfg(feature = "tokio")]
+impl AsyncTransport for TokioTcpStream {
+	fn send_query<'a>(&'a mut self, query: &'a QueryBuf) -> AsyncTransportFuture<'a, ()> {
+		Box::pin(send_query_async(self, query))
+	}
+	fn read_response<'a>(&'a mut self) -> AsyncTransportFuture<'a, QueryBuf> {
+		Box::pin(read_response_async(self))
+	}
 }
 
+/// Returns true if `resp` looks like a response to `query`, i.e. it has the response flag set and
+/// the same transaction ID and question.
 #[cfg(feature = "std")]
-fn build_proof(resolver: SocketAddr, domain: &Name, ty: u16) -> Result<(Vec<u8>, u32), Error> {
-	let mut stream = TcpStream::connect(resolver)?;
-	build_proof_impl!(stream, send_query, read_response, domain, ty)
+fn is_response_to(resp: &[u8], query: &[u8]) -> bool {
+	let question_end = query.len() - QUERY_OPT_LEN;
+	resp.len() >= question_end && resp[..2] == query[..2] && resp[2] & 0x80 != 0 &&
+		resp[4..6] == query[4..6] && resp[12..question_end] == query[12..question_end]
 }
 
-#[cfg(feature = "tokio")]
-async fn build_proof_async(resolver: SocketAddr, domain: &Name, ty: u16) -> Result<(Vec<u8>, u32), Error> {
-	let mut stream = TokioTcpStream::connect(resolver).await?;
-	build_proof_impl!(stream, send_query_async, read_response_async, domain, ty, { Ok::<(), Error>(()) })
+/// Gets a random transaction ID for a UDP query.
+///
+/// While we only accept signed data, an off-path attacker who can guess the transaction ID can
+/// still spoof responses which cause proof building to fail.
+#[cfg(feature = "std")]
+fn random_txid() -> u16 {
+	use std::hash::{BuildHasher, Hasher};
+	// `RandomState` is randomly keyed for each new instance, so hashing nothing gives us a random
+	// value without requiring a dependency on an RNG crate.
+	std::collections::hash_map::RandomState::new().build_hasher().finish() as u16
 }
 
-/// Builds a DNSSEC proof for an A record by querying a recursive resolver, returning the proof as
-/// well as the TTL for the proof provided by the recursive resolver.
-///
-/// Note that this proof is NOT verified in any way, you need to use the [`crate::validation`]
-/// module to validate the records contained.
+/// Sets the transaction ID in the DNS message in `msg`.
 #[cfg(feature = "std")]
-pub fn build_a_proof(resolver: SocketAddr, domain: &Name) -> Result<(Vec<u8>, u32), Error> {
-	build_proof(resolver, domain, A::TYPE)
+fn set_txid(msg: &mut QueryBuf, txid: u16) {
+	if msg.len() >= 2 { msg[..2].copy_from_slice(&txid.to_be_bytes()); }
 }
 
-/// Builds a DNSSEC proof for an AAAA record by querying a recursive resolver, returning the proof
-/// as well as the TTL for the proof provided by the recursive resolver.
-///
-/// Note that this proof is NOT verified in any way, you need to use the [`crate::validation`]
-/// module to validate the records contained.
+/// Returns true if the TC flag is set in `resp`.
 #[cfg(feature = "std")]
-pub fn build_aaaa_proof(resolver: SocketAddr, domain: &Name) -> Result<(Vec<u8>, u32), Error> {
-	build_proof(resolver, domain, AAAA::TYPE)
+fn is_truncated(resp: &[u8]) -> bool {
+	resp[2] & 0x02 != 0
 }
 
-/// Builds a DNSSEC proof for an TXT record by querying a recursive resolver, returning the proof
-/// as well as the TTL for the proof provided by the recursive resolver.
-///
-/// Note that this proof is NOT verified in any way, you need to use the [`crate::validation`]
-/// module to validate the records contained.
+/// The result of receiving a datagram on a UDP transport which responds to an outstanding query.
 #[cfg(feature = "std")]
-pub fn build_txt_proof(resolver: SocketAddr, domain: &Name) -> Result<(Vec<u8>, u32), Error> {
-	build_proof(resolver, domain, Txt::TYPE)
+enum UdpResponse {
+	/// A complete response, with our fixed [`TXID`] restored.
+	Complete(QueryBuf),
+	/// The response was truncated, so the given query must be retried over TCP.
+	Truncated(QueryBuf),
 }
 
-/// Builds a DNSSEC proof for an TLSA record by querying a recursive resolver, returning the proof
-/// as well as the TTL for the proof provided by the recursive resolver.
+/// Handles a datagram received by a UDP transport, returning `None` if it does not respond to any
+/// query in `outstanding` (e.g. because it's a duplicate of a response we already processed or was
+/// spoofed). Otherwise, the query it responds to is removed from `outstanding`.
 ///
-/// Note that this proof is NOT verified in any way, you need to use the [`crate::validation`]
-/// module to validate the records contained.
+/// `datagram` should have been read into a buffer larger than [`EDNS_UDP_PAYLOAD_SIZE`], so that
+/// a resolver which ignores our advertised payload size and sends a datagram which doesn't fit is
+/// treated as if it had truncated its response.
 #[cfg(feature = "std")]
-pub fn build_tlsa_proof(resolver: SocketAddr, domain: &Name) -> Result<(Vec<u8>, u32), Error> {
-	build_proof(resolver, domain, TLSA::TYPE)
+fn handle_udp_datagram(outstanding: &mut Vec<QueryBuf>, datagram: &[u8]) -> Option<UdpResponse> {
+	let idx = outstanding.iter().position(|query| is_response_to(datagram, query))?;
+	let query = outstanding.swap_remove(idx);
+	if datagram.len() > EDNS_UDP_PAYLOAD_SIZE as usize || is_truncated(datagram) {
+		return Some(UdpResponse::Truncated(query));
+	}
+	let mut resp = QueryBuf::new_zeroed(0);
+	resp.extend_from_slice(datagram);
+	set_txid(&mut resp, TXID);
+	Some(UdpResponse::Complete(resp))
 }
 
+/// Returns true if the given error from reading a socket with a timeout indicates the timeout
+/// passed.
+#[cfg(feature = "std")]
+fn is_timeout(e: &Error) -> bool {
+	e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut
+}
 
-/// Builds a DNSSEC proof for an A record by querying a recursive resolver, returning the proof as
-/// well as the TTL for the proof provided by the recursive resolver.
+/// A [`Transport`] which sends queries over UDP, retrying any query whose response was truncated
+/// over TCP.
 ///
-/// Note that this proof is NOT verified in any way, you need to use the [`crate::validation`]
-/// module to validate the records contained.
+/// Each query is sent with a random transaction ID, and responses are only accepted if they match
+/// an outstanding query's transaction ID and question. Outstanding queries are retransmitted if
+/// no response arrives within [`UDP_RETRANSMIT_TIMEOUT`], doubling the timeout each time, up to
+/// [`UDP_RETRANSMITS`] times.
+#[cfg(feature = "std")]
+pub(crate) struct UdpTransport {
+	socket: UdpSocket,
+	resolver: SocketAddr,
+	tcp: Option<TcpStream>,
+	outstanding: Vec<QueryBuf>,
+}
+
+#[cfg(feature = "std")]
+impl UdpTransport {
+	pub(crate) fn connect(resolver: SocketAddr) -> Result<Self, Error> {
+		let socket = UdpSocket::bind(bind_addr(resolver))?;
+		socket.connect(resolver)?;
+		Ok(UdpTransport { socket, resolver, tcp: None, outstanding: Vec::new() })
+	}
+
+	fn retry_over_tcp(&mut self, query: &QueryBuf) -> Result<QueryBuf, Error> {
+		if self.tcp.is_none() { self.tcp = Some(TcpStream::connect(self.resolver)?); }
+		let stream = self.tcp.as_mut().expect("Just set");
+		send_query(stream, query)?;
+		let mut resp = read_response(stream)?;
+		set_txid(&mut resp, TXID);
+		Ok(resp)
+	}
+}
+
+#[cfg(feature = "std")]
+impl Transport for UdpTransport {
+	fn send_query(&mut self, query: &QueryBuf) -> Result<(), Error> {
+		let mut query = query.clone();
+		set_txid(&mut query, random_txid());
+		self.socket.send(&query)?;
+		self.outstanding.push(query);
+		Ok(())
+	}
+	fn read_response(&mut self) -> Result<QueryBuf, Error> {
+		let mut buf = [0; EDNS_UDP_PAYLOAD_SIZE as usize + 1];
+		let mut timeout = UDP_RETRANSMIT_TIMEOUT;
+		let mut retransmits = 0;
+		loop {
+			self.socket.set_read_timeout(Some(timeout))?;
+			let len = match self.socket.recv(&mut buf) {
+				Ok(len) => len,
+				Err(e) if is_timeout(&e) && retransmits < UDP_RETRANSMITS => {
+					for query in self.outstanding.iter() { self.socket.send(query)?; }
+					retransmits += 1;
+					timeout *= 2;
+					continue;
+				},
+				Err(e) => return Err(e),
+			};
+			match handle_udp_datagram(&mut self.outstanding, &buf[..len]) {
+				Some(UdpResponse::Complete(resp)) => return Ok(resp),
+				Some(UdpResponse::Truncated(query)) => return self.retry_over_tcp(&query),
+				None => {},
+			}
+		}
+	}
+}
+
+/// The async version of [`UdpTransport`], using `tokio`.
 #[cfg(feature = "tokio")]
-pub async fn build_a_proof_async(resolver: SocketAddr, domain: &Name) -> Result<(Vec<u8>, u32), Error> {
-	build_proof_async(resolver, domain, A::TYPE).await
+pub(crate) struct TokioUdpTransport {
+	socket: TokioUdpSocket,
+	resolver: SocketAddr,
+	tcp: Option<TokioTcpStream>,
+	outstanding: Vec<QueryBuf>,
 }
 
-/// Builds a DNSSEC proof for an AAAA record by querying a recursive resolver, returning the proof
-/// as well as the TTL for the proof provided by the recursive resolver.
-///
-/// Note that this proof is NOT verified in any way, you need to use the [`crate::validation`]
-/// module to validate the records contained.
 #[cfg(feature = "tokio")]
-pub async fn build_aaaa_proof_async(resolver: SocketAddr, domain: &Name) -> Result<(Vec<u8>, u32), Error> {
-	build_proof_async(resolver, domain, AAAA::TYPE).await
+impl TokioUdpTransport {
+	pub(crate) async fn connect(resolver: SocketAddr) -> Result<Self, Error> {
+		let socket = TokioUdpSocket::bind(bind_addr(resolver)).await?;
+		socket.connect(resolver).await?;
+		Ok(TokioUdpTransport { socket, resolver, tcp: None, outstanding: Vec::new() })
+	}
+
+	async fn retry_over_tcp(&mut self, query: &QueryBuf) -> Result<QueryBuf, Error> {
+		if self.tcp.is_none() { self.tcp = Some(TokioTcpStream::connect(self.resolver).await?); }
+		let stream = self.tcp.as_mut().expect("Just set");
+		send_query_async(stream, query).await?;
+		let mut resp = read_response_async(stream).await?;
+		set_txid(&mut resp, TXID);
+		Ok(resp)
+	}
+
+	async fn read_response_async(&mut self) -> Result<QueryBuf, Error> {
+		let mut buf = [0; EDNS_UDP_PAYLOAD_SIZE as usize + 1];
+		let mut timeout = UDP_RETRANSMIT_TIMEOUT;
+		let mut retransmits = 0;
+		loop {
+			let len = match tokio_crate::time::timeout(timeout, self.socket.recv(&mut buf)).await {
+				Ok(res) => res?,
+				Err(_) if retransmits < UDP_RETRANSMITS => {
+					for query in self.outstanding.iter() { self.socket.send(query).await?; }
+					retransmits += 1;
+					timeout *= 2;
+					continue;
+				},
+				Err(_) => return Err(Error::new(ErrorKind::TimedOut, "Timed out waiting for a UDP response")),
+			};
+			match handle_udp_datagram(&mut self.outstanding, &buf[..len]) {
+				Some(UdpResponse::Complete(resp)) => return Ok(resp),
+				Some(UdpResponse::Truncated(query)) => return self.retry_over_tcp(&query).await,
+				None => {},
+			}
+		}
+	}
 }
 
-/// Builds a DNSSEC proof for an TXT record by querying a recursive resolver, returning the proof
-/// as well as the TTL for the proof provided by the recursive resolver.
-///
-/// Note that this proof is NOT verified in any way, you need to use the [`crate::validation`]
-/// module to validate the records contained.
 #[cfg(feature = "tokio")]
-pub async fn build_txt_proof_async(resolver: SocketAddr, domain: &Name) -> Result<(Vec<u8>, u32), Error> {
-	build_proof_async(resolver, domain, Txt::TYPE).await
+impl AsyncTransport for TokioUdpTransport {
+	fn send_query<'a>(&'a mut self, query: &'a QueryBuf) -> AsyncTransportFuture<'a, ()> {
+		Box::pin(async move {
+			let mut query = query.clone();
+			set_txid(&mut query, random_txid());
+			self.socket.send(&query).await?;
+			self.outstanding.push(query);
+			Ok(())
+		})
+	}
+	fn read_response<'a>(&'a mut self) -> AsyncTransportFuture<'a, QueryBuf> {
+		Box::pin(self.read_response_async())
+	}
 }
 
-/// Builds a DNSSEC proof for an TLSA record by querying a recursive resolver, returning the proof
-/// as well as the TTL for the proof provided by the recursive resolver.
+/// The EDNS option code for padding, from RFC 7830.
+#[cfg(feature = "dot")]
+const EDNS_PADDING_OPTION: u16 = 12;
+/// The block size we pad queries to, as recommended by RFC 8467.
+#[cfg(feature = "dot")]
+const QUERY_PADDING_BLOCK: usize = 128;
+
+/// Pads a query built by [`build_query`] to a multiple of [`QUERY_PADDING_BLOCK`] bytes using the
+/// EDNS padding option so that the query size doesn't leak which name is being queried.
 ///
-/// Note that this proof is NOT verified in any way, you need to use the [`crate::validation`]
-/// module to validate the records contained.
-#[cfg(feature = "tokio")]
-pub async fn build_tlsa_proof_async(resolver: SocketAddr, domain: &Name) -> Result<(Vec<u8>, u32), Error> {
-	build_proof_async(resolver, domain, TLSA::TYPE).await
+/// Queries which do not end with an OPT record without options are returned unmodified.
+#[cfg(feature = "dot")]
+fn pad_query(query: &QueryBuf) -> QueryBuf {
+	let mut padded = QueryBuf::new_zeroed(0);
+	padded.extend_from_slice(query);
+	let len = query.len();
+	if len < 12 + QUERY_OPT_LEN || query[len - QUERY_OPT_LEN..len - 8] != [0, 0, 0x29] ||
+		query[len - 2..] != [0, 0]
+	{
+		return padded;
+	}
+	let padded_len = (len + 4 + QUERY_PADDING_BLOCK - 1) / QUERY_PADDING_BLOCK * QUERY_PADDING_BLOCK;
+	let padding_len = padded_len - len - 4;
+	padded[len - 2..].copy_from_slice(&(4 + padding_len as u16).to_be_bytes());
+	padded.extend_from_slice(&EDNS_PADDING_OPTION.to_be_bytes());
+	padded.extend_from_slice(&(padding_len as u16).to_be_bytes());
+	padded.extend_from_slice(&[0; QUERY_PADDING_BLOCK][..padding_len]);
+	padded
 }
 
-#[cfg(all(feature = "validation", feature = "std", test))]
-mod tests {
-	use super::*;
-	use crate::validation::*;
+#[cfg(feature = "dot")]
+fn tls_error(e: rustls::Error) -> Error {
+	Error::new(ErrorKind::Other, e)
+}
 
-	use rand::seq::SliceRandom;
+/// Builds a TLS client configuration trusting the Mozilla root certificates from `webpki-roots`.
+#[cfg(feature = "dot")]
+fn default_dot_config() -> Result<Arc<rustls::ClientConfig>, Error> {
+	let roots = rustls::RootCertStore { roots: webpki_roots::TLS_SERVER_ROOTS.to_vec() };
+	let config = rustls::ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+		.with_safe_default_protocol_versions().map_err(tls_error)?
+		.with_root_certificates(roots)
+		.with_no_client_auth();
+	Ok(Arc::new(config))
+}
 
-	use std::net::ToSocketAddrs;
-	use std::time::SystemTime;
+#[cfg(feature = "dot")]
+fn dot_connection(server_name: &str, config: Arc<rustls::ClientConfig>)
+-> Result<rustls::ClientConnection, Error> {
+	let server_name = rustls::pki_types::ServerName::try_from(server_name.to_owned())
+		.map_err(|_| Error::new(ErrorKind::InvalidInput, "Invalid TLS server name"))?;
+	rustls::ClientConnection::new(config, server_name).map_err(tls_error)
+}
 
-	#[test]
-	fn test_cloudflare_txt_query() {
-		let sockaddr = "8.8.8.8:53".to_socket_addrs().unwrap().next().unwrap();
-		let query_name = "cloudflare.com.".try_into().unwrap();
-		let (proof, _) = build_txt_proof(sockaddr, &query_name).unwrap();
+/// Completes a TLS handshake over `stream`, authenticating the server as `server_name`.
+#[cfg(feature = "dot")]
+fn tls_connect(stream: TcpStream, server_name: &str, config: Arc<rustls::ClientConfig>)
+-> Result<rustls::StreamOwned<rustls::ClientConnection, TcpStream>, Error> {
+	let conn = dot_connection(server_name, config)?;
+	let mut stream = rustls::StreamOwned::new(conn, stream);
+	while stream.conn.is_handshaking() {
+		stream.conn.complete_io(&mut stream.sock)?;
+	}
+	Ok(stream)
+}
 
-		let mut rrs = parse_rr_stream(&proof).unwrap();
-		rrs.shuffle(&mut rand::rngs::OsRng);
-		let verified_rrs = verify_rr_stream(&rrs).unwrap();
-		assert!(verified_rrs.verified_rrs.len() > 1);
+/// A [`Transport`] which sends queries to a resolver over DNS-over-TLS, as described in RFC 7858.
+///
+/// The resolver's certificate must be valid for the server name it was connected with, and all
+/// queries are padded as described in RFC 8467.
+#[cfg(feature = "dot")]
+pub struct DotTransport(rustls::StreamOwned<rustls::ClientConnection, TcpStream>);
+
+#[cfg(feature = "dot")]
+impl DotTransport {
+	/// Connects to the DoT resolver at the given address (generally on port 853), authenticating
+	/// it as `server_name` using the Mozilla root certificates from `webpki-roots`.
+	pub fn connect(resolver: SocketAddr, server_name: &str) -> Result<Self, Error> {
+		Self::connect_with_config(resolver, server_name, default_dot_config()?)
+	}
 
-		let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
-		assert!(verified_rrs.valid_from < now);
-		assert!(verified_rrs.expires > now);
+	/// Connects to the DoT resolver at the given address (generally on port 853), authenticating
+	/// it as `server_name` using the given `rustls` configuration.
+	pub fn connect_with_config(resolver: SocketAddr, server_name: &str, config: Arc<rustls::ClientConfig>)
+	-> Result<Self, Error> {
+		Ok(DotTransport(tls_connect(TcpStream::connect(resolver)?, server_name, config)?))
 	}
+}
 
-	#[test]
-	fn test_sha1_query() {
-		let sockaddr = "8.8.8.8:53".to_socket_addrs().unwrap().next().unwrap();
-		let query_name = "benthecarman.com.".try_into().unwrap();
-		let (proof, _) = build_a_proof(sockaddr, &query_name).unwrap();
+#[cfg(feature = "dot")]
+impl Transport for DotTransport {
+	fn send_query(&mut self, query: &QueryBuf) -> Result<(), Error> {
+		send_query(&mut self.0, &pad_query(query))
+	}
+	fn read_response(&mut self) -> Result<QueryBuf, Error> { read_response(&mut self.0) }
+}
 
-		let mut rrs = parse_rr_stream(&proof).unwrap();
-		rrs.shuffle(&mut rand::rngs::OsRng);
-		let verified_rrs = verify_rr_stream(&rrs).unwrap();
-		assert!(verified_rrs.verified_rrs.len() >= 1);
+/// A TLS stream over a `tokio` TCP stream, driving `rustls` directly.
+#[cfg(all(feature = "dot", feature = "tokio"))]
+struct TokioTlsStream {
+	conn: rustls::ClientConnection,
+	stream: TokioTcpStream,
+}
 
-		let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
-		assert!(verified_rrs.valid_from < now);
-		assert!(verified_rrs.expires > now);
+#[cfg(all(feature = "dot", feature = "tokio"))]
+impl TokioTlsStream {
+	/// Completes a TLS handshake over `stream`, authenticating the server as `server_name`.
+	async fn connect(stream: TokioTcpStream, server_name: &str, config: Arc<rustls::ClientConfig>)
+	-> Result<Self, Error> {
+		let conn = dot_connection(server_name, config)?;
+		let mut res = TokioTlsStream { conn, stream };
+		let mut plaintext = Vec::new();
+		while res.conn.is_handshaking() {
+			res.write_tls().await?;
+			if res.conn.is_handshaking() { res.read(&mut plaintext).await?; }
+		}
+		Ok(res)
 	}
 
-	#[test]
-	fn test_txt_query() {
-		let sockaddr = "8.8.8.8:53".to_socket_addrs().unwrap().next().unwrap();
-		let query_name = "matt.user._bitcoin-payment.mattcorallo.com.".try_into().unwrap();
-		let (proof, _) = build_txt_proof(sockaddr, &query_name).unwrap();
+	/// Writes any pending TLS data to the socket.
+	async fn write_tls(&mut self) -> Result<(), Error> {
+		let mut buf = Vec::new();
+		while self.conn.wants_write() { self.conn.write_tls(&mut buf)?; }
+		self.stream.write_all(&buf).await
+	}
 
-		let mut rrs = parse_rr_stream(&proof).unwrap();
-		rrs.shuffle(&mut rand::rngs::OsRng);
-		let verified_rrs = verify_rr_stream(&rrs).unwrap();
-		assert_eq!(verified_rrs.verified_rrs.len(), 1);
+	async fn write_all(&mut self, data: &[u8]) -> Result<(), Error> {
+		self.conn.writer().write_all(data)?;
+		self.write_tls().await
+	}
 
-		let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
-		assert!(verified_rrs.valid_from < now);
-		assert!(verified_rrs.expires > now);
+	/// Reads some TLS data from the socket, appending any decrypted data to `plaintext`.
+	async fn read(&mut self, plaintext: &mut Vec<u8>) -> Result<(), Error> {
+		let mut buf = [0; 4096];
+		let len = self.stream.read(&mut buf).await?;
+		if len == 0 { return Err(Error::new(ErrorKind::UnexpectedEof, "TLS connection closed")); }
+		let mut read = &buf[..len];
+		while !read.is_empty() {
+			self.conn.read_tls(&mut read)?;
+			let state = self.conn.process_new_packets().map_err(tls_error)?;
+			let start = plaintext.len();
+			plaintext.resize(start + state.plaintext_bytes_to_read(), 0);
+			self.conn.reader().read_exact(&mut plaintext[start..])?;
+		}
+		// We may need to respond to handshake messages or alerts.
+		self.write_tls().await
 	}
+}
 
-	#[test]
-	fn test_cname_query() {
-		for resolver in ["1.1.1.1:53", "8.8.8.8:53", "9.9.9.9:53"] {
-			let sockaddr = resolver.to_socket_addrs().unwrap().next().unwrap();
-			let query_name = "cname_test.dnssec_proof_tests.bitcoin.ninja.".try_into().unwrap();
-			let (proof, _) = build_txt_proof(sockaddr, &query_name).unwrap();
+/// The async version of [`DotTransport`], using `tokio`.
+#[cfg(all(feature = "dot", feature = "tokio"))]
+pub struct TokioDotTransport {
+	tls: TokioTlsStream,
+	plaintext: Vec<u8>,
+}
 
-			let mut rrs = parse_rr_stream(&proof).unwrap();
-			rrs.shuffle(&mut rand::rngs::OsRng);
-			let verified_rrs = verify_rr_stream(&rrs).unwrap();
-			assert_eq!(verified_rrs.verified_rrs.len(), 2);
+#[cfg(all(feature = "dot", feature = "tokio"))]
+impl TokioDotTransport {
+	/// Connects to the DoT resolver at the given address (generally on port 853), authenticating
+	/// it as `server_name` using the Mozilla root certificates from `webpki-roots`.
+	pub async fn connect(resolver: SocketAddr, server_name: &str) -> Result<Self, Error> {
+		Self::connect_with_config(resolver, server_name, default_dot_config()?).await
+	}
 
-			let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
-			assert!(verified_rrs.valid_from < now);
-			assert!(verified_rrs.expires > now);
+	/// Connects to the DoT resolver at the given address (generally on port 853), authenticating
+	/// it as `server_name` using the given `rustls` configuration.
+	pub async fn connect_with_config(resolver: SocketAddr, server_name: &str,
+		config: Arc<rustls::ClientConfig>
+	) -> Result<Self, Error> {
+		let stream = TokioTcpStream::connect(resolver).await?;
+		let tls = TokioTlsStream::connect(stream, server_name, config).await?;
+		Ok(TokioDotTransport { tls, plaintext: Vec::new() })
+	}
 
-			let resolved_rrs = verified_rrs.resolve_name(&query_name);
-			assert_eq!(resolved_rrs.len(), 1);
-			if let RR::Txt(txt) = &resolved_rrs[0] {
-				assert_eq!(txt.name.as_str(), "txt_test.dnssec_proof_tests.bitcoin.ninja.");
-				assert_eq!(txt.data, b"dnssec_prover_test");
-			} else { panic!(); }
+	async fn read_response_async(&mut self) -> Result<QueryBuf, Error> {
+		loop {
+			if self.plaintext.len() >= 2 {
+				let len = u16::from_be_bytes([self.plaintext[0], self.plaintext[1]]) as usize;
+				if self.plaintext.len() >= 2 + len {
+					let mut resp = QueryBuf::new_zeroed(0);
+					resp.extend_from_slice(&self.plaintext[2..2 + len]);
+					self.plaintext.drain(..2 + len);
+					return Ok(resp);
+				}
+			}
+			self.tls.read(&mut self.plaintext).await?;
 		}
 	}
+}
 
-	#[cfg(feature = "tokio")]
-	use tokio_crate as tokio;
+#[cfg(all(feature = "dot", feature = "tokio"))]
+impl AsyncTransport for TokioDotTransport {
+	fn send_query<'a>(&'a mut self, query: &'a QueryBuf) -> AsyncTransportFuture<'a, ()> {
+		Box::pin(async move {
+			let query = pad_query(query);
+			let mut framed = Vec::with_capacity(2 + query.len());
+			framed.extend_from_slice(&(query.len() as u16).to_be_bytes());
+			framed.extend_from_slice(&query);
+			self.tls.write_all(&framed).await
+		})
+	}
+	fn read_response<'a>(&'a mut self) -> AsyncTransportFuture<'a, QueryBuf> {
+		Box::pin(self.read_response_async())
+	}
+}
+
+/// The HTTP method used to send DoH queries, as described in RFC 8484.
+#[cfg(feature = "doh")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DohMethod {
+	/// Send queries base64url-encoded in the `dns` URL parameter of GET requests, which are
+	/// more likely to be cached.
+	Get,
+	/// Send queries in the body of POST requests.
+	Post,
+}
+
+/// The maximum length of the headers of a DoH response we'll accept.
+#[cfg(feature = "doh")]
+const MAX_HTTP_HEADER_LEN: usize = 8192;
+
+/// A parsed DoH endpoint URL.
+#[cfg(feature = "doh")]
+#[derive(Clone)]
+struct DohUrl {
+	tls: bool,
+	/// The host, without brackets for IPv6 addresses.
+	host: String,
+	port: u16,
+	/// The host and port as given in the URL, for the `Host` header.
+	authority: String,
+	/// The path and query.
+	path: String,
+}
+
+#[cfg(feature = "doh")]
+fn parse_doh_url(url: &str) -> Result<DohUrl, Error> {
+	let bad_url = || Error::new(ErrorKind::InvalidInput, "Invalid DoH endpoint URL");
+	let (tls, rest) = if let Some(rest) = url.strip_prefix("https://") {
+		(true, rest)
+	} else if let Some(rest) = url.strip_prefix("http://") {
+		(false, rest)
+	} else {
+		return Err(bad_url());
+	};
+	let (authority, path) = match rest.find('/') {
+		Some(pos) => rest.split_at(pos),
+		None => (rest, "/"),
+	};
+	if authority.contains('@') { return Err(bad_url()); }
+	let default_port = if tls { 443 } else { 80 };
+	let (host, port) = if let Some(bracketed) = authority.strip_prefix('[') {
+		let (host, port) = bracketed.split_once(']').ok_or_else(bad_url)?;
+		match port {
+			"" => (host, default_port),
+			_ => (host, port.strip_prefix(':').ok_or_else(bad_url)?.parse().map_err(|_| bad_url())?),
+		}
+	} else if let Some((host, port)) = authority.split_once(':') {
+		(host, port.parse().map_err(|_| bad_url())?)
+	} else {
+		(authority, default_port)
+	};
+	if host.is_empty() { return Err(bad_url()); }
+	Ok(DohUrl {
+		tls, host: host.to_owned(), port, authority: authority.to_owned(), path: path.to_owned(),
+	})
+}
+
+#[cfg(feature = "doh")]
+fn build_http_request(url: &DohUrl, method: DohMethod, query: &[u8]) -> Vec<u8> {
+	let mut req = String::new();
+	match method {
+		DohMethod::Get => {
+			let separator = if url.path.contains('?') { '&' } else { '?' };
+			let _ = write!(req, "GET {}{}dns={} HTTP/1.1\r\n", url.path, separator, crate::base64::encode_url(query));
+		},
+		DohMethod::Post => {
+			let _ = write!(req, "POST {} HTTP/1.1\r\n", url.path);
+			let _ = write!(req, "Content-Type: application/dns-message\r\nContent-Length: {}\r\n", query.len());
+		},
+	}
+	let _ = write!(req, "Host: {}\r\nAccept: application/dns-message\r\n\r\n", url.authority);
+	let mut req = req.into_bytes();
+	if method == DohMethod::Post { req.extend_from_slice(query); }
+	req
+}
+
+/// A DoH response, along with whether the server will close the connection after it.
+#[cfg(feature = "doh")]
+struct HttpResponse {
+	body: QueryBuf,
+	close: bool,
+}
+
+#[cfg(feature = "doh")]
+fn find_subslice(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+	haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+/// Attempts to parse an HTTP/1.1 response to a DoH query from the start of `buf`, returning it
+/// and the number of bytes it spanned or `None` if more data is needed.
+#[cfg(feature = "doh")]
+fn parse_http_response(buf: &[u8]) -> Result<Option<(HttpResponse, usize)>, Error> {
+	let bad_resp = |msg| Error::new(ErrorKind::InvalidData, msg);
+	let header_end = match find_subslice(buf, b"\r\n\r\n") {
+		Some(pos) => pos,
+		None if buf.len() > MAX_HTTP_HEADER_LEN => return Err(bad_resp("HTTP headers too long")),
+		None => return Ok(None),
+	};
+	let header = core::str::from_utf8(&buf[..header_end]).map_err(|_| bad_resp("Invalid HTTP headers"))?;
+	let mut lines = header.split("\r\n");
+	let mut status_line = lines.next().expect("split always returns an element").splitn(3, ' ');
+	let version = status_line.next().expect("splitn always returns an element");
+	if !version.starts_with("HTTP/1.") { return Err(bad_resp("Invalid HTTP response")); }
+	if status_line.next() != Some("200") {
+		return Err(Error::new(ErrorKind::Other, "DoH server returned an error"));
+	}
+
+	let mut close = version == "HTTP/1.0";
+	let (mut content_len, mut chunked, mut is_dns_message) = (None, false, false);
+	for line in lines {
+		let (name, value) = line.split_once(':').ok_or_else(|| bad_resp("Invalid HTTP header"))?;
+		let value = value.trim();
+		if name.eq_ignore_ascii_case("content-length") {
+			content_len = Some(value.parse::<usize>().map_err(|_| bad_resp("Invalid Content-Length"))?);
+		} else if name.eq_ignore_ascii_case("transfer-encoding") {
+			if !value.eq_ignore_ascii_case("chunked") { return Err(bad_resp("Unsupported Transfer-Encoding")); }
+			chunked = true;
+		} else if name.eq_ignore_ascii_case("connection") {
+			close = value.eq_ignore_ascii_case("close");
+		} else if name.eq_ignore_ascii_case("content-type") {
+			let media_type = value.split(';').next().expect("split always returns an element");
+			is_dns_message = media_type.trim().eq_ignore_ascii_case("application/dns-message");
+		}
+	}
+	if !is_dns_message { return Err(bad_resp("DoH response was not a DNS message")); }
+
+	let mut body = QueryBuf::new_zeroed(0);
+	let mut pos = header_end + 4;
+	if chunked {
+		loop {
+			let line_len = if let Some(len) = find_subslice(&buf[pos..], b"\r\n") { len } else { return Ok(None) };
+			let size = core::str::from_utf8(&buf[pos..pos + line_len]).ok()
+				.and_then(|line| usize::from_str_radix(line.split(';').next()?.trim(), 16).ok())
+				.ok_or_else(|| bad_resp("Invalid chunk size"))?;
+			pos += line_len + 2;
+			if size == 0 { break; }
+			if body.len() + size > u16::MAX as usize { return Err(bad_resp("DoH response too long")); }
+			if buf.len() < pos + size + 2 { return Ok(None); }
+			if &buf[pos + size..pos + size + 2] != b"\r\n" { return Err(bad_resp("Invalid chunk")); }
+			body.extend_from_slice(&buf[pos..pos + size]);
+			pos += size + 2;
+		}
+		// Skip any trailers up to the final empty line.
+		loop {
+			let line_len = if let Some(len) = find_subslice(&buf[pos..], b"\r\n") { len } else { return Ok(None) };
+			pos += line_len + 2;
+			if line_len == 0 { break; }
+		}
+	} else {
+		// We don't support bodies delimited by the connection closing.
+		let len = content_len.ok_or_else(|| bad_resp("DoH response had no Content-Length"))?;
+		if len > u16::MAX as usize { return Err(bad_resp("DoH response too long")); }
+		if buf.len() < pos + len { return Ok(None); }
+		body.extend_from_slice(&buf[pos..pos + len]);
+		pos += len;
+	}
+	Ok(Some((HttpResponse { body, close }, pos)))
+}
+
+/// Parses and removes the response at the start of `buf`, if it's complete.
+#[cfg(feature = "doh")]
+fn take_http_response(buf: &mut Vec<u8>) -> Result<Option<HttpResponse>, Error> {
+	Ok(parse_http_response(buf)?.map(|(resp, len)| {
+		buf.drain(..len);
+		resp
+	}))
+}
+
+/// Returns true if `e` indicates that the connection to a DoH server was closed (e.g. because the
+/// server closed an idle keep-alive connection), rather than the server returning an error.
+#[cfg(feature = "doh")]
+fn is_connection_closed(e: &Error) -> bool {
+	matches!(e.kind(), ErrorKind::UnexpectedEof | ErrorKind::ConnectionReset |
+		ErrorKind::ConnectionAborted | ErrorKind::BrokenPipe | ErrorKind::NotConnected)
+}
+
+/// A plaintext or TLS connection to a DoH server.
+#[cfg(feature = "doh")]
+enum DohStream {
+	Plain(TcpStream),
+	Tls(Box<rustls::StreamOwned<rustls::ClientConnection, TcpStream>>),
+}
+
+#[cfg(feature = "doh")]
+impl DohStream {
+	fn stream(&mut self) -> &mut dyn ReadWrite {
+		match self {
+			DohStream::Plain(stream) => stream,
+			DohStream::Tls(stream) => &mut **stream,
+		}
+	}
+}
+
+#[cfg(feature = "doh")]
+trait ReadWrite: Read + Write {}
+#[cfg(feature = "doh")]
+impl<T: Read + Write> ReadWrite for T {}
+
+/// A [`Transport`] which sends queries to a DNS-over-HTTPS server, as described in RFC 8484.
+///
+/// Queries are sent one at a time over a single HTTP/1.1 keep-alive connection, which is
+/// re-opened if the server closes it.
+#[cfg(feature = "doh")]
+pub struct DohTransport {
+	url: DohUrl,
+	method: DohMethod,
+	config: Arc<rustls::ClientConfig>,
+	stream: Option<DohStream>,
+	buf: Vec<u8>,
+	queued: VecDeque<QueryBuf>,
+}
+
+#[cfg(feature = "doh")]
+impl DohTransport {
+	/// Creates a transport for the given DoH endpoint URL (e.g.
+	/// `https://cloudflare-dns.com/dns-query`), authenticating the server using the Mozilla root
+	/// certificates from `webpki-roots`.
+	///
+	/// `http://` endpoints are also supported, though should only be used for local servers.
+	pub fn new(endpoint: &str, method: DohMethod) -> Result<Self, Error> {
+		Self::with_config(endpoint, method, default_dot_config()?)
+	}
+
+	/// Creates a transport for the given DoH endpoint URL, authenticating the server using the
+	/// given `rustls` configuration.
+	pub fn with_config(endpoint: &str, method: DohMethod, config: Arc<rustls::ClientConfig>)
+	-> Result<Self, Error> {
+		Ok(DohTransport {
+			url: parse_doh_url(endpoint)?, method, config, stream: None, buf: Vec::new(),
+			queued: VecDeque::new(),
+		})
+	}
+
+	fn connect(&self) -> Result<DohStream, Error> {
+		let stream = TcpStream::connect((self.url.host.as_str(), self.url.port))?;
+		if self.url.tls {
+			let tls = tls_connect(stream, &self.url.host, Arc::clone(&self.config))?;
+			Ok(DohStream::Tls(Box::new(tls)))
+		} else {
+			Ok(DohStream::Plain(stream))
+		}
+	}
+
+	fn try_exchange(&mut self, request: &[u8]) -> Result<QueryBuf, Error> {
+		if self.stream.is_none() {
+			self.stream = Some(self.connect()?);
+			self.buf.clear();
+		}
+		let stream = self.stream.as_mut().expect("Just set").stream();
+		stream.write_all(request)?;
+		stream.flush()?;
+		let resp = loop {
+			if let Some(resp) = take_http_response(&mut self.buf)? { break resp; }
+			let mut read_buf = [0; 4096];
+			let len = stream.read(&mut read_buf)?;
+			if len == 0 { return Err(Error::new(ErrorKind::UnexpectedEof, "DoH connection closed")); }
+			self.buf.extend_from_slice(&read_buf[..len]);
+		};
+		if resp.close { self.stream = None; }
+		Ok(resp.body)
+	}
+
+	fn exchange(&mut self, query: &QueryBuf) -> Result<QueryBuf, Error> {
+		let request = build_http_request(&self.url, self.method, query);
+		let reused = self.stream.is_some();
+		let mut res = self.try_exchange(&request);
+		if reused && matches!(&res, Err(e) if is_connection_closed(e)) {
+			// The server may have closed an idle keep-alive connection, so retry once.
+			self.stream = None;
+			res = self.try_exchange(&request);
+		}
+		if res.is_err() { self.stream = None; }
+		res
+	}
+}
+
+#[cfg(feature = "doh")]
+impl Transport for DohTransport {
+	fn send_query(&mut self, query: &QueryBuf) -> Result<(), Error> {
+		self.queued.push_back(query.clone());
+		Ok(())
+	}
+	fn read_response(&mut self) -> Result<QueryBuf, Error> {
+		let query = self.queued.pop_front()
+			.ok_or_else(|| Error::new(ErrorKind::Other, "No queries awaiting responses"))?;
+		self.exchange(&query)
+	}
+}
+
+/// A plaintext or TLS connection to a DoH server, using `tokio`.
+#[cfg(all(feature = "doh", feature = "tokio"))]
+enum TokioDohStream {
+	Plain(TokioTcpStream),
+	Tls(Box<TokioTlsStream>),
+}
+
+/// The async version of [`DohTransport`], using `tokio`.
+#[cfg(all(feature = "doh", feature = "tokio"))]
+pub struct TokioDohTransport {
+	url: DohUrl,
+	method: DohMethod,
+	config: Arc<rustls::ClientConfig>,
+	stream: Option<TokioDohStream>,
+	buf: Vec<u8>,
+	queued: VecDeque<QueryBuf>,
+}
+
+#[cfg(all(feature = "doh", feature = "tokio"))]
+impl TokioDohTransport {
+	/// Creates a transport for the given DoH endpoint URL (e.g.
+	/// `https://cloudflare-dns.com/dns-query`), authenticating the server using the Mozilla root
+	/// certificates from `webpki-roots`.
+	///
+	/// `http://` endpoints are also supported, though should only be used for local servers.
+	pub fn new(endpoint: &str, method: DohMethod) -> Result<Self, Error> {
+		Self::with_config(endpoint, method, default_dot_config()?)
+	}
+
+	/// Creates a transport for the given DoH endpoint URL, authenticating the server using the
+	/// given `rustls` configuration.
+	pub fn with_config(endpoint: &str, method: DohMethod, config: Arc<rustls::ClientConfig>)
+	-> Result<Self, Error> {
+		Ok(TokioDohTransport {
+			url: parse_doh_url(endpoint)?, method, config, stream: None, buf: Vec::new(),
+			queued: VecDeque::new(),
+		})
+	}
+
+	async fn connect(&self) -> Result<TokioDohStream, Error> {
+		let stream = TokioTcpStream::connect((self.url.host.as_str(), self.url.port)).await?;
+		if self.url.tls {
+			let tls = TokioTlsStream::connect(stream, &self.url.host, Arc::clone(&self.config)).await?;
+			Ok(TokioDohStream::Tls(Box::new(tls)))
+		} else {
+			Ok(TokioDohStream::Plain(stream))
+		}
+	}
+
+	async fn try_exchange(&mut self, request: &[u8]) -> Result<QueryBuf, Error> {
+		if self.stream.is_none() {
+			self.stream = Some(self.connect().await?);
+			self.buf.clear();
+		}
+		let stream = self.stream.as_mut().expect("Just set");
+		match stream {
+			TokioDohStream::Plain(stream) => stream.write_all(request).await?,
+			TokioDohStream::Tls(stream) => stream.write_all(request).await?,
+		}
+		let resp = loop {
+			if let Some(resp) = take_http_response(&mut self.buf)? { break resp; }
+			match stream {
+				TokioDohStream::Plain(stream) => {
+					let mut read_buf = [0; 4096];
+					let len = stream.read(&mut read_buf).await?;
+					if len == 0 { return Err(Error::new(ErrorKind::UnexpectedEof, "DoH connection closed")); }
+					self.buf.extend_from_slice(&read_buf[..len]);
+				},
+				TokioDohStream::Tls(stream) => stream.read(&mut self.buf).await?,
+			}
+		};
+		if resp.close { self.stream = None; }
+		Ok(resp.body)
+	}
+
+	async fn exchange(&mut self, query: &QueryBuf) -> Result<QueryBuf, Error> {
+		let request = build_http_request(&self.url, self.method, query);
+		let reused = self.stream.is_some();
+		let mut res = self.try_exchange(&request).await;
+		if reused && matches!(&res, Err(e) if is_connection_closed(e)) {
+			// The server may have closed an idle keep-alive connection, so retry once.
+			self.stream = None;
+			res = self.try_exchange(&request).await;
+		}
+		if res.is_err() { self.stream = None; }
+		res
+	}
+}
+
+#[cfg(all(feature = "doh", feature = "tokio"))]
+impl AsyncTransport for TokioDohTransport {
+	fn send_query<'a>(&'a mut self, query: &'a QueryBuf) -> AsyncTransportFuture<'a, ()> {
+		self.queued.push_back(query.clone());
+		Box::pin(async { Ok(()) })
+	}
+	fn read_response<'a>(&'a mut self) -> AsyncTransportFuture<'a, QueryBuf> {
+		Box::pin(async move {
+			let query = self.queued.pop_front()
+				.ok_or_else(|| Error::new(ErrorKind::Other, "No queries awaiting responses"))?;
+			self.exchange(&query).await
+		})
+	}
+}
+
+#[cfg(feature = "std")]
+fn bind_addr(resolver: SocketAddr) -> SocketAddr {
+	if resolver.is_ipv4() {
+		(std::net::Ipv4Addr::UNSPECIFIED, 0).into()
+	} else {
+		(std::net::Ipv6Addr::UNSPECIFIED, 0).into()
+	}
+}
+
+#[cfg(feature = "std")]
+macro_rules! build_proof_impl {
+	($transport: ident, $queries: expr $(, $async_ok: tt)?) => { {
+		// We require the initial query to have already gone out, and assume our resolver will
+		// return any CNAMEs all the way to the final record in the response. From there, we just
+		// have to take any RRSIGs in the response and walk them up to the root. We do so
+		// iteratively, sending DNSKEY and DS lookups after every response, deduplicating requests
+		// using `dnskeys_requested`.
+		let (mut builder, initial_queries) = ProofBuilder::new_multi($queries);
+		for query in initial_queries {
+			$transport.send_query(&query)
+				$(.await?; $async_ok)??; // Either await?; Ok(())?, or just ?
+		}
+		while builder.awaiting_responses() {
+			let response = $transport.read_response()
+				$(.await?; $async_ok)??; // Either await?; Ok(())?, or just ?
+			let new_queries = builder.process_response(&response)
+				.map_err(|()| Error::new(ErrorKind::Other, "Bad response"))?;
+			for query in new_queries {
+				$transport.send_query(&query)
+					$(.await?; $async_ok)??; // Either await?; Ok(())?, or just ?
+			}
+		}
+
+		builder.finish_proof()
+			.map_err(|()| Error::new(ErrorKind::Other, "Too many requests required"))
+	} }
+}
+
+/// Builds one combined DNSSEC proof for records of each of the given (name, type) pairs by
+/// querying a recursive resolver over the given [`Transport`], returning the proof as well as the
+/// TTL for the proof provided by the recursive resolver.
+///
+/// Any DNSKEY and DS records required by more than one of the queries are only fetched once.
+///
+/// You can find constants for supported standard types in the [`crate::rr`] module.
+///
+/// Note that this proof is NOT verified in any way, you need to use the [`crate::validation`]
+/// module to validate the records contained.
+#[cfg(feature = "std")]
+pub fn build_proof_with<T: Transport>(transport: &mut T, queries: &[(Name, u16)])
+-> Result<(Vec<u8>, u32), Error> {
+	build_proof_impl!(transport, queries)
+}
+
+/// Builds one combined DNSSEC proof for records of each of the given (name, type) pairs by
+/// querying a recursive resolver over the given [`AsyncTransport`], returning the proof as well as the
+/// TTL for the proof provided by the recursive resolver.
+///
+/// Any DNSKEY and DS records required by more than one of the queries are only fetched once.
+///
+/// You can find constants for supported standard types in the [`crate::rr`] module.
+///
+/// Note that this proof is NOT verified in any way, you need to use the [`crate::validation`]
+/// module to validate the records contained.
+#[cfg(feature = "std")]
+pub async fn build_proof_with_async<T: AsyncTransport>(transport: &mut T, queries: &[(Name, u16)])
+-> Result<(Vec<u8>, u32), Error> {
+	build_proof_impl!(transport, queries, { Ok::<(), Error>(()) })
+}
+
+/// Configuration for building proofs using [`build_proof_with_config`] or
+/// [`build_proof_with_config_async`], describing which recursive resolvers to query and how long
+/// to wait for them.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResolverConfig {
+	/// The recursive resolvers to query over TCP, in order of preference. If building a proof
+	/// fails with one resolver due to an I/O error or timeout (after any retries), the next is
+	/// tried.
+	pub resolvers: Vec<SocketAddr>,
+	/// How long to wait for each response (or connection to a resolver) before giving up.
+	pub query_timeout: Duration,
+	/// How long to spend building a proof overall, across all resolvers and retries, before
+	/// giving up.
+	pub overall_timeout: Duration,
+	/// How many times to retry building a proof with each resolver after the first attempt fails
+	/// due to an I/O error or timeout.
+	pub retries: usize,
+}
+
+#[cfg(feature = "std")]
+impl ResolverConfig {
+	/// Constructs a new [`ResolverConfig`] which queries the given resolvers, with a 5 second
+	/// timeout for each query, a 30 second timeout overall and a single retry per resolver.
+	pub fn new(resolvers: Vec<SocketAddr>) -> Self {
+		ResolverConfig {
+			resolvers,
+			query_timeout: Duration::from_secs(5),
+			overall_timeout: Duration::from_secs(30),
+			retries: 1,
+		}
+	}
+}
+
+/// Gets how long we can wait for the next query given the per-query timeout and overall deadline.
+#[cfg(feature = "std")]
+fn time_left(deadline: Instant, query_timeout: Duration) -> Result<Duration, Error> {
+	match deadline.checked_duration_since(Instant::now()) {
+		Some(remaining) if remaining > Duration::ZERO => Ok(cmp::min(remaining, query_timeout)),
+		_ => Err(Error::new(ErrorKind::TimedOut, "Timed out building proof")),
+	}
+}
+
+/// Returns true if building a proof failed due to an I/O error or timeout which may be resolved by
+/// retrying or trying another resolver, rather than the resolver giving us a bad response or the
+/// proof requiring too many queries.
+///
+/// Errors building the proof itself are always reported as [`ErrorKind::Other`], which the
+/// standard library does not use for I/O errors.
+#[cfg(feature = "std")]
+fn is_retryable(e: &Error) -> bool {
+	e.kind() != ErrorKind::Other
+}
+
+/// A [`TcpStream`] which gives up waiting on the resolver once the per-query timeout or overall
+/// deadline passes.
+#[cfg(feature = "std")]
+struct DeadlineTcpStream {
+	stream: TcpStream,
+	query_timeout: Duration,
+	deadline: Instant,
+	/// When we'll give up reading the current response.
+	response_deadline: Instant,
+}
+
+#[cfg(feature = "std")]
+impl Read for DeadlineTcpStream {
+	fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+		// Re-arm the timeout before each read so that a resolver which trickles out its response
+		// can't keep us waiting past the deadline.
+		self.stream.set_read_timeout(Some(time_left(self.response_deadline, self.query_timeout)?))?;
+		self.stream.read(buf)
+	}
+}
+
+#[cfg(feature = "std")]
+impl Transport for DeadlineTcpStream {
+	fn send_query(&mut self, query: &QueryBuf) -> Result<(), Error> {
+		self.stream.set_write_timeout(Some(time_left(self.deadline, self.query_timeout)?))?;
+		send_query(&mut self.stream, query)
+	}
+	fn read_response(&mut self) -> Result<QueryBuf, Error> {
+		self.response_deadline = Instant::now() + time_left(self.deadline, self.query_timeout)?;
+		read_response(self)
+	}
+}
+
+/// Builds one combined DNSSEC proof for records of each of the given (name, type) pairs by
+/// querying the recursive resolvers in the given [`ResolverConfig`] over TCP, returning the proof,
+/// the TTL for the proof provided by the recursive resolver and the address of the resolver which
+/// provided it.
+///
+/// Building the proof is only retried (or another resolver tried) after I/O errors or timeouts. If
+/// a resolver gives us a response we can't use, or the proof requires too many queries, we give
+/// up immediately.
+///
+/// You can find constants for supported standard types in the [`crate::rr`] module.
+///
+/// Note that this proof is NOT verified in any way, you need to use the [`crate::validation`]
+/// module to validate the records contained.
+#[cfg(feature = "std")]
+pub fn build_proof_with_config(config: &ResolverConfig, queries: &[(Name, u16)])
+-> Result<(Vec<u8>, u32, SocketAddr), Error> {
+	let deadline = Instant::now() + config.overall_timeout;
+	let mut err = Error::new(ErrorKind::InvalidInput, "No resolvers configured");
+	for resolver in config.resolvers.iter() {
+		for _ in 0..=config.retries {
+			let res = time_left(deadline, config.query_timeout).and_then(|timeout| {
+				let stream = TcpStream::connect_timeout(resolver, timeout)?;
+				let query_timeout = config.query_timeout;
+				let mut stream = DeadlineTcpStream { stream, query_timeout, deadline, response_deadline: deadline };
+				build_proof_with(&mut stream, queries)
+			});
+			match res {
+				Ok((proof, ttl)) => return Ok((proof, ttl, *resolver)),
+				Err(e) if is_retryable(&e) => err = e,
+				Err(e) => return Err(e),
+			}
+		}
+	}
+	Err(err)
+}
+
+/// Builds one combined DNSSEC proof for records of each of the given (name, type) pairs by
+/// querying a recursive resolver over TCP, returning the proof as well as the TTL for the proof
+/// provided by the recursive resolver.
+///
+/// Any DNSKEY and DS records required by more than one of the queries are only fetched once.
+///
+/// This uses the defaults from [`ResolverConfig::new`]: each query times out after 5 seconds and
+/// building the proof after 30 seconds overall, with building the proof retried once after an I/O
+/// error or timeout. Use [`build_proof_with_config`] to customize this.
+///
+/// You can find constants for supported standard types in the [`crate::rr`] module.
+///
+/// Note that this proof is NOT verified in any way, you need to use the [`crate::validation`]
+/// module to validate the records contained.
+#[cfg(feature = "std")]
+pub fn build_proof(resolver: SocketAddr, queries: &[(Name, u16)]) -> Result<(Vec<u8>, u32), Error> {
+	let (proof, ttl, _) = build_proof_with_config(&ResolverConfig::new(vec![resolver]), queries)?;
+	Ok((proof, ttl))
+}
+
+/// The maximum number of TCP connections we'll have open to a resolver at once when building a
+/// proof async.
+#[cfg(feature = "tokio")]
+const MAX_CONCURRENT_CONNECTIONS: usize = 4;
+
+/// A future which completes with the output of whichever of a set of futures completes first,
+/// removing that future from the set.
+#[cfg(feature = "tokio")]
+struct FirstCompleted<'a, F: Future + Unpin>(&'a mut Vec<F>);
+
+#[cfg(feature = "tokio")]
+impl<'a, F: Future + Unpin> Future for FirstCompleted<'a, F> {
+	type Output = F::Output;
+	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+		for idx in 0..self.0.len() {
+			if let Poll::Ready(res) = Pin::new(&mut self.0[idx]).poll(cx) {
+				self.0.swap_remove(idx);
+				return Poll::Ready(res);
+			}
+		}
+		Poll::Pending
+	}
+}
+
+/// Sends `query` over the given connection (or a fresh one to `resolver`) and reads the response,
+/// returning the connection so that it can be reused.
+#[cfg(feature = "tokio")]
+async fn exchange_async(stream: Option<TokioTcpStream>, resolver: SocketAddr, query: QueryBuf,
+	query_timeout: Duration, deadline: Instant)
+-> Result<(TokioTcpStream, QueryBuf), Error> {
+	let timeout = time_left(deadline, query_timeout)?;
+	let exchange = async move {
+		let mut stream = match stream {
+			Some(stream) => stream,
+			None => TokioTcpStream::connect(resolver).await?,
+		};
+		send_query_async(&mut stream, &query).await?;
+		let response = read_response_async(&mut stream).await?;
+		Ok((stream, response))
+	};
+	tokio_crate::time::timeout(timeout, exchange).await
+		.map_err(|_| Error::new(ErrorKind::TimedOut, "Timed out waiting for a response"))?
+}
+
+#[cfg(feature = "tokio")]
+async fn build_proof_async_attempt(resolver: SocketAddr, queries: &[(Name, u16)],
+	query_timeout: Duration, deadline: Instant)
+-> Result<(Vec<u8>, u32), Error> {
+	// Many resolvers answer queries on a TCP connection one at a time, so rather than pipelining
+	// every query over one connection we spread outstanding queries across several, reusing each
+	// connection once its query has been answered. This way the number of round trips required
+	// scales with the depth of the proof rather than the number of queries.
+	let (mut builder, mut queued) = ProofBuilder::new_multi(queries);
+	let mut idle_streams = Vec::new();
+	let mut in_flight = Vec::with_capacity(MAX_CONCURRENT_CONNECTIONS);
+	while builder.awaiting_responses() {
+		while in_flight.len() < MAX_CONCURRENT_CONNECTIONS {
+			if let Some(query) = queued.pop() {
+				let stream = idle_streams.pop();
+				in_flight.push(Box::pin(exchange_async(stream, resolver, query, query_timeout, deadline)));
+			} else { break; }
+		}
+		if in_flight.is_empty() {
+			return Err(Error::new(ErrorKind::Other, "No queries awaiting responses"));
+		}
+		let (stream, response) = FirstCompleted(&mut in_flight).await?;
+		idle_streams.push(stream);
+		let new_queries = builder.process_response(&response)
+			.map_err(|()| Error::new(ErrorKind::Other, "Bad response"))?;
+		queued.extend(new_queries);
+	}
+
+	builder.finish_proof()
+		.map_err(|()| Error::new(ErrorKind::Other, "Too many requests required"))
+}
+
+/// Builds one combined DNSSEC proof for records of each of the given (name, type) pairs by
+/// querying the recursive resolvers in the given [`ResolverConfig`] over TCP, returning the proof,
+/// the TTL for the proof provided by the recursive resolver and the address of the resolver which
+/// provided it.
+///
+/// Building the proof is only retried (or another resolver tried) after I/O errors or timeouts. If
+/// a resolver gives us a response we can't use, or the proof requires too many queries, we give
+/// up immediately.
+///
+/// You can find constants for supported standard types in the [`crate::rr`] module.
+///
+/// Note that this proof is NOT verified in any way, you need to use the [`crate::validation`]
+/// module to validate the records contained.
+#[cfg(feature = "tokio")]
+pub async fn build_proof_with_config_async(config: &ResolverConfig, queries: &[(Name, u16)])
+-> Result<(Vec<u8>, u32, SocketAddr), Error> {
+	let deadline = Instant::now() + config.overall_timeout;
+	let mut err = Error::new(ErrorKind::InvalidInput, "No resolvers configured");
+	for resolver in config.resolvers.iter() {
+		for _ in 0..=config.retries {
+			let res =
+				build_proof_async_attempt(*resolver, queries, config.query_timeout, deadline).await;
+			match res {
+				Ok((proof, ttl)) => return Ok((proof, ttl, *resolver)),
+				Err(e) if is_retryable(&e) => err = e,
+				Err(e) => return Err(e),
+			}
+		}
+	}
+	Err(err)
+}
+
+/// Builds one combined DNSSEC proof for records of each of the given (name, type) pairs by
+/// querying a recursive resolver over TCP, returning the proof as well as the TTL for the proof
+/// provided by the recursive resolver.
+///
+/// Any DNSKEY and DS records required by more than one of the queries are only fetched once.
+///
+/// This uses the defaults from [`ResolverConfig::new`]: each query times out after 5 seconds and
+/// building the proof after 30 seconds overall, with building the proof retried once after an I/O
+/// error or timeout. Use [`build_proof_with_config_async`] to customize this.
+///
+/// You can find constants for supported standard types in the [`crate::rr`] module.
+///
+/// Note that this proof is NOT verified in any way, you need to use the [`crate::validation`]
+/// module to validate the records contained.
+#[cfg(feature = "tokio")]
+pub async fn build_proof_async(resolver: SocketAddr, queries: &[(Name, u16)]) -> Result<(Vec<u8>, u32), Error> {
+	let config = ResolverConfig::new(vec![resolver]);
+	let (proof, ttl, _) = build_proof_with_config_async(&config, queries).await?;
+	Ok((proof, ttl))
+}
+
+/// Builds one combined DNSSEC proof for records of each of the given (name, type) pairs by
+/// querying a recursive resolver over UDP, returning the proof as well as the TTL for the proof
+/// provided by the recursive resolver.
+///
+/// Queries are retransmitted if the resolver doesn't respond within a second (doubling the timeout
+/// each time, up to twice), and any query for which the resolver's response is truncated (or
+/// larger than the UDP payload size we advertise) is retried over TCP.
+///
+/// You can find constants for supported standard types in the [`crate::rr`] module.
+///
+/// Note that this proof is NOT verified in any way, you need to use the [`crate::validation`]
+/// module to validate the records contained.
+#[cfg(feature = "std")]
+pub fn build_proof_udp(resolver: SocketAddr, queries: &[(Name, u16)]) -> Result<(Vec<u8>, u32), Error> {
+	build_proof_with(&mut UdpTransport::connect(resolver)?, queries)
+}
+
+/// Builds one combined DNSSEC proof for records of each of the given (name, type) pairs by
+/// querying a recursive resolver over UDP, returning the proof as well as the TTL for the proof
+/// provided by the recursive resolver.
+///
+/// Queries are retransmitted if the resolver doesn't respond within a second (doubling the timeout
+/// each time, up to twice), and any query for which the resolver's response is truncated (or
+/// larger than the UDP payload size we advertise) is retried over TCP.
+///
+/// You can find constants for supported standard types in the [`crate::rr`] module.
+///
+/// Note that this proof is NOT verified in any way, you need to use the [`crate::validation`]
+/// module to validate the records contained.
+#[cfg(feature = "tokio")]
+pub async fn build_proof_udp_async(resolver: SocketAddr, queries: &[(Name, u16)]) -> Result<(Vec<u8>, u32), Error> {
+	build_proof_with_async(&mut TokioUdpTransport::connect(resolver).await?, queries).await
+}
+
+/// Builds one combined DNSSEC proof for records of each of the given (name, type) pairs by
+/// querying a recursive resolver over DNS-over-TLS, returning the proof as well as the TTL for
+/// the proof provided by the recursive resolver.
+///
+/// The resolver is authenticated as `server_name` using the Mozilla root certificates from
+/// `webpki-roots`. Use [`DotTransport::connect_with_config`] and [`build_proof_with`] to
+/// customize this.
+///
+/// You can find constants for supported standard types in the [`crate::rr`] module.
+///
+/// Note that this proof is NOT verified in any way, you need to use the [`crate::validation`]
+/// module to validate the records contained.
+#[cfg(feature = "dot")]
+pub fn build_proof_dot(resolver: SocketAddr, server_name: &str, queries: &[(Name, u16)])
+-> Result<(Vec<u8>, u32), Error> {
+	build_proof_with(&mut DotTransport::connect(resolver, server_name)?, queries)
+}
+
+/// Builds one combined DNSSEC proof for records of each of the given (name, type) pairs by
+/// querying a recursive resolver over DNS-over-TLS, returning the proof as well as the TTL for
+/// the proof provided by the recursive resolver.
+///
+/// The resolver is authenticated as `server_name` using the Mozilla root certificates from
+/// `webpki-roots`. Use [`TokioDotTransport::connect_with_config`] and
+/// [`build_proof_with_async`] to customize this.
+///
+/// You can find constants for supported standard types in the [`crate::rr`] module.
+///
+/// Note that this proof is NOT verified in any way, you need to use the [`crate::validation`]
+/// module to validate the records contained.
+#[cfg(all(feature = "dot", feature = "tokio"))]
+pub async fn build_proof_dot_async(resolver: SocketAddr, server_name: &str, queries: &[(Name, u16)])
+-> Result<(Vec<u8>, u32), Error> {
+	let mut transport = TokioDotTransport::connect(resolver, server_name).await?;
+	build_proof_with_async(&mut transport, queries).await
+}
+
+/// Builds one combined DNSSEC proof for records of each of the given (name, type) pairs by
+/// querying a DNS-over-HTTPS server at the given endpoint URL (e.g.
+/// `https://cloudflare-dns.com/dns-query`), returning the proof as well as the TTL for the proof
+/// provided by the recursive resolver.
+///
+/// The server is authenticated using the Mozilla root certificates from `webpki-roots`. Use
+/// [`DohTransport::with_config`] and [`build_proof_with`] to customize this.
+///
+/// You can find constants for supported standard types in the [`crate::rr`] module.
+///
+/// Note that this proof is NOT verified in any way, you need to use the [`crate::validation`]
+/// module to validate the records contained.
+#[cfg(feature = "doh")]
+pub fn build_proof_doh(endpoint: &str, method: DohMethod, queries: &[(Name, u16)])
+-> Result<(Vec<u8>, u32), Error> {
+	build_proof_with(&mut DohTransport::new(endpoint, method)?, queries)
+}
+
+/// Builds one combined DNSSEC proof for records of each of the given (name, type) pairs by
+/// querying a DNS-over-HTTPS server at the given endpoint URL (e.g.
+/// `https://cloudflare-dns.com/dns-query`), returning the proof as well as the TTL for the proof
+/// provided by the recursive resolver.
+///
+/// The server is authenticated using the Mozilla root certificates from `webpki-roots`. Use
+/// [`TokioDohTransport::with_config`] and [`build_proof_with_async`] to customize this.
+///
+/// You can find constants for supported standard types in the [`crate::rr`] module.
+///
+/// Note that this proof is NOT verified in any way, you need to use the [`crate::validation`]
+/// module to validate the records contained.
+#[cfg(all(feature = "doh", feature = "tokio"))]
+pub async fn build_proof_doh_async(endpoint: &str, method: DohMethod, queries: &[(Name, u16)])
+-> Result<(Vec<u8>, u32), Error> {
+	build_proof_with_async(&mut TokioDohTransport::new(endpoint, method)?, queries).await
+}
+
+#[cfg(all(feature = "validation", feature = "std", test))]
+mod tests {
+	use super::*;
+	use crate::validation::*;
+
+	use rand::seq::SliceRandom;
+
+	use std::net::ToSocketAddrs;
+	use std::time::SystemTime;
+
+	#[test]
+	fn test_cloudflare_txt_query() {
+		let sockaddr = "8.8.8.8:53".to_socket_addrs().unwrap().next().unwrap();
+		let query_name: Name = "cloudflare.com.".try_into().unwrap();
+		let (proof, _) = build_proof(sockaddr, &[(query_name.clone(), Txt::TYPE)]).unwrap();
+
+		let mut rrs = parse_rr_stream(&proof).unwrap();
+		rrs.shuffle(&mut rand::rngs::OsRng);
+		let verified_rrs = verify_rr_stream(&rrs).unwrap();
+		assert!(verified_rrs.verified_rrs.len() > 1);
+
+		let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+		assert!(verified_rrs.valid_from < now);
+		assert!(verified_rrs.expires > now);
+	}
+
+	#[test]
+	fn test_sha1_query() {
+		let sockaddr = "8.8.8.8:53".to_socket_addrs().unwrap().next().unwrap();
+		let query_name: Name = "benthecarman.com.".try_into().unwrap();
+		let (proof, _) = build_proof(sockaddr, &[(query_name, A::TYPE)]).unwrap();
+
+		let mut rrs = parse_rr_stream(&proof).unwrap();
+		rrs.shuffle(&mut rand::rngs::OsRng);
+		let verified_rrs = verify_rr_stream(&rrs).unwrap();
+		assert!(verified_rrs.verified_rrs.len() >= 1);
+
+		let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+		assert!(verified_rrs.valid_from < now);
+		assert!(verified_rrs.expires > now);
+	}
+
+	#[test]
+	fn test_txt_query() {
+		let sockaddr = "8.8.8.8:53".to_socket_addrs().unwrap().next().unwrap();
+		let query_name: Name = "matt.user._bitcoin-payment.mattcorallo.com.".try_into().unwrap();
+		let (proof, _) = build_proof(sockaddr, &[(query_name.clone(), Txt::TYPE)]).unwrap();
+
+		let mut rrs = parse_rr_stream(&proof).unwrap();
+		rrs.shuffle(&mut rand::rngs::OsRng);
+		let verified_rrs = verify_rr_stream(&rrs).unwrap();
+		assert_eq!(verified_rrs.verified_rrs.len(), 1);
+
+		let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+		assert!(verified_rrs.valid_from < now);
+		assert!(verified_rrs.expires > now);
+	}
+
+	#[test]
+	fn test_cname_query() {
+		for resolver in ["1.1.1.1:53", "8.8.8.8:53", "9.9.9.9:53"] {
+			let sockaddr = resolver.to_socket_addrs().unwrap().next().unwrap();
+			let query_name: Name = "cname_test.dnssec_proof_tests.bitcoin.ninja.".try_into().unwrap();
+			let (proof, _) = build_proof(sockaddr, &[(query_name.clone(), Txt::TYPE)]).unwrap();
+
+			let mut rrs = parse_rr_stream(&proof).unwrap();
+			rrs.shuffle(&mut rand::rngs::OsRng);
+			let verified_rrs = verify_rr_stream(&rrs).unwrap();
+			assert_eq!(verified_rrs.verified_rrs.len(), 2);
+
+			let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+			assert!(verified_rrs.valid_from < now);
+			assert!(verified_rrs.expires > now);
+
+			let resolved_rrs = verified_rrs.resolve_name(&query_name);
+			assert_eq!(resolved_rrs.len(), 1);
+			if let RR::Txt(txt) = &resolved_rrs[0] {
+				assert_eq!(txt.name.as_str(), "txt_test.dnssec_proof_tests.bitcoin.ninja.");
+				assert_eq!(txt.data, b"dnssec_prover_test");
+			} else { panic!(); }
+		}
+	}
+
+	/// Builds a stand-in recursive resolver's response to `query`.
+	///
+	/// It answers TXT queries for `example.com.` and `big.example.com.` (signed by the root) and
+	/// DNSKEY queries for the root, but always truncates `example.com.` TXT responses over UDP and
+	/// ignores the advertised UDP payload size for `big.example.com.` to exercise the TCP fallback.
+	fn respond(query: &[u8], over_udp: bool) -> Vec<u8> {
+		let query = Message::parse(query).unwrap();
+		assert_eq!(query.edns.as_ref().unwrap().udp_payload_size, 1232);
+		assert!(query.edns.as_ref().unwrap().dnssec_ok);
+		let mut resp = Message {
+			id: query.id, flags: FLAG_RESPONSE | FLAG_RECURSION_AVAILABLE | FLAG_AUTHENTICATED_DATA,
+			rcode: RCODE_NOERROR, questions: query.questions.clone(), answers: Vec::new(),
+			authorities: Vec::new(), additionals: Vec::new(), edns: query.edns.clone(),
+		};
+		let records: &[&str] = match (query.questions[0].name.as_str(), query.questions[0].ty) {
+			("example.com.", Txt::TYPE) if over_udp => {
+				resp.flags |= FLAG_TRUNCATED;
+				&[]
+			},
+			("example.com.", Txt::TYPE) => &[
+				"example.com. IN TXT \"stand-in\"",
+				"example.com. IN RRSIG TXT 13 2 3600 20300101000000 20200101000000 1234 . AAAA",
+			],
+			("big.example.com.", Txt::TYPE) => &[
+				"big.example.com. IN RRSIG TXT 13 3 3600 20300101000000 20200101000000 1234 . AAAA",
+			],
+			(".", DnsKey::TYPE) => &[". IN DNSKEY 257 3 13 AAAA"],
+			_ => &[],
+		};
+		resp.answers = records.iter().map(|rr| (rr.parse().unwrap(), 3600)).collect();
+		if query.questions[0].name.as_str() == "big.example.com." {
+			let txt = Txt { name: query.questions[0].name.clone(), data: vec![b'x'; 1500] };
+			resp.answers.push((txt.into(), 3600));
+		}
+		resp.serialize().unwrap()
+	}
+
+	/// Spawns a stand-in recursive resolver on a local port, listening on both UDP and TCP and
+	/// responding using [`respond`].
+	fn spawn_stand_in_resolver() -> SocketAddr {
+		use std::net::{TcpListener, UdpSocket};
+		let (udp, tcp) = loop {
+			let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+			if let Ok(tcp) = TcpListener::bind(udp.local_addr().unwrap()) { break (udp, tcp); }
+		};
+		let addr = udp.local_addr().unwrap();
+
+		std::thread::spawn(move || {
+			let mut buf = [0; 1232];
+			// Drop the first query, which should be retransmitted
+			udp.recv_from(&mut buf).unwrap();
+			loop {
+				let (len, src) = udp.recv_from(&mut buf).unwrap();
+				// Send stray responses with the wrong transaction ID or question first, which
+				// should be ignored
+				let mut stray = respond(&buf[..len], true);
+				stray[0] ^= 0xff;
+				udp.send_to(&stray, src).unwrap();
+				let mut stray = respond(&buf[..len], true);
+				stray[13] = b'x';
+				udp.send_to(&stray, src).unwrap();
+				udp.send_to(&respond(&buf[..len], true), src).unwrap();
+			}
+		});
+		std::thread::spawn(move || {
+			for stream in tcp.incoming() {
+				let mut stream = stream.unwrap();
+				while let Ok(query) = read_response(&mut stream) {
+					send_query(&mut stream, &respond(&query, false)).unwrap();
+				}
+			}
+		});
+		addr
+	}
+
+	#[test]
+	fn test_udp_truncation_fallback() {
+		let resolver = spawn_stand_in_resolver();
+		let query_name: Name = "example.com.".try_into().unwrap();
+		let (proof, ttl) = build_proof_udp(resolver, &[(query_name.clone(), Txt::TYPE)]).unwrap();
+		assert_eq!(ttl, 3600);
+
+		let rrs = parse_rr_stream(&proof).unwrap();
+		assert_eq!(rrs.len(), 3);
+		assert!(matches!(&rrs[0], RR::Txt(txt) if txt.data == b"stand-in"));
+		assert!(matches!(&rrs[1], RR::RRSig(_)));
+		assert!(matches!(&rrs[2], RR::DnsKey(_)));
+	}
+
+	#[test]
+	fn test_udp_oversized_response_fallback() {
+		let resolver = spawn_stand_in_resolver();
+		let query_name: Name = "big.example.com.".try_into().unwrap();
+		let (proof, _) = build_proof_udp(resolver, &[(query_name.clone(), Txt::TYPE)]).unwrap();
+
+		let rrs = parse_rr_stream(&proof).unwrap();
+		assert_eq!(rrs.len(), 3);
+		assert!(matches!(&rrs[0], RR::RRSig(_)));
+		assert!(matches!(&rrs[1], RR::Txt(txt) if txt.data.len() == 1500));
+		assert!(matches!(&rrs[2], RR::DnsKey(_)));
+	}
+
+	#[test]
+	fn test_udp_multi_query() {
+		let resolver = spawn_stand_in_resolver();
+		let queries: [(Name, u16); 2] = [
+			("example.com.".try_into().unwrap(), Txt::TYPE),
+			("big.example.com.".try_into().unwrap(), Txt::TYPE),
+		];
+		let (proof, _) = build_proof_udp(resolver, &queries).unwrap();
+
+		// Both records are included, but the root DNSKEY is only fetched once.
+		let rrs = parse_rr_stream(&proof).unwrap();
+		assert_eq!(rrs.len(), 5);
+		assert_eq!(rrs.iter().filter(|rr| matches!(rr, RR::DnsKey(_))).count(), 1);
+		assert!(rrs.iter().any(|rr| matches!(rr, RR::Txt(txt) if txt.data == b"stand-in")));
+		assert!(rrs.iter().any(|rr| matches!(rr, RR::Txt(txt) if txt.data.len() == 1500)));
+	}
+
+	/// Spawns a resolver which accepts TCP connections but never responds.
+	fn spawn_silent_resolver() -> SocketAddr {
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let addr = listener.local_addr().unwrap();
+		std::thread::spawn(move || {
+			// Hold on to the connections so that they stay open.
+			let mut streams = Vec::new();
+			for stream in listener.incoming() { streams.push(stream); }
+		});
+		addr
+	}
+
+	fn failover_config(silent: SocketAddr, resolver: SocketAddr) -> ResolverConfig {
+		let mut config = ResolverConfig::new(vec![silent, resolver]);
+		config.query_timeout = Duration::from_millis(100);
+		config
+	}
+
+	#[test]
+	fn test_resolver_failover() {
+		let (silent, resolver) = (spawn_silent_resolver(), spawn_stand_in_resolver());
+		let query_name: Name = "example.com.".try_into().unwrap();
+		let config = failover_config(silent, resolver);
+		let (proof, ttl, used) = build_proof_with_config(&config, &[(query_name.clone(), Txt::TYPE)]).unwrap();
+		assert_eq!((ttl, used), (3600, resolver));
+		assert_eq!(parse_rr_stream(&proof).unwrap().len(), 3);
+
+		// The overall timeout applies across resolvers and retries
+		let mut config = ResolverConfig::new(vec![silent; 3]);
+		config.overall_timeout = Duration::from_millis(200);
+		let start = Instant::now();
+		assert!(build_proof_with_config(&config, &[(query_name.clone(), Txt::TYPE)]).is_err());
+		assert!(start.elapsed() < Duration::from_secs(2));
+
+		let config = ResolverConfig::new(Vec::new());
+		assert!(build_proof_with_config(&config, &[(query_name.clone(), Txt::TYPE)]).is_err());
+	}
+
+	/// Spawns a resolver which answers TCP queries using [`respond`], but either trickles out each
+	/// response a byte at a time or clears the AD flag so that the response can't be used.
+	/// Returns its address and a counter of the connections made to it.
+	fn spawn_misbehaving_resolver(trickle: bool)
+	-> (SocketAddr, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+		use std::sync::Arc;
+		use std::sync::atomic::{AtomicUsize, Ordering};
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let addr = listener.local_addr().unwrap();
+		let connections = Arc::new(AtomicUsize::new(0));
+		let connections_ref = Arc::clone(&connections);
+		std::thread::spawn(move || {
+			for stream in listener.incoming() {
+				connections_ref.fetch_add(1, Ordering::AcqRel);
+				let mut stream = stream.unwrap();
+				std::thread::spawn(move || {
+					while let Ok(query) = read_response(&mut stream) {
+						let mut resp = respond(&query, false);
+						if trickle {
+							let mut framed = (resp.len() as u16).to_be_bytes().to_vec();
+							framed.extend_from_slice(&resp);
+							for b in framed {
+								std::thread::sleep(Duration::from_millis(20));
+								if stream.write_all(&[b]).is_err() { return; }
+							}
+						} else {
+							resp[3] &= !(FLAG_AUTHENTICATED_DATA as u8);
+							send_query(&mut stream, &resp).unwrap();
+						}
+					}
+				});
+			}
+		});
+		(addr, connections)
+	}
+
+	#[test]
+	fn test_resolver_retries() {
+		use std::sync::atomic::Ordering;
+		let query_name: Name = "example.com.".try_into().unwrap();
+		let resolver = spawn_stand_in_resolver();
+
+		// A resolver which trickles out its responses times out, even though each byte arrives well
+		// within the query timeout, and we fail over to the next resolver.
+		let (trickling, connections) = spawn_misbehaving_resolver(true);
+		let config = failover_config(trickling, resolver);
+		let start = Instant::now();
+		let (_, _, used) = build_proof_with_config(&config, &[(query_name.clone(), Txt::TYPE)]).unwrap();
+		assert_eq!(used, resolver);
+		assert!(start.elapsed() < Duration::from_secs(1));
+		assert_eq!(connections.load(Ordering::Acquire), 2);
+
+		// But a resolver which gives us a bad response is neither retried nor failed over from.
+		let (bad, connections) = spawn_misbehaving_resolver(false);
+		let config = failover_config(bad, resolver);
+		let err = build_proof_with_config(&config, &[(query_name.clone(), Txt::TYPE)]).unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::Other);
+		assert_eq!(connections.load(Ordering::Acquire), 1);
+	}
+
+	/// A [`Transport`] which answers queries using [`respond`], in reverse order.
+	struct MockTransport(Vec<QueryBuf>);
+	impl Transport for MockTransport {
+		fn send_query(&mut self, query: &QueryBuf) -> Result<(), Error> {
+			let mut resp = QueryBuf::new_zeroed(0);
+			resp.extend_from_slice(&respond(query, false));
+			self.0.push(resp);
+			Ok(())
+		}
+		fn read_response(&mut self) -> Result<QueryBuf, Error> {
+			self.0.pop().ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, "No queries"))
+		}
+	}
+	impl AsyncTransport for MockTransport {
+		fn send_query<'a>(&'a mut self, query: &'a QueryBuf) -> AsyncTransportFuture<'a, ()> {
+			Box::pin(async move { Transport::send_query(self, query) })
+		}
+		fn read_response<'a>(&'a mut self) -> AsyncTransportFuture<'a, QueryBuf> {
+			Box::pin(async move { Transport::read_response(self) })
+		}
+	}
+
+	#[test]
+	fn test_mock_transport() {
+		let query_name: Name = "example.com.".try_into().unwrap();
+		let mut transport = MockTransport(Vec::new());
+		let (proof, ttl) = build_proof_with(&mut transport, &[(query_name.clone(), Txt::TYPE)]).unwrap();
+		assert_eq!(ttl, 3600);
+		assert_eq!(parse_rr_stream(&proof).unwrap().len(), 3);
+		assert!(transport.0.is_empty());
+
+		assert!(build_proof_with(&mut transport, &[(query_name.clone(), A::TYPE)]).is_err());
+	}
+
+	/// Builds TLS server and client configurations using a self-signed certificate for both
+	/// `dns.example` and `localhost` (with its PKCS#8 private key) from `src/test/`, which
+	/// doesn't expire until the year 9999.
+	#[cfg(feature = "dot")]
+	fn test_tls_configs() -> (Arc<rustls::ServerConfig>, Arc<rustls::ClientConfig>) {
+		use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+		let provider = Arc::new(rustls::crypto::ring::default_provider());
+		let cert = CertificateDer::from(&include_bytes!("test/tls_test_cert.der")[..]);
+		let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(&include_bytes!("test/tls_test_key.der")[..]));
+		let server_config = rustls::ServerConfig::builder_with_provider(Arc::clone(&provider))
+			.with_safe_default_protocol_versions().unwrap()
+			.with_no_client_auth()
+			.with_single_cert(vec![cert.clone()], key).unwrap();
+		let server_config = Arc::new(server_config);
+
+		let mut roots = rustls::RootCertStore::empty();
+		roots.add(cert).unwrap();
+		let client_config = rustls::ClientConfig::builder_with_provider(provider)
+			.with_safe_default_protocol_versions().unwrap()
+			.with_root_certificates(roots)
+			.with_no_client_auth();
+		(server_config, Arc::new(client_config))
+	}
+
+	/// Spawns a stand-in DoT resolver with a self-signed certificate for `dns.example`, which
+	/// checks that queries are padded and responds using [`respond`].
+	///
+	/// Returns the resolver's address and a client configuration which trusts its certificate.
+	#[cfg(feature = "dot")]
+	fn spawn_stand_in_dot_resolver() -> (SocketAddr, Arc<rustls::ClientConfig>) {
+		let (server_config, client_config) = test_tls_configs();
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let addr = listener.local_addr().unwrap();
+		std::thread::spawn(move || {
+			for stream in listener.incoming() {
+				let conn = rustls::ServerConnection::new(Arc::clone(&server_config)).unwrap();
+				let mut stream = rustls::StreamOwned::new(conn, stream.unwrap());
+				std::thread::spawn(move || {
+					while let Ok(query) = read_response(&mut stream) {
+						assert_eq!(query.len() % 128, 0);
+						let edns = Message::parse(&query).unwrap().edns.unwrap();
+						assert_eq!(edns.options.len(), 1);
+						assert_eq!(edns.options[0].code, 12);
+						assert!(edns.options[0].data.iter().all(|b| *b == 0));
+						send_query(&mut stream, &respond(&query, false)).unwrap();
+					}
+				});
+			}
+		});
+		(addr, client_config)
+	}
+
+	#[test]
+	#[cfg(feature = "dot")]
+	fn test_query_padding() {
+		let query = build_query(&"example.com.".try_into().unwrap(), Txt::TYPE);
+		let padded = pad_query(&query);
+		assert_eq!(padded.len(), 128);
+		assert_eq!(&padded[..query.len() - 2], &query[..query.len() - 2]);
+		assert_eq!(Message::parse(&padded).unwrap().edns.unwrap().options[0].data, [0; 128 - 44]);
+
+		let long_name = "a".repeat(63) + "." + &"b".repeat(63) + ".";
+		let padded = pad_query(&build_query(&long_name.as_str().try_into().unwrap(), Txt::TYPE));
+		assert_eq!(padded.len(), 256);
+	}
+
+	#[test]
+	#[cfg(feature = "dot")]
+	fn test_dot_stand_in() {
+		let (resolver, config) = spawn_stand_in_dot_resolver();
+		let query_name: Name = "example.com.".try_into().unwrap();
+		let mut transport = DotTransport::connect_with_config(resolver, "dns.example", Arc::clone(&config)).unwrap();
+		let (proof, ttl) = build_proof_with(&mut transport, &[(query_name.clone(), Txt::TYPE)]).unwrap();
+		assert_eq!(ttl, 3600);
+		assert_eq!(parse_rr_stream(&proof).unwrap().len(), 3);
+
+		// The server name is pinned, so connecting with any other name must fail.
+		assert!(DotTransport::connect_with_config(resolver, "other.example", Arc::clone(&config)).is_err());
+		// As does connecting without trusting the stand-in's certificate.
+		assert!(DotTransport::connect(resolver, "dns.example").is_err());
+	}
+
+	/// Serves DoH requests for `/dns-query` on `stream` using [`respond`], optionally using chunked
+	/// responses or closing the connection after each response.
+	#[cfg(feature = "doh")]
+	fn serve_doh<S: Read + Write>(mut stream: S, chunked: bool, close: bool) {
+		let mut buf = Vec::new();
+		loop {
+			let header_end = loop {
+				if let Some(pos) = find_subslice(&buf, b"\r\n\r\n") { break pos; }
+				let mut read_buf = [0; 4096];
+				match stream.read(&mut read_buf) {
+					Ok(0)|Err(_) => return,
+					Ok(len) => buf.extend_from_slice(&read_buf[..len]),
+				}
+			};
+			let header = std::str::from_utf8(&buf[..header_end]).unwrap().to_owned();
+			let mut lines = header.split("\r\n");
+			let request_line = lines.next().unwrap();
+			let headers = lines.map(|line| line.split_once(": ").unwrap()).collect::<Vec<_>>();
+			assert!(headers.contains(&("Accept", "application/dns-message")));
+			assert!(headers.iter().any(|(name, _)| *name == "Host"));
+
+			let body_start = header_end + 4;
+			let query = if let Some(target) = request_line.strip_prefix("GET ") {
+				let target = target.strip_suffix(" HTTP/1.1").unwrap();
+				let (path, dns) = target.split_once("?dns=").unwrap();
+				assert_eq!(path, "/dns-query");
+				buf.drain(..body_start);
+				crate::base64::decode_url(dns).unwrap()
+			} else {
+				assert_eq!(request_line, "POST /dns-query HTTP/1.1");
+				assert!(headers.contains(&("Content-Type", "application/dns-message")));
+				let len: usize = headers.iter().find(|(name, _)| *name == "Content-Length").unwrap().1.parse().unwrap();
+				while buf.len() < body_start + len {
+					let mut read_buf = [0; 4096];
+					let read_len = stream.read(&mut read_buf).unwrap();
+					buf.extend_from_slice(&read_buf[..read_len]);
+				}
+				buf.drain(..body_start);
+				buf.drain(..len).collect()
+			};
+
+			let body = respond(&query, false);
+			let mut resp = b"HTTP/1.1 200 OK\r\nContent-Type: application/dns-message\r\n".to_vec();
+			if close { resp.extend_from_slice(b"Connection: close\r\n"); }
+			if chunked {
+				resp.extend_from_slice(b"Transfer-Encoding: chunked\r\n\r\n");
+				for chunk in body.chunks(50) {
+					resp.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+					resp.extend_from_slice(chunk);
+					resp.extend_from_slice(b"\r\n");
+				}
+				resp.extend_from_slice(b"0\r\n\r\n");
+			} else {
+				resp.extend_from_slice(format!("Content-Length: {}\r\n\r\n", body.len()).as_bytes());
+				resp.extend_from_slice(&body);
+			}
+			stream.write_all(&resp).unwrap();
+			if close { return; }
+		}
+	}
+
+	/// Spawns a stand-in DoH server on a local port, returning its address and a counter of the
+	/// connections it accepted. If a TLS configuration is given, it serves HTTPS.
+	#[cfg(feature = "doh")]
+	fn spawn_stand_in_doh_server(chunked: bool, close: bool, tls: Option<Arc<rustls::ServerConfig>>)
+	-> (SocketAddr, Arc<std::sync::atomic::AtomicUsize>) {
+		use std::sync::atomic::{AtomicUsize, Ordering};
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let addr = listener.local_addr().unwrap();
+		let connections = Arc::new(AtomicUsize::new(0));
+		let connections_ref = Arc::clone(&connections);
+		std::thread::spawn(move || {
+			for stream in listener.incoming() {
+				connections_ref.fetch_add(1, Ordering::AcqRel);
+				let stream = stream.unwrap();
+				let tls = tls.clone();
+				std::thread::spawn(move || {
+					if let Some(config) = tls {
+						let conn = rustls::ServerConnection::new(config).unwrap();
+						serve_doh(rustls::StreamOwned::new(conn, stream), chunked, close);
+					} else {
+						serve_doh(stream, chunked, close);
+					}
+				});
+			}
+		});
+		(addr, connections)
+	}
+
+	/// Spawns a DoH server which answers the first request on each connection with a fixed body.
+	/// After that, it either closes the connection without warning or answers with an HTTP error.
+	#[cfg(feature = "doh")]
+	fn spawn_flaky_doh_server(close: bool) -> (SocketAddr, Arc<std::sync::atomic::AtomicUsize>) {
+		use std::sync::atomic::{AtomicUsize, Ordering};
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let addr = listener.local_addr().unwrap();
+		let connections = Arc::new(AtomicUsize::new(0));
+		let connections_ref = Arc::clone(&connections);
+		std::thread::spawn(move || {
+			for stream in listener.incoming() {
+				connections_ref.fetch_add(1, Ordering::AcqRel);
+				let mut stream = stream.unwrap();
+				std::thread::spawn(move || {
+					let mut buf = Vec::new();
+					for request in 0.. {
+						// We only expect GET requests, so only need to read up to the end of the headers.
+						while find_subslice(&buf, b"\r\n\r\n").is_none() {
+							let mut read_buf = [0; 4096];
+							match stream.read(&mut read_buf) {
+								Ok(0)|Err(_) => return,
+								Ok(len) => buf.extend_from_slice(&read_buf[..len]),
+							}
+						}
+						buf.clear();
+						if request == 0 {
+							stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Type: application/dns-message\r\nContent-Length: 3\r\n\r\nabc").unwrap();
+						} else if close {
+							return;
+						} else {
+							stream.write_all(b"HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\n\r\n").unwrap();
+						}
+					}
+				});
+			}
+		});
+		(addr, connections)
+	}
+
+	#[test]
+	#[cfg(feature = "doh")]
+	fn test_doh_retries() {
+		use std::sync::atomic::Ordering;
+		let query = build_query(&"example.com.".try_into().unwrap(), Txt::TYPE);
+
+		// A closed keep-alive connection is re-opened and the query retried...
+		let (addr, connections) = spawn_flaky_doh_server(true);
+		let mut transport = DohTransport::new(&format!("http://{}/dns-query", addr), DohMethod::Get).unwrap();
+		for _ in 0..2 {
+			transport.send_query(&query).unwrap();
+			assert_eq!(&*transport.read_response().unwrap(), b"abc");
+		}
+		assert_eq!(connections.load(Ordering::Acquire), 2);
+
+		// ...but HTTP errors are not retried.
+		let (addr, connections) = spawn_flaky_doh_server(false);
+		let mut transport = DohTransport::new(&format!("http://{}/dns-query", addr), DohMethod::Get).unwrap();
+		transport.send_query(&query).unwrap();
+		assert_eq!(&*transport.read_response().unwrap(), b"abc");
+		transport.send_query(&query).unwrap();
+		assert!(transport.read_response().is_err());
+		assert_eq!(connections.load(Ordering::Acquire), 1);
+	}
+
+	#[test]
+	#[cfg(feature = "doh")]
+	fn test_doh_url_parsing() {
+		let url = parse_doh_url("https://cloudflare-dns.com/dns-query").unwrap();
+		assert!(url.tls);
+		assert_eq!((url.host.as_str(), url.port, url.path.as_str()), ("cloudflare-dns.com", 443, "/dns-query"));
+		let url = parse_doh_url("http://[::1]:8053/q?x=y").unwrap();
+		assert!(!url.tls);
+		assert_eq!((url.host.as_str(), url.port, url.authority.as_str()), ("::1", 8053, "[::1]:8053"));
+		assert_eq!(parse_doh_url("http://[::1]").unwrap().port, 80);
+		assert_eq!(parse_doh_url("https://dns.example").unwrap().path, "/");
+
+		let query = build_query(&"example.com.".try_into().unwrap(), Txt::TYPE);
+		let url = parse_doh_url("http://[::1]:8053/q?x=y").unwrap();
+		let get = String::from_utf8(build_http_request(&url, DohMethod::Get, &query)).unwrap();
+		assert!(get.starts_with("GET /q?x=y&dns=AAABIAABAAAAAAAB"));
+		assert!(get.ends_with("Host: [::1]:8053\r\nAccept: application/dns-message\r\n\r\n"));
+
+		for bad in ["dns.example/dns-query", "ftp://dns.example/", "https://:443/", "https://dns.example:x/",
+			"https://user@dns.example/", "https://[::1/"]
+		{
+			assert!(parse_doh_url(bad).is_err());
+		}
+	}
+
+	#[test]
+	#[cfg(feature = "doh")]
+	fn test_http_response_parsing() {
+		let resp = b"HTTP/1.1 200 OK\r\ncontent-type: application/dns-message; x=y\r\nContent-Length: 3\r\n\r\nabcHTTP/1.1";
+		let (parsed, len) = parse_http_response(resp).unwrap().unwrap();
+		assert_eq!(&*parsed.body, b"abc");
+		assert!(!parsed.close);
+		assert_eq!(len, resp.len() - 8);
+		for partial_len in 0..len {
+			assert!(parse_http_response(&resp[..partial_len]).unwrap().is_none());
+		}
+
+		let resp = b"HTTP/1.0 200 OK\r\nContent-Type: application/dns-message\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nab\r\n1;ext\r\nc\r\n0\r\nTrailer: x\r\n\r\n";
+		let (parsed, len) = parse_http_response(resp).unwrap().unwrap();
+		assert_eq!((&*parsed.body, parsed.close, len), (&b"abc"[..], true, resp.len()));
+		assert!(parse_http_response(&resp[..resp.len() - 1]).unwrap().is_none());
+
+		assert!(parse_http_response(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n").is_err());
+		assert!(parse_http_response(b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: 0\r\n\r\n").is_err());
+		assert!(parse_http_response(b"HTTP/1.1 200 OK\r\nContent-Type: application/dns-message\r\n\r\n").is_err());
+		assert!(parse_http_response(&[b'a'; MAX_HTTP_HEADER_LEN + 1]).is_err());
+	}
+
+	#[test]
+	#[cfg(feature = "doh")]
+	fn test_doh_stand_in() {
+		use std::sync::atomic::Ordering;
+		let query_name: Name = "example.com.".try_into().unwrap();
+		for (method, chunked, close) in [(DohMethod::Get, false, false), (DohMethod::Post, true, false),
+			(DohMethod::Get, true, true), (DohMethod::Post, false, true)]
+		{
+			let (addr, connections) = spawn_stand_in_doh_server(chunked, close, None);
+			let endpoint = format!("http://{}/dns-query", addr);
+			let (proof, ttl) = build_proof_doh(&endpoint, method, &[(query_name.clone(), Txt::TYPE)]).unwrap();
+			assert_eq!(ttl, 3600);
+			assert_eq!(parse_rr_stream(&proof).unwrap().len(), 3);
+			// Both queries should use the same connection unless the server closes it.
+			assert_eq!(connections.load(Ordering::Acquire), if close { 2 } else { 1 });
+		}
+
+		let (server_config, client_config) = test_tls_configs();
+		let (addr, connections) = spawn_stand_in_doh_server(false, false, Some(server_config));
+		let endpoint = format!("https://localhost:{}/dns-query", addr.port());
+		let mut transport = DohTransport::with_config(&endpoint, DohMethod::Get, client_config).unwrap();
+		let (proof, _) = build_proof_with(&mut transport, &[(query_name.clone(), Txt::TYPE)]).unwrap();
+		assert_eq!(parse_rr_stream(&proof).unwrap().len(), 3);
+		assert_eq!(connections.load(Ordering::Acquire), 1);
+
+		// The stand-in's certificate is not trusted by default
+		assert!(build_proof_doh(&endpoint, DohMethod::Get, &[(query_name.clone(), Txt::TYPE)]).is_err());
+	}
+
+	#[cfg(feature = "tokio")]
+	use tokio_crate as tokio;
+
+	#[cfg(all(feature = "dot", feature = "tokio"))]
+	#[tokio::test]
+	async fn test_dot_stand_in_async() {
+		let (resolver, config) = spawn_stand_in_dot_resolver();
+		let query_name: Name = "example.com.".try_into().unwrap();
+		let mut transport = TokioDotTransport::connect_with_config(resolver, "dns.example", Arc::clone(&config))
+			.await.unwrap();
+		let (proof, _) = build_proof_with_async(&mut transport, &[(query_name.clone(), Txt::TYPE)]).await.unwrap();
+		assert_eq!(parse_rr_stream(&proof).unwrap().len(), 3);
+
+		assert!(TokioDotTransport::connect_with_config(resolver, "other.example", config).await.is_err());
+	}
+
+	#[cfg(all(feature = "doh", feature = "tokio"))]
+	#[tokio::test]
+	async fn test_doh_stand_in_async() {
+		use std::sync::atomic::Ordering;
+		let query_name: Name = "example.com.".try_into().unwrap();
+		let (addr, connections) = spawn_stand_in_doh_server(true, false, None);
+		let endpoint = format!("http://{}/dns-query", addr);
+		let (proof, _) = build_proof_doh_async(&endpoint, DohMethod::Post, &[(query_name.clone(), Txt::TYPE)]).await.unwrap();
+		assert_eq!(parse_rr_stream(&proof).unwrap().len(), 3);
+		assert_eq!(connections.load(Ordering::Acquire), 1);
+
+		let (server_config, client_config) = test_tls_configs();
+		let (addr, connections) = spawn_stand_in_doh_server(false, true, Some(server_config));
+		let endpoint = format!("https://localhost:{}/dns-query", addr.port());
+		let mut transport = TokioDohTransport::with_config(&endpoint, DohMethod::Get, client_config).unwrap();
+		let (proof, _) = build_proof_with_async(&mut transport, &[(query_name.clone(), Txt::TYPE)]).await.unwrap();
+		assert_eq!(parse_rr_stream(&proof).unwrap().len(), 3);
+		assert_eq!(connections.load(Ordering::Acquire), 2);
+	}
+
+	#[cfg(all(feature = "doh", feature = "tokio"))]
+	#[tokio::test]
+	async fn test_doh_retries_async() {
+		use std::sync::atomic::Ordering;
+		let query = build_query(&"example.com.".try_into().unwrap(), Txt::TYPE);
+
+		let (addr, connections) = spawn_flaky_doh_server(true);
+		let mut transport = TokioDohTransport::new(&format!("http://{}/dns-query", addr), DohMethod::Get).unwrap();
+		for _ in 0..2 {
+			AsyncTransport::send_query(&mut transport, &query).await.unwrap();
+			assert_eq!(&*AsyncTransport::read_response(&mut transport).await.unwrap(), b"abc");
+		}
+		assert_eq!(connections.load(Ordering::Acquire), 2);
+
+		let (addr, connections) = spawn_flaky_doh_server(false);
+		let mut transport = TokioDohTransport::new(&format!("http://{}/dns-query", addr), DohMethod::Get).unwrap();
+		AsyncTransport::send_query(&mut transport, &query).await.unwrap();
+		assert_eq!(&*AsyncTransport::read_response(&mut transport).await.unwrap(), b"abc");
+		AsyncTransport::send_query(&mut transport, &query).await.unwrap();
+		assert!(AsyncTransport::read_response(&mut transport).await.is_err());
+		assert_eq!(connections.load(Ordering::Acquire), 1);
+	}
+
+	#[cfg(feature = "tokio")]
+	#[tokio::test]
+	async fn test_mock_transport_async() {
+		let query_name: Name = "example.com.".try_into().unwrap();
+		let mut transport = MockTransport(Vec::new());
+		let (proof, _) = build_proof_with_async(&mut transport, &[(query_name.clone(), Txt::TYPE)]).await.unwrap();
+		assert_eq!(parse_rr_stream(&proof).unwrap().len(), 3);
+	}
+
+	#[cfg(feature = "tokio")]
+	#[tokio::test]
+	async fn test_udp_truncation_fallback_async() {
+		let resolver = spawn_stand_in_resolver();
+		let query_name: Name = "example.com.".try_into().unwrap();
+		let (proof, _) = build_proof_udp_async(resolver, &[(query_name.clone(), Txt::TYPE)]).await.unwrap();
+		assert_eq!(parse_rr_stream(&proof).unwrap().len(), 3);
+	}
+
+	#[cfg(feature = "tokio")]
+	#[tokio::test]
+	async fn test_udp_oversized_response_fallback_async() {
+		let resolver = spawn_stand_in_resolver();
+		let query_name: Name = "big.example.com.".try_into().unwrap();
+		let (proof, _) = build_proof_udp_async(resolver, &[(query_name.clone(), Txt::TYPE)]).await.unwrap();
+		let rrs = parse_rr_stream(&proof).unwrap();
+		assert!(matches!(&rrs[1], RR::Txt(txt) if txt.data.len() == 1500));
+	}
+
+	/// Builds a stand-in recursive resolver's response to `query`, with records for `example.com.`
+	/// and `_443._tcp.example.com.` forming a proof chain of depth three. Queries for
+	/// `nx.example.com.` get a signed NXDOMAIN and queries for other types at `example.com.` get a
+	/// signed NODATA response.
+	fn respond_chain(query: &[u8]) -> Vec<u8> {
+		let query = Message::parse(query).unwrap();
+		let records: &[&str] = match (query.questions[0].name.as_str(), query.questions[0].ty) {
+			("example.com.", Txt::TYPE) => &[
+				"example.com. IN TXT \"stand-in\"",
+				"example.com. IN RRSIG TXT 13 2 3600 20300101000000 20200101000000 1 example.com. AAAA",
+			],
+			("_443._tcp.example.com.", TLSA::TYPE) => &[
+				"_443._tcp.example.com. IN TLSA 3 1 1 0000",
+				"_443._tcp.example.com. IN RRSIG TLSA 13 4 3600 20300101000000 20200101000000 1 example.com. AAAA",
+			],
+			("example.com.", DnsKey::TYPE) => &[
+				"example.com. IN DNSKEY 257 3 13 AAAA",
+				"example.com. IN RRSIG DNSKEY 13 2 3600 20300101000000 20200101000000 1 example.com. AAAA",
+			],
+			("example.com.", DS::TYPE) => &[
+				"example.com. IN DS 1 13 2 0000",
+				"example.com. IN RRSIG DS 13 2 3600 20300101000000 20200101000000 2 com. AAAA",
+			],
+			("com.", DnsKey::TYPE) => &[
+				"com. IN DNSKEY 257 3 13 AAAA",
+				"com. IN RRSIG DNSKEY 13 1 3600 20300101000000 20200101000000 2 com. AAAA",
+			],
+			("com.", DS::TYPE) => &[
+				"com. IN DS 2 13 2 0000",
+				"com. IN RRSIG DS 13 1 3600 20300101000000 20200101000000 3 . AAAA",
+			],
+			(".", DnsKey::TYPE) => &[". IN DNSKEY 257 3 13 AAAA"],
+			_ => &[],
+		};
+		let name = query.questions[0].name.as_str();
+		let authorities: &[&str] = if records.is_empty() && name.ends_with("example.com.") {
+			&[
+				"example.com. IN SOA ns.example.com. admin.example.com. 1 3600 600 86400 3600",
+				"example.com. IN RRSIG SOA 13 2 3600 20300101000000 20200101000000 1 example.com. AAAA",
+				"example.com. IN NSEC _443._tcp.example.com. TXT RRSIG NSEC DNSKEY SOA DS",
+				"example.com. IN RRSIG NSEC 13 2 3600 20300101000000 20200101000000 1 example.com. AAAA",
+				"_443._tcp.example.com. IN NSEC example.com. TLSA RRSIG NSEC",
+				"_443._tcp.example.com. IN RRSIG NSEC 13 4 3600 20300101000000 20200101000000 1 example.com. AAAA",
+				"example.com. IN NS ns.example.com.",
+			]
+		} else { &[] };
+		Message {
+			id: query.id, flags: FLAG_RESPONSE | FLAG_RECURSION_AVAILABLE | FLAG_AUTHENTICATED_DATA,
+			rcode: if name == "nx.example.com." { RCODE_NXDOMAIN } else { RCODE_NOERROR },
+			questions: query.questions.clone(),
+			answers: records.iter().map(|rr| (rr.parse().unwrap(), 3600)).collect(),
+			authorities: authorities.iter().map(|rr| (rr.parse().unwrap(), 3600)).collect(),
+			additionals: Vec::new(), edns: query.edns.clone(),
+		}.serialize().unwrap()
+	}
+
+	#[test]
+	fn test_negative_proof() {
+		for (name, ty) in [("nx.example.com.", Txt::TYPE), ("example.com.", A::TYPE)] {
+			let query_name: Name = name.try_into().unwrap();
+			let (mut builder, initial_query) = ProofBuilder::new(&query_name, ty);
+			let mut queued = vec![initial_query];
+			while builder.awaiting_responses() {
+				let mut resp = QueryBuf::new_zeroed(0);
+				resp.extend_from_slice(&respond_chain(&queued.pop().unwrap()));
+				queued.extend(builder.process_response(&resp).unwrap());
+			}
+			let (proof, _) = builder.finish_proof().unwrap();
+			let rrs = parse_rr_stream(&proof).unwrap();
+			// The SOA, both NSECs and their signatures, and the DNSKEY/DS chain, but no NS
+			assert_eq!(rrs.len(), 6 + 9);
+			assert!(rrs.iter().any(|rr| matches!(rr, RR::SOA(_))));
+			assert_eq!(rrs.iter().filter(|rr| matches!(rr, RR::NSec(_))).count(), 2);
+			assert!(!rrs.iter().any(|rr| matches!(rr, RR::NS(_))));
+		}
+
+		// Negative responses without any NSEC or NSEC3 records can't be used
+		let (mut builder, initial_query) = ProofBuilder::new(&"nx.example.org.".try_into().unwrap(), Txt::TYPE);
+		let mut resp = QueryBuf::new_zeroed(0);
+		resp.extend_from_slice(&respond_chain(&initial_query));
+		assert!(builder.process_response(&resp).is_err());
+	}
+
+	#[cfg(feature = "validation")]
+	#[test]
+	fn test_bogus_proof_aborts() {
+		let query_name: Name = "example.com.".try_into().unwrap();
+		let (mut builder, initial_query) = ProofBuilder::new(&query_name, Txt::TYPE);
+		builder.enable_validation();
+		let mut queued = vec![initial_query];
+		let mut queried = Vec::new();
+		let mut resp = QueryBuf::new_zeroed(0);
+		while builder.awaiting_responses() {
+			let query = queued.remove(0);
+			let question = Message::parse(&query).unwrap().questions.pop().unwrap();
+			queried.push((question.name, question.ty));
+			resp = QueryBuf::new_zeroed(0);
+			resp.extend_from_slice(&respond_chain(&query));
+			match builder.process_response(&resp) {
+				Ok(new_queries) => queued.extend(new_queries),
+				Err(()) => break,
+			}
+		}
+		// The stand-in signatures are bogus, which is noticed as soon as example.com.'s DNSKEYs
+		// arrive, before we ask for com.'s.
+		assert_eq!(builder.bogus_zone(), Some((&query_name, &ValidationError::Invalid)));
+		assert_eq!(queried.last().unwrap(), &(query_name, DnsKey::TYPE));
+		assert!(!queried.iter().any(|(name, _)| name.as_str() == "com."));
+		assert!(!builder.awaiting_responses());
+		assert!(builder.process_response(&resp).is_err());
+		assert!(builder.finish_proof().is_err());
+	}
+
+	#[test]
+	fn test_multi_query_proof() {
+		let txt_name: Name = "example.com.".try_into().unwrap();
+		let tlsa_name: Name = "_443._tcp.example.com.".try_into().unwrap();
+		let queries = [(txt_name.clone(), Txt::TYPE), (tlsa_name, TLSA::TYPE), (txt_name, Txt::TYPE)];
+		let (mut builder, mut queued) = ProofBuilder::new_multi(&queries);
+		assert_eq!(queued.len(), 2);
+		let mut queries_made = queued.len();
+		while builder.awaiting_responses() {
+			let mut resp = QueryBuf::new_zeroed(0);
+			resp.extend_from_slice(&respond_chain(&queued.pop().unwrap()));
+			let new_queries = builder.process_response(&resp).unwrap();
+			queries_made += new_queries.len();
+			queued.extend(new_queries);
+		}
+		// The DNSKEY and DS queries are shared between both records
+		assert_eq!(queries_made, 7);
+		assert!(builder.clone().finish_proof_envelope(0).is_err());
+		let (proof, ttl) = builder.finish_proof().unwrap();
+		assert_eq!(ttl, 3600);
+		assert_eq!(parse_rr_stream(&proof).unwrap().len(), 13);
+	}
+
+	/// Spawns a stand-in recursive resolver on a local TCP port which answers queries on each
+	/// connection one at a time using [`respond_chain`], returning its address and a counter of
+	/// the connections made to it.
+	///
+	/// The response to the DNSKEY query for `example.com.` is held until three connections have
+	/// been opened, forcing the DS response for `example.com.` to be processed first. This makes
+	/// the number of connections the client needs deterministic.
+	#[cfg(feature = "tokio")]
+	fn spawn_stand_in_chain_resolver() -> (SocketAddr, std::sync::Arc<(std::sync::Mutex<usize>, std::sync::Condvar)>) {
+		use std::net::TcpListener;
+		use std::sync::{Arc, Condvar, Mutex};
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let addr = listener.local_addr().unwrap();
+		let connections = Arc::new((Mutex::new(0), Condvar::new()));
+		let connections_ref = Arc::clone(&connections);
+		std::thread::spawn(move || {
+			for stream in listener.incoming() {
+				let mut stream = stream.unwrap();
+				*connections_ref.0.lock().unwrap() += 1;
+				connections_ref.1.notify_all();
+				let connections = Arc::clone(&connections_ref);
+				std::thread::spawn(move || {
+					while let Ok(query) = read_response(&mut stream) {
+						let question = Message::parse(&query).unwrap().questions.pop().unwrap();
+						if question.name.as_str() == "example.com." && question.ty == DnsKey::TYPE {
+							let count = connections.0.lock().unwrap();
+							drop(connections.1.wait_while(count, |count| *count < 3).unwrap());
+						}
+						send_query(&mut stream, &respond_chain(&query)).unwrap();
+					}
+				});
+			}
+		});
+		(addr, connections)
+	}
+
+	#[cfg(feature = "tokio")]
+	#[tokio::test]
+	async fn test_resolver_failover_async() {
+		let (silent, resolver) = (spawn_silent_resolver(), spawn_stand_in_resolver());
+		let query_name: Name = "example.com.".try_into().unwrap();
+		let config = failover_config(silent, resolver);
+		let (proof, ttl, used) =
+			build_proof_with_config_async(&config, &[(query_name.clone(), Txt::TYPE)]).await.unwrap();
+		assert_eq!((ttl, used), (3600, resolver));
+		assert_eq!(parse_rr_stream(&proof).unwrap().len(), 3);
+
+		let mut config = ResolverConfig::new(vec![silent; 3]);
+		config.overall_timeout = Duration::from_millis(200);
+		let start = Instant::now();
+		assert!(build_proof_with_config_async(&config, &[(query_name.clone(), Txt::TYPE)]).await.is_err());
+		assert!(start.elapsed() < Duration::from_secs(2));
+	}
+
+	#[cfg(feature = "tokio")]
+	#[tokio::test]
+	async fn test_resolver_retries_async() {
+		use std::sync::atomic::Ordering;
+		let query_name: Name = "example.com.".try_into().unwrap();
+		let resolver = spawn_stand_in_resolver();
+
+		let (trickling, connections) = spawn_misbehaving_resolver(true);
+		let config = failover_config(trickling, resolver);
+		let (_, _, used) =
+			build_proof_with_config_async(&config, &[(query_name.clone(), Txt::TYPE)]).await.unwrap();
+		assert_eq!(used, resolver);
+		assert_eq!(connections.load(Ordering::Acquire), 2);
+
+		let (bad, connections) = spawn_misbehaving_resolver(false);
+		let config = failover_config(bad, resolver);
+		let err = build_proof_with_config_async(&config, &[(query_name.clone(), Txt::TYPE)]).await.unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::Other);
+		assert_eq!(connections.load(Ordering::Acquire), 1);
+	}
+
+	#[cfg(feature = "tokio")]
+	#[tokio::test]
+	async fn test_concurrent_queries_async() {
+		let (resolver, connections) = spawn_stand_in_chain_resolver();
+		let query_name: Name = "example.com.".try_into().unwrap();
+		let (proof, ttl) = build_proof_async(resolver, &[(query_name, Txt::TYPE)]).await.unwrap();
+		assert_eq!(ttl, 3600);
+		assert_eq!(parse_rr_stream(&proof).unwrap().len(), 11);
+
+		// The DNSKEY and DS queries for each zone are in flight at the same time, requiring more
+		// than one connection. When the DS response for example.com. arrives, com.'s DNSKEY and DS
+		// queries need a third connection as example.com.'s DNSKEY query is still in flight, but
+		// connections are reused rather than opened for each query after that.
+		assert_eq!(*connections.0.lock().unwrap(), 3);
+	}
 
 	#[cfg(feature = "tokio")]
 	#[tokio::test]
 	async fn test_txt_query_async() {
 		let sockaddr = "8.8.8.8:53".to_socket_addrs().unwrap().next().unwrap();
-		let query_name = "matt.user._bitcoin-payment.mattcorallo.com.".try_into().unwrap();
-		let (proof, _) = build_txt_proof_async(sockaddr, &query_name).await.unwrap();
+		let query_name: Name = "matt.user._bitcoin-payment.mattcorallo.com.".try_into().unwrap();
+		let (proof, _) = build_proof_async(sockaddr, &[(query_name.clone(), Txt::TYPE)]).await.unwrap();
 
 		let mut rrs = parse_rr_stream(&proof).unwrap();
 		rrs.shuffle(&mut rand::rngs::OsRng);
@@ -552,8 +2745,8 @@ mod tests {
 	async fn test_cross_domain_cname_query_async() {
 		for resolver in ["1.1.1.1:53", "8.8.8.8:53", "9.9.9.9:53"] {
 			let sockaddr = resolver.to_socket_addrs().unwrap().next().unwrap();
-			let query_name = "wildcard.x_domain_cname_wild.dnssec_proof_tests.bitcoin.ninja.".try_into().unwrap();
-			let (proof, _) = build_txt_proof_async(sockaddr, &query_name).await.unwrap();
+			let query_name: Name = "wildcard.x_domain_cname_wild.dnssec_proof_tests.bitcoin.ninja.".try_into().unwrap();
+			let (proof, _) = build_proof_async(sockaddr, &[(query_name.clone(), Txt::TYPE)]).await.unwrap();
 
 			let mut rrs = parse_rr_stream(&proof).unwrap();
 			rrs.shuffle(&mut rand::rngs::OsRng);
@@ -578,8 +2771,8 @@ mod tests {
 	async fn test_dname_wildcard_query_async() {
 		for resolver in ["1.1.1.1:53", "8.8.8.8:53", "9.9.9.9:53"] {
 			let sockaddr = resolver.to_socket_addrs().unwrap().next().unwrap();
-			let query_name = "wildcard_a.wildcard_b.dname_test.dnssec_proof_tests.bitcoin.ninja.".try_into().unwrap();
-			let (proof, _) = build_txt_proof_async(sockaddr, &query_name).await.unwrap();
+			let query_name: Name = "wildcard_a.wildcard_b.dname_test.dnssec_proof_tests.bitcoin.ninja.".try_into().unwrap();
+			let (proof, _) = build_proof_async(sockaddr, &[(query_name.clone(), Txt::TYPE)]).await.unwrap();
 
 			let mut rrs = parse_rr_stream(&proof).unwrap();
 			rrs.shuffle(&mut rand::rngs::OsRng);
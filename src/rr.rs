@@ -5,31 +5,40 @@
 
 use alloc::vec::Vec;
 use alloc::string::String;
-use alloc::borrow::ToOwned;
+use alloc::borrow::{Cow, ToOwned};
 use alloc::format;
 
 use core::cmp::{self, Ordering};
 use core::fmt;
 use core::fmt::Write;
 
+use crate::idna;
 use crate::ser::*;
+use crate::zonefile;
 
 /// A valid domain name.
 ///
-/// It must end with a ".", be no longer than 255 bytes, consist of only printable ASCII
-/// characters and each label may be no longer than 63 bytes.
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// It must end with a ".", be no longer than 255 bytes in wire form, and each label must be
+/// between 1 and 63 bytes long.
+///
+/// Labels may contain arbitrary bytes. In the text form (as accepted by [`Name::try_from`] and
+/// returned by [`Name::as_str`]) a `.`, `\` or `"` within a label is escaped with a preceding
+/// `\` and any byte which is not printable ASCII is written as `\DDD` (in decimal), as described
+/// in RFC 1035 section 5.1. Names are canonicalized on construction - ASCII letters are
+/// lowercased and escapes are only used where required - so two [`Name`]s are equal exactly when
+/// they refer to the same domain.
+///
+/// [`Name`]s are ordered by their canonical wire encoding, as required when sorting the RDATA of
+/// records in an RRSet for signing. See RFC 4034 section 6.1 for the ordering used in
+/// [`NSec`] chains.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Name(String);
 impl Name {
 	/// Gets the underlying human-readable domain name
 	pub fn as_str(&self) -> &str { &self.0 }
 	/// Gets the number of labels in this name
 	pub fn labels(&self) -> u8 {
-		if self.as_str() == "." {
-			0
-		} else {
-			self.as_str().chars().filter(|c| *c == '.').count() as u8
-		}
+		split_labels(self.as_str()).len() as u8
 	}
 	/// Gets a string containing the last `n` labels in this [`Name`] (which is also a valid name).
 	pub fn trailing_n_labels(&self, n: u8) -> Option<&str> {
@@ -41,9 +50,64 @@ impl Name {
 		} else if n == 0 {
 			Some(".")
 		} else {
-			self.as_str().splitn(labels as usize - n as usize + 1, '.').last()
+			let separators = label_separators(self.as_str());
+			Some(&self.as_str()[separators[(labels - n - 1) as usize] + 1..])
 		}
 	}
+	/// Builds a [`Name`] from a (possibly) internationalized domain name, e.g. as entered by a
+	/// user, converting any non-ASCII labels to their `xn--` Punycode form per IDNA.
+	///
+	/// Unlike [`Name::try_from`], the trailing `.` is optional here.
+	///
+	/// Only the UTS #46 mapping and validation steps which don't require large Unicode tables are
+	/// performed - labels are lowercased, the ideographic (`。`) and fullwidth full stops are
+	/// treated as `.`, and labels with misplaced hyphens, whitespace or control characters are
+	/// rejected. The input should already be in Unicode Normalization Form C.
+	pub fn from_unicode(name: &str) -> Result<Name, ()> {
+		Name::try_from(idna::to_ascii(name)?)
+	}
+	/// Gets the Unicode form of this name for display, decoding any `xn--` Punycode labels.
+	///
+	/// Labels which are not valid Punycode are left in their ASCII form.
+	pub fn to_unicode(&self) -> String {
+		idna::to_unicode(self.as_str())
+	}
+	/// Iterates over the raw (unescaped) bytes of each label in this [`Name`], from left to
+	/// right, excluding the empty root label.
+	pub fn iter_labels(&self) -> impl DoubleEndedIterator<Item = Cow<'_, [u8]>> + ExactSizeIterator {
+		split_labels(self.as_str()).into_iter().map(unescape_label)
+	}
+	/// Gets the [`Name`] with the leftmost label removed, or `None` if this is the root.
+	pub fn parent(&self) -> Option<Name> {
+		if self.as_str() == "." { return None; }
+		let parent = self.trailing_n_labels(self.labels() - 1)?;
+		Some(Name(parent.to_owned()))
+	}
+	/// Checks whether this [`Name`] is equal to or below `zone` in the DNS hierarchy.
+	///
+	/// Names are compared label-by-label, so `badexample.com.` is *not* a subdomain of
+	/// `example.com.`.
+	pub fn is_subdomain_of(&self, zone: &Name) -> bool {
+		name_in_zone(self.as_str(), zone.as_str())
+	}
+	/// Gets the longest [`Name`] which both this [`Name`] and `other` are subdomains of.
+	pub fn common_ancestor(&self, other: &Name) -> Name {
+		let common_labels = split_labels(self.as_str()).into_iter().rev()
+			.zip(split_labels(other.as_str()).into_iter().rev())
+			.take_while(|(a, b)| a == b)
+			.count();
+		let ancestor = self.trailing_n_labels(common_labels as u8)
+			.expect("We can't have more labels in common than we have labels");
+		Name(ancestor.to_owned())
+	}
+	/// Compares this [`Name`] with `other` in the canonical DNS name order described in RFC 4034
+	/// section 6.1, as used in [`NSec`] chains.
+	///
+	/// Note that this differs from the [`Ord`] implementation on [`Name`], which orders by the
+	/// wire encoding.
+	pub fn canonical_cmp(&self, other: &Name) -> Ordering {
+		nsec_ord(self.as_str(), other.as_str())
+	}
 }
 impl core::ops::Deref for Name {
 	type Target = str;
@@ -54,18 +118,79 @@ impl fmt::Display for Name {
 		self.0.fmt(f)
 	}
 }
+impl Ord for Name {
+	fn cmp(&self, o: &Name) -> Ordering {
+		// Compare the wire encodings, i.e. label by label, comparing the length first.
+		let mut self_labels = split_labels(self.as_str()).into_iter().map(unescape_label);
+		let mut o_labels = split_labels(o.as_str()).into_iter().map(unescape_label);
+		loop {
+			match (self_labels.next(), o_labels.next()) {
+				(Some(a), Some(b)) => {
+					let label_cmp = a.len().cmp(&b.len()).then_with(|| a.cmp(&b));
+					if !label_cmp.is_eq() { return label_cmp; }
+				},
+				(Some(_), None) => return Ordering::Greater,
+				(None, Some(_)) => return Ordering::Less,
+				(None, None) => return Ordering::Equal,
+			}
+		}
+	}
+}
+impl PartialOrd for Name {
+	fn partial_cmp(&self, o: &Name) -> Option<Ordering> { Some(self.cmp(o)) }
+}
 impl TryFrom<String> for Name {
 	type Error = ();
 	fn try_from(s: String) -> Result<Name, ()> {
-		if s.is_empty() { return Err(()); }
-		if *s.as_bytes().last().unwrap_or(&0) != b"."[0] { return Err(()); }
-		if s.len() > 255 { return Err(()); }
-		if s.chars().any(|c| !c.is_ascii_graphic() || c == '"') { return Err(()); }
-		for label in s.split('.') {
-			if label.len() > 63 { return Err(()); }
+		if s == "." { return Ok(Name(s)); }
+
+		let bytes = s.as_bytes();
+		let mut labels = Vec::new();
+		let mut label = Vec::new();
+		let mut pos = 0;
+		while pos < bytes.len() {
+			match bytes[pos] {
+				b'.' => {
+					if label.is_empty() || label.len() > 63 { return Err(()); }
+					labels.push(core::mem::take(&mut label));
+					pos += 1;
+				},
+				b'\\' => {
+					let escaped = *bytes.get(pos + 1).ok_or(())?;
+					if escaped.is_ascii_digit() {
+						let digits = bytes.get(pos + 1..pos + 4).ok_or(())?;
+						let mut val: u16 = 0;
+						for d in digits {
+							if !d.is_ascii_digit() { return Err(()); }
+							val = val * 10 + (d - b'0') as u16;
+						}
+						label.push(val.try_into().map_err(|_| ())?);
+						pos += 4;
+					} else if escaped.is_ascii_graphic() || escaped == b' ' {
+						label.push(escaped);
+						pos += 2;
+					} else {
+						return Err(());
+					}
+				},
+				c if c.is_ascii_graphic() && c != b'"' => {
+					label.push(c);
+					pos += 1;
+				},
+				_ => return Err(()),
+			}
 		}
+		// The name must end with an (unescaped) "."
+		if !label.is_empty() || labels.is_empty() { return Err(()); }
+		if labels.iter().map(|l| l.len() + 1).sum::<usize>() + 1 > 255 { return Err(()); }
 
-		Ok(Name(s.to_ascii_lowercase()))
+		let mut res = String::with_capacity(s.len());
+		for label in labels.iter_mut() {
+			label.make_ascii_lowercase();
+			escape_label(&mut res, label);
+			res.push('.');
+		}
+		Ok(Name(res))
 	}
 }
 impl TryFrom<&str> for Name {
@@ -75,6 +200,122 @@ impl TryFrom<&str> for Name {
 	}
 }
 
+/// Appends the given label to `out`, escaping bytes as required for the text form of a [`Name`].
+pub(crate) fn escape_label(out: &mut String, label: &[u8]) {
+	for c in label {
+		match *c {
+			b'.'|b'\\'|b'"' => { out.push('\\'); out.push(*c as char); },
+			c if c.is_ascii_graphic() => out.push(c as char),
+			c => write!(out, "\\{:03}", c).expect("Write to a String shouldn't fail"),
+		}
+	}
+}
+
+/// Escapes the text form of a [`Name`] for inclusion in a JSON string.
+fn json_name(name: &Name) -> Cow<'_, str> {
+	if name.contains(['\\', '"']) {
+		Cow::Owned(name.replace('\\', "\\\\").replace('"', "\\\""))
+	} else {
+		Cow::Borrowed(name.as_str())
+	}
+}
+
+/// Gets the byte offsets of the (unescaped) `.`s which terminate each label in the text form of a
+/// [`Name`].
+fn label_separators(name: &str) -> Vec<usize> {
+	let bytes = name.as_bytes();
+	let mut res = Vec::new();
+	let mut pos = 0;
+	while pos < bytes.len() {
+		match bytes[pos] {
+			b'\\' if bytes.get(pos + 1).map_or(false, |c| c.is_ascii_digit()) => pos += 4,
+			b'\\' => pos += 2,
+			b'.' => { res.push(pos); pos += 1; },
+			_ => pos += 1,
+		}
+	}
+	res
+}
+
+/// Splits the text form of a [`Name`] into its (still escaped) labels, excluding the root.
+pub(crate) fn split_labels(name: &str) -> Vec<&str> {
+	if name == "." { return Vec::new(); }
+	let mut res = Vec::new();
+	let mut start = 0;
+	for separator in label_separators(name) {
+		res.push(&name[start..separator]);
+		start = separator + 1;
+	}
+	res
+}
+
+/// Compares two names in the canonical order described in RFC 4034 section 6.1.
+pub(crate) fn nsec_ord(a: &str, b: &str) -> Ordering {
+	let mut a_label_iter = split_labels(a).into_iter().rev().map(unescape_label);
+	let mut b_label_iter = split_labels(b).into_iter().rev().map(unescape_label);
+	loop {
+		match (a_label_iter.next(), b_label_iter.next()) {
+			(Some(_), None) => return Ordering::Greater,
+			(None, Some(_)) => return Ordering::Less,
+			(Some(a_label), Some(b_label)) => {
+				let mut a_bytes = a_label.iter().copied();
+				let mut b_bytes = b_label.iter().copied();
+				loop {
+					match (a_bytes.next(), b_bytes.next()) {
+						(Some(_), None) => return Ordering::Greater,
+						(None, Some(_)) => return Ordering::Less,
+						(Some(mut a), Some(mut b)) => {
+							if a.is_ascii_uppercase() {
+								a += b'a' - b'A';
+							}
+							if b.is_ascii_uppercase() {
+								b += b'a' - b'A';
+							}
+							if a != b { return a.cmp(&b); }
+						},
+						(None, None) => break,
+					}
+				}
+			},
+			(None, None) => return Ordering::Equal,
+		}
+	}
+}
+/// Checks whether `name` is equal to or below `zone`, comparing whole labels.
+pub(crate) fn name_in_zone(name: &str, zone: &str) -> bool {
+	let name_labels = split_labels(name);
+	let zone_labels = split_labels(zone);
+	name_labels.len() >= zone_labels.len() &&
+		name_labels[name_labels.len() - zone_labels.len()..] == zone_labels[..]
+}
+
+/// Gets the raw bytes of a label from the text form of a [`Name`].
+pub(crate) fn unescape_label(label: &str) -> Cow<'_, [u8]> {
+	if !label.contains('\\') { return Cow::Borrowed(label.as_bytes()); }
+	let bytes = label.as_bytes();
+	let mut res = Vec::with_capacity(bytes.len());
+	let mut pos = 0;
+	while pos < bytes.len() {
+		if bytes[pos] == b'\\' && pos + 1 < bytes.len() {
+			let digits = &bytes[pos + 1..cmp::min(pos + 4, bytes.len())];
+			if digits.len() == 3 && digits.iter().all(|d| d.is_ascii_digit()) {
+				let val = digits.iter().fold(0u16, |v, d| v * 10 + (d - b'0') as u16);
+				res.push(val as u8);
+				pos += 4;
+			} else {
+				res.push(bytes[pos + 1]);
+				pos += 2;
+			}
+		} else {
+			res.push(bytes[pos]);
+			pos += 1;
+		}
+	}
+	Cow::Owned(res)
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "lowercase"))]
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 /// A supported Resource Record
 ///
@@ -87,6 +328,8 @@ pub enum RR {
 	AAAA(AAAA),
 	/// A name server resource record
 	NS(NS),
+	/// A Start of Authority resource record
+	SOA(SOA),
 	/// A text resource record
 	Txt(Txt),
 	/// A TLS Certificate Association resource record
@@ -99,12 +342,18 @@ pub enum RR {
 	DnsKey(DnsKey),
 	/// A Delegated Signer resource record
 	DS(DS),
+	/// A Child DS resource record
+	CDS(CDS),
+	/// A Child DNS (Public) Key resource record
+	CDnsKey(CDnsKey),
 	/// A Resource Record Signature record
 	RRSig(RRSig),
 	/// A Next Secure Record record
 	NSec(NSec),
 	/// A Next Secure Record version 3 record
 	NSec3(NSec3),
+	/// A Message Digest for DNS Zones record
+	ZoneMD(ZoneMD),
 }
 impl RR {
 	/// Gets the name this record refers to.
@@ -113,15 +362,19 @@ impl RR {
 			RR::A(rr) => &rr.name,
 			RR::AAAA(rr) => &rr.name,
 			RR::NS(rr) => &rr.name,
+			RR::SOA(rr) => &rr.name,
 			RR::Txt(rr) => &rr.name,
 			RR::CName(rr) => &rr.name,
 			RR::DName(rr) => &rr.name,
 			RR::TLSA(rr) => &rr.name,
 			RR::DnsKey(rr) => &rr.name,
 			RR::DS(rr) => &rr.name,
+			RR::CDS(rr) => &rr.name,
+			RR::CDnsKey(rr) => &rr.name,
 			RR::RRSig(rr) => &rr.name,
 			RR::NSec(rr) => &rr.name,
 			RR::NSec3(rr) => &rr.name,
+			RR::ZoneMD(rr) => &rr.name,
 		}
 	}
 	/// Gets a JSON encoding of this record
@@ -130,15 +383,19 @@ impl RR {
 			RR::A(rr) => StaticRecord::json(rr),
 			RR::AAAA(rr) => StaticRecord::json(rr),
 			RR::NS(rr) => StaticRecord::json(rr),
+			RR::SOA(rr) => StaticRecord::json(rr),
 			RR::Txt(rr) => StaticRecord::json(rr),
 			RR::CName(rr) => StaticRecord::json(rr),
 			RR::DName(rr) => StaticRecord::json(rr),
 			RR::TLSA(rr) => StaticRecord::json(rr),
 			RR::DnsKey(rr) => StaticRecord::json(rr),
 			RR::DS(rr) => StaticRecord::json(rr),
+			RR::CDS(rr) => StaticRecord::json(rr),
+			RR::CDnsKey(rr) => StaticRecord::json(rr),
 			RR::RRSig(rr) => StaticRecord::json(rr),
 			RR::NSec(rr) => StaticRecord::json(rr),
 			RR::NSec3(rr) => StaticRecord::json(rr),
+			RR::ZoneMD(rr) => StaticRecord::json(rr),
 		}
 	}
 	fn ty(&self) -> u16 {
@@ -146,15 +403,19 @@ impl RR {
 			RR::A(_) => A::TYPE,
 			RR::AAAA(_) => AAAA::TYPE,
 			RR::NS(_) => NS::TYPE,
+			RR::SOA(_) => SOA::TYPE,
 			RR::Txt(_) => Txt::TYPE,
 			RR::CName(_) => CName::TYPE,
 			RR::DName(_) => DName::TYPE,
 			RR::TLSA(_) => TLSA::TYPE,
 			RR::DnsKey(_) => DnsKey::TYPE,
 			RR::DS(_) => DS::TYPE,
+			RR::CDS(_) => CDS::TYPE,
+			RR::CDnsKey(_) => CDnsKey::TYPE,
 			RR::RRSig(_) => RRSig::TYPE,
 			RR::NSec(_) => NSec::TYPE,
 			RR::NSec3(_) => NSec3::TYPE,
+			RR::ZoneMD(_) => ZoneMD::TYPE,
 		}
 	}
 	fn write_u16_len_prefixed_data<W: Writer>(&self, out: &mut W) {
@@ -162,47 +423,107 @@ impl RR {
 			RR::A(rr) => StaticRecord::write_u16_len_prefixed_data(rr, out),
 			RR::AAAA(rr) => StaticRecord::write_u16_len_prefixed_data(rr, out),
 			RR::NS(rr) => StaticRecord::write_u16_len_prefixed_data(rr, out),
+			RR::SOA(rr) => StaticRecord::write_u16_len_prefixed_data(rr, out),
 			RR::Txt(rr) => StaticRecord::write_u16_len_prefixed_data(rr, out),
 			RR::CName(rr) => StaticRecord::write_u16_len_prefixed_data(rr, out),
 			RR::DName(rr) => StaticRecord::write_u16_len_prefixed_data(rr, out),
 			RR::TLSA(rr) => StaticRecord::write_u16_len_prefixed_data(rr, out),
 			RR::DnsKey(rr) => StaticRecord::write_u16_len_prefixed_data(rr, out),
 			RR::DS(rr) => StaticRecord::write_u16_len_prefixed_data(rr, out),
+			RR::CDS(rr) => StaticRecord::write_u16_len_prefixed_data(rr, out),
+			RR::CDnsKey(rr) => StaticRecord::write_u16_len_prefixed_data(rr, out),
 			RR::RRSig(rr) => StaticRecord::write_u16_len_prefixed_data(rr, out),
 			RR::NSec(rr) => StaticRecord::write_u16_len_prefixed_data(rr, out),
 			RR::NSec3(rr) => StaticRecord::write_u16_len_prefixed_data(rr, out),
+			RR::ZoneMD(rr) => StaticRecord::write_u16_len_prefixed_data(rr, out),
 		}
 	}
-	fn ty_to_rr_name(ty: u16) -> Option<&'static str> {
+	pub(crate) fn write_presentation<W: Write>(&self, ttl: Option<u32>, out: &mut W) -> fmt::Result {
+		match self {
+			RR::A(rr) => write_presentation(rr, ttl, out),
+			RR::AAAA(rr) => write_presentation(rr, ttl, out),
+			RR::NS(rr) => write_presentation(rr, ttl, out),
+			RR::SOA(rr) => write_presentation(rr, ttl, out),
+			RR::Txt(rr) => write_presentation(rr, ttl, out),
+			RR::CName(rr) => write_presentation(rr, ttl, out),
+			RR::DName(rr) => write_presentation(rr, ttl, out),
+			RR::TLSA(rr) => write_presentation(rr, ttl, out),
+			RR::DnsKey(rr) => write_presentation(rr, ttl, out),
+			RR::DS(rr) => write_presentation(rr, ttl, out),
+			RR::CDS(rr) => write_presentation(rr, ttl, out),
+			RR::CDnsKey(rr) => write_presentation(rr, ttl, out),
+			RR::RRSig(rr) => write_presentation(rr, ttl, out),
+			RR::NSec(rr) => write_presentation(rr, ttl, out),
+			RR::NSec3(rr) => write_presentation(rr, ttl, out),
+			RR::ZoneMD(rr) => write_presentation(rr, ttl, out),
+		}
+	}
+	pub(crate) fn ty_to_rr_name(ty: u16) -> Option<&'static str> {
 		match ty {
 			A::TYPE => Some("A"),
 			AAAA::TYPE => Some("AAAA"),
 			NS::TYPE => Some("NS"),
+			SOA::TYPE => Some("SOA"),
 			Txt::TYPE => Some("TXT"),
 			CName::TYPE => Some("CNAME"),
 			DName::TYPE => Some("DNAME"),
 			TLSA::TYPE => Some("TLSA"),
 			DnsKey::TYPE => Some("DNSKEY"),
 			DS::TYPE => Some("DS"),
+			CDS::TYPE => Some("CDS"),
+			CDnsKey::TYPE => Some("CDNSKEY"),
 			RRSig::TYPE => Some("RRSIG"),
 			NSec::TYPE => Some("NSEC"),
 			NSec3::TYPE => Some("NSEC3"),
+			ZoneMD::TYPE => Some("ZONEMD"),
 			_ => None,
 		}
 	}
+	#[cfg(feature = "serde")]
+	pub(crate) fn rr_name_to_ty(name: &str) -> Option<u16> {
+		[A::TYPE, AAAA::TYPE, NS::TYPE, SOA::TYPE, Txt::TYPE, CName::TYPE, DName::TYPE, TLSA::TYPE,
+			DnsKey::TYPE, DS::TYPE, CDS::TYPE, CDnsKey::TYPE, RRSig::TYPE, NSec::TYPE, NSec3::TYPE,
+			ZoneMD::TYPE]
+			.into_iter().find(|ty| Self::ty_to_rr_name(*ty) == Some(name))
+	}
 }
 impl From<A> for RR { fn from(a: A) -> RR { RR::A(a) } }
 impl From<AAAA> for RR { fn from(aaaa: AAAA) -> RR { RR::AAAA(aaaa) } }
 impl From<NS> for RR { fn from(ns: NS) -> RR { RR::NS(ns) } }
+impl From<SOA> for RR { fn from(soa: SOA) -> RR { RR::SOA(soa) } }
 impl From<Txt> for RR { fn from(txt: Txt) -> RR { RR::Txt(txt) } }
 impl From<CName> for RR { fn from(cname: CName) -> RR { RR::CName(cname) } }
 impl From<DName> for RR { fn from(cname: DName) -> RR { RR::DName(cname) } }
 impl From<TLSA> for RR { fn from(tlsa: TLSA) -> RR { RR::TLSA(tlsa) } }
 impl From<DnsKey> for RR { fn from(dnskey: DnsKey) -> RR { RR::DnsKey(dnskey) } }
 impl From<DS> for RR { fn from(ds: DS) -> RR { RR::DS(ds) } }
+impl From<CDS> for RR { fn from(cds: CDS) -> RR { RR::CDS(cds) } }
+impl From<CDnsKey> for RR { fn from(cdnskey: CDnsKey) -> RR { RR::CDnsKey(cdnskey) } }
 impl From<RRSig> for RR { fn from(rrsig: RRSig) -> RR { RR::RRSig(rrsig) } }
 impl From<NSec> for RR { fn from(nsec: NSec) -> RR { RR::NSec(nsec) } }
 impl From<NSec3> for RR { fn from(nsec3: NSec3) -> RR { RR::NSec3(nsec3) } }
+impl From<ZoneMD> for RR { fn from(zonemd: ZoneMD) -> RR { RR::ZoneMD(zonemd) } }
+
+/// Formats the record in the zone file presentation format, without a TTL.
+impl fmt::Display for RR {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { self.write_presentation(None, f) }
+}
+impl fmt::Display for A { fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write_presentation(self, None, f) } }
+impl fmt::Display for AAAA { fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write_presentation(self, None, f) } }
+impl fmt::Display for NS { fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write_presentation(self, None, f) } }
+impl fmt::Display for SOA { fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write_presentation(self, None, f) } }
+impl fmt::Display for Txt { fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write_presentation(self, None, f) } }
+impl fmt::Display for CName { fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write_presentation(self, None, f) } }
+impl fmt::Display for DName { fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write_presentation(self, None, f) } }
+impl fmt::Display for TLSA { fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write_presentation(self, None, f) } }
+impl fmt::Display for DnsKey { fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write_presentation(self, None, f) } }
+impl fmt::Display for DS { fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write_presentation(self, None, f) } }
+impl fmt::Display for CDS { fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write_presentation(self, None, f) } }
+impl fmt::Display for CDnsKey { fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write_presentation(self, None, f) } }
+impl fmt::Display for RRSig { fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write_presentation(self, None, f) } }
+impl fmt::Display for NSec { fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write_presentation(self, None, f) } }
+impl fmt::Display for NSec3 { fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write_presentation(self, None, f) } }
+impl fmt::Display for ZoneMD { fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write_presentation(self, None, f) } }
 
 pub(crate) trait StaticRecord : Ord + Sized {
 	// http://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-4
@@ -211,6 +532,21 @@ pub(crate) trait StaticRecord : Ord + Sized {
 	fn json(&self) -> String;
 	fn write_u16_len_prefixed_data<W: Writer>(&self, out: &mut W);
 	fn read_from_data(name: Name, data: &[u8], wire_packet: &[u8]) -> Result<Self, ()>;
+	/// Writes the record data in the presentation format (without a trailing newline).
+	fn write_presentation_rdata<W: Write>(&self, out: &mut W) -> fmt::Result;
+	/// Reads the record data from the presentation format, resolving relative names against
+	/// `origin`. Any trailing tokens are left in `rdata`.
+	fn read_from_presentation(name: Name, rdata: &mut &[&str], origin: Option<&Name>) -> Result<Self, ()>;
+}
+
+/// Writes a record in the presentation format, i.e. `name [ttl] IN TYPE rdata`.
+fn write_presentation<RR: StaticRecord, W: Write>(rr: &RR, ttl: Option<u32>, out: &mut W) -> fmt::Result {
+	zonefile::write_name(out, rr.name())?;
+	if let Some(ttl) = ttl { write!(out, " {}", ttl)?; }
+	out.write_str(" IN ")?;
+	zonefile::write_ty(out, RR::TYPE)?;
+	out.write_char(' ')?;
+	rr.write_presentation_rdata(out)
 }
 
 /// A record that can be written to a generic [`Writer`]
@@ -259,6 +595,7 @@ impl Record for RR {
 	}
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq)]
 /// A text resource record, containing arbitrary text data
 pub struct Txt {
@@ -268,6 +605,7 @@ pub struct Txt {
 	///
 	/// While this is generally UTF-8-valid, there is no specific requirement that it be, and thus
 	/// is an arbitrary series of bytes here.
+	#[cfg_attr(feature = "serde", serde(rename = "contents", with = "crate::serde_utils::txt"))]
 	pub data: Vec<u8>,
 }
 /// The wire type for TXT records
@@ -298,10 +636,10 @@ impl StaticRecord for Txt {
 	fn json(&self) -> String {
 		if let Ok(s) = core::str::from_utf8(&self.data) {
 			if s.chars().all(|c| !c.is_control() && c != '"' && c != '\\') {
-				return format!("{{\"type\":\"txt\",\"name\":\"{}\",\"contents\":\"{}\"}}", self.name.0, s);
+				return format!("{{\"type\":\"txt\",\"name\":\"{}\",\"contents\":\"{}\"}}", json_name(&self.name), s);
 			}
 		}
-		format!("{{\"type\":\"txt\",\"name\":\"{}\",\"contents\":{:?}}}", self.name.0, &self.data[..])
+		format!("{{\"type\":\"txt\",\"name\":\"{}\",\"contents\":{:?}}}", json_name(&self.name), &self.data[..])
 	}
 	fn read_from_data(name: Name, mut data: &[u8], _wire_packet: &[u8]) -> Result<Self, ()> {
 		let mut parsed_data = Vec::with_capacity(data.len().saturating_sub(1));
@@ -329,8 +667,19 @@ impl StaticRecord for Txt {
 			}
 		}
 	}
+	fn write_presentation_rdata<W: Write>(&self, out: &mut W) -> fmt::Result {
+		zonefile::write_char_strings(out, &self.data)
+	}
+	fn read_from_presentation(name: Name, rdata: &mut &[&str], _origin: Option<&Name>) -> Result<Self, ()> {
+		let mut data = Vec::new();
+		while !rdata.is_empty() {
+			data.extend_from_slice(&zonefile::read_char_string(rdata)?);
+		}
+		Ok(Txt { name, data })
+	}
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 /// A TLS Certificate Association resource record containing information about the TLS certificate
 /// which should be expected when communicating with the host at the given name.
@@ -342,12 +691,14 @@ pub struct TLSA {
 	pub name: Name,
 	/// The type of constraint on the TLS certificate(s) used which should be enforced by this
 	/// record.
+	#[cfg_attr(feature = "serde", serde(rename = "usage"))]
 	pub cert_usage: u8,
 	/// Whether to match on the full certificate, or only the public key.
 	pub selector: u8,
 	/// The type of data included which is used to match the TLS certificate(s).
 	pub data_ty: u8,
 	/// The certificate data or hash of the certificate data itself.
+	#[cfg_attr(feature = "serde", serde(with = "crate::serde_utils::hex"))]
 	pub data: Vec<u8>,
 }
 /// The wire type for TLSA records
@@ -359,7 +710,7 @@ impl StaticRecord for TLSA {
 		let mut out = String::with_capacity(128+self.data.len()*2);
 		write!(&mut out,
 			"{{\"type\":\"tlsa\",\"name\":\"{}\",\"usage\":{},\"selector\":{},\"data_ty\":{},\"data\":\"",
-			self.name.0, self.cert_usage, self.selector, self.data_ty
+			json_name(&self.name), self.cert_usage, self.selector, self.data_ty
 		).expect("Write to a String shouldn't fail");
 		for c in self.data.iter() {
 			write!(&mut out, "{:02X}", c)
@@ -380,8 +731,19 @@ impl StaticRecord for TLSA {
 		out.write(&[self.cert_usage, self.selector, self.data_ty]);
 		out.write(&self.data);
 	}
+	fn write_presentation_rdata<W: Write>(&self, out: &mut W) -> fmt::Result {
+		write!(out, "{} {} {} ", self.cert_usage, self.selector, self.data_ty)?;
+		zonefile::write_hex(out, &self.data)
+	}
+	fn read_from_presentation(name: Name, rdata: &mut &[&str], _origin: Option<&Name>) -> Result<Self, ()> {
+		Ok(TLSA {
+			name, cert_usage: zonefile::read_u8(rdata)?, selector: zonefile::read_u8(rdata)?,
+			data_ty: zonefile::read_u8(rdata)?, data: zonefile::read_hex_rest(rdata)?,
+		})
+	}
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 /// A Canonical Name resource record, referring all queries for this name to another name.
 pub struct CName {
@@ -397,20 +759,25 @@ impl StaticRecord for CName {
 	fn name(&self) -> &Name { &self.name }
 	fn json(&self) -> String {
 		format!("{{\"type\":\"cname\",\"name\":\"{}\",\"canonical_name\":\"{}\"}}",
-			self.name.0, self.canonical_name.0)
+			json_name(&self.name), json_name(&self.canonical_name))
 	}
 	fn read_from_data(name: Name, mut data: &[u8], wire_packet: &[u8]) -> Result<Self, ()> {
-		let res = CName { name, canonical_name: read_wire_packet_name(&mut data, wire_packet)? };
-		debug_assert!(data.is_empty());
-		Ok(res)
+		Ok(CName { name, canonical_name: read_wire_packet_name(&mut data, wire_packet)? })
 	}
 	fn write_u16_len_prefixed_data<W: Writer>(&self, out: &mut W) {
 		let len: u16 = name_len(&self.canonical_name);
 		out.write(&len.to_be_bytes());
 		write_name(out, &self.canonical_name);
 	}
+	fn write_presentation_rdata<W: Write>(&self, out: &mut W) -> fmt::Result {
+		zonefile::write_name(out, &self.canonical_name)
+	}
+	fn read_from_presentation(name: Name, rdata: &mut &[&str], origin: Option<&Name>) -> Result<Self, ()> {
+		Ok(CName { name, canonical_name: zonefile::read_name(rdata, origin)? })
+	}
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 /// A Delegation Name resource record, referring all queries for subdomains of this name to another
 /// subtree of the DNS.
@@ -428,21 +795,26 @@ impl StaticRecord for DName {
 	fn name(&self) -> &Name { &self.name }
 	fn json(&self) -> String {
 		format!("{{\"type\":\"dname\",\"name\":\"{}\",\"delegation_name\":\"{}\"}}",
-			self.name.0, self.delegation_name.0)
+			json_name(&self.name), json_name(&self.delegation_name))
 	}
 	fn read_from_data(name: Name, mut data: &[u8], wire_packet: &[u8]) -> Result<Self, ()> {
-		let res = DName { name, delegation_name: read_wire_packet_name(&mut data, wire_packet)? };
-		debug_assert!(data.is_empty());
-		Ok(res)
+		Ok(DName { name, delegation_name: read_wire_packet_name(&mut data, wire_packet)? })
 	}
 	fn write_u16_len_prefixed_data<W: Writer>(&self, out: &mut W) {
 		let len: u16 = name_len(&self.delegation_name);
 		out.write(&len.to_be_bytes());
 		write_name(out, &self.delegation_name);
 	}
+	fn write_presentation_rdata<W: Write>(&self, out: &mut W) -> fmt::Result {
+		zonefile::write_name(out, &self.delegation_name)
+	}
+	fn read_from_presentation(name: Name, rdata: &mut &[&str], origin: Option<&Name>) -> Result<Self, ()> {
+		Ok(DName { name, delegation_name: zonefile::read_name(rdata, origin)? })
+	}
 }
 
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 /// A public key resource record which can be used to validate [`RRSig`]s.
 pub struct DnsKey {
@@ -455,6 +827,7 @@ pub struct DnsKey {
 	/// The algorithm which this public key uses to sign data.
 	pub alg: u8,
 	/// The public key itself.
+	#[cfg_attr(feature = "serde", serde(with = "crate::serde_utils::hex"))]
 	pub pubkey: Vec<u8>,
 }
 impl StaticRecord for DnsKey {
@@ -464,7 +837,7 @@ impl StaticRecord for DnsKey {
 		let mut out = String::with_capacity(128+self.pubkey.len()*2);
 		write!(&mut out,
 			"{{\"type\":\"dnskey\",\"name\":\"{}\",\"flags\":{},\"protocol\":{},\"alg\":{},\"pubkey\":\"",
-			self.name.0, self.flags, self.protocol, self.alg
+			json_name(&self.name), self.flags, self.protocol, self.alg
 		).expect("Write to a String shouldn't fail");
 		for c in self.pubkey.iter() {
 			write!(&mut out, "{:02X}", c)
@@ -487,25 +860,40 @@ impl StaticRecord for DnsKey {
 		out.write(&self.alg.to_be_bytes());
 		out.write(&self.pubkey);
 	}
+	fn write_presentation_rdata<W: Write>(&self, out: &mut W) -> fmt::Result {
+		write!(out, "{} {} {} ", self.flags, self.protocol, self.alg)?;
+		zonefile::write_base64(out, &self.pubkey)
+	}
+	fn read_from_presentation(name: Name, rdata: &mut &[&str], _origin: Option<&Name>) -> Result<Self, ()> {
+		Ok(DnsKey {
+			name, flags: zonefile::read_u16(rdata)?, protocol: zonefile::read_u8(rdata)?,
+			alg: zonefile::read_u8(rdata)?, pubkey: zonefile::read_base64_rest(rdata)?,
+		})
+	}
 }
 impl DnsKey {
 	/// A short (non-cryptographic) digest which can be used to refer to this [`DnsKey`].
 	pub fn key_tag(&self) -> u16 {
-		let mut res = u32::from(self.flags);
-		res += u32::from(self.protocol) << 8;
-		res += u32::from(self.alg);
-		for (idx, b) in self.pubkey.iter().enumerate() {
-			if idx % 2 == 0 {
-				res += u32::from(*b) << 8;
-			} else {
-				res += u32::from(*b);
-			}
+		key_tag(self.flags, self.protocol, self.alg, &self.pubkey)
+	}
+}
+
+fn key_tag(flags: u16, protocol: u8, alg: u8, pubkey: &[u8]) -> u16 {
+	let mut res = u32::from(flags);
+	res += u32::from(protocol) << 8;
+	res += u32::from(alg);
+	for (idx, b) in pubkey.iter().enumerate() {
+		if idx % 2 == 0 {
+			res += u32::from(*b) << 8;
+		} else {
+			res += u32::from(*b);
 		}
-		res += (res >> 16) & 0xffff;
-		(res & 0xffff) as u16
 	}
+	res += (res >> 16) & 0xffff;
+	(res & 0xffff) as u16
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 /// A Delegation Signer resource record which indicates that some alternative [`DnsKey`] can sign
 /// for records in the zone which matches [`self.name`].
@@ -526,6 +914,7 @@ pub struct DS {
 	/// The type of digest used to hash the referred-to [`DnsKey`].
 	pub digest_type: u8,
 	/// The digest itself.
+	#[cfg_attr(feature = "serde", serde(with = "crate::serde_utils::hex"))]
 	pub digest: Vec<u8>,
 }
 impl StaticRecord for DS {
@@ -535,7 +924,7 @@ impl StaticRecord for DS {
 		let mut out = String::with_capacity(128+self.digest.len()*2);
 		write!(&mut out,
 			"{{\"type\":\"ds\",\"name\":\"{}\",\"key_tag\":{},\"alg\":{},\"digest_type\":{},\"digest\":\"",
-			self.name.0, self.key_tag, self.alg, self.digest_type
+			json_name(&self.name), self.key_tag, self.alg, self.digest_type
 		).expect("Write to a String shouldn't fail");
 		for c in self.digest.iter() {
 			write!(&mut out, "{:02X}", c)
@@ -558,8 +947,167 @@ impl StaticRecord for DS {
 		out.write(&self.digest_type.to_be_bytes());
 		out.write(&self.digest);
 	}
+	fn write_presentation_rdata<W: Write>(&self, out: &mut W) -> fmt::Result {
+		write!(out, "{} {} {} ", self.key_tag, self.alg, self.digest_type)?;
+		zonefile::write_hex(out, &self.digest)
+	}
+	fn read_from_presentation(name: Name, rdata: &mut &[&str], _origin: Option<&Name>) -> Result<Self, ()> {
+		Ok(DS {
+			name, key_tag: zonefile::read_u16(rdata)?, alg: zonefile::read_u8(rdata)?,
+			digest_type: zonefile::read_u8(rdata)?, digest: zonefile::read_hex_rest(rdata)?,
+		})
+	}
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// A Child DS resource record, published by a child zone at its apex to indicate the [`DS`]
+/// records it wishes its parent to publish for it (see RFC 7344).
+///
+/// The fields are identical to those of a [`DS`] record. The special form with all fields zero
+/// and a single zero digest byte requests that the parent delete all [`DS`] records for the child
+/// (see RFC 8078).
+pub struct CDS {
+	/// The name this record is at.
+	///
+	/// This is the apex of the child zone which is requesting the [`DS`] update.
+	pub name: Name,
+	/// A short tag which describes the matching [`DnsKey`].
+	pub key_tag: u16,
+	/// The algorithm which the [`DnsKey`] referred to by this [`CDS`] uses.
+	pub alg: u8,
+	/// The type of digest used to hash the referred-to [`DnsKey`].
+	pub digest_type: u8,
+	/// The digest itself.
+	#[cfg_attr(feature = "serde", serde(with = "crate::serde_utils::hex"))]
+	pub digest: Vec<u8>,
+}
+impl StaticRecord for CDS {
+	const TYPE: u16 = 59;
+	fn name(&self) -> &Name { &self.name }
+	fn json(&self) -> String {
+		let mut out = String::with_capacity(128+self.digest.len()*2);
+		write!(&mut out,
+			"{{\"type\":\"cds\",\"name\":\"{}\",\"key_tag\":{},\"alg\":{},\"digest_type\":{},\"digest\":\"",
+			json_name(&self.name), self.key_tag, self.alg, self.digest_type
+		).expect("Write to a String shouldn't fail");
+		for c in self.digest.iter() {
+			write!(&mut out, "{:02X}", c)
+				.expect("Write to a String shouldn't fail");
+		}
+		out += "\"}";
+		out
+	}
+	fn read_from_data(name: Name, mut data: &[u8], _wire_packet: &[u8]) -> Result<Self, ()> {
+		Ok(CDS {
+			name, key_tag: read_u16(&mut data)?, alg: read_u8(&mut data)?,
+			digest_type: read_u8(&mut data)?, digest: data.to_vec(),
+		})
+	}
+	fn write_u16_len_prefixed_data<W: Writer>(&self, out: &mut W) {
+		let len = 2 + 1 + 1 + self.digest.len();
+		out.write(&(len as u16).to_be_bytes());
+		out.write(&self.key_tag.to_be_bytes());
+		out.write(&self.alg.to_be_bytes());
+		out.write(&self.digest_type.to_be_bytes());
+		out.write(&self.digest);
+	}
+	fn write_presentation_rdata<W: Write>(&self, out: &mut W) -> fmt::Result {
+		write!(out, "{} {} {} ", self.key_tag, self.alg, self.digest_type)?;
+		zonefile::write_hex(out, &self.digest)
+	}
+	fn read_from_presentation(name: Name, rdata: &mut &[&str], _origin: Option<&Name>) -> Result<Self, ()> {
+		Ok(CDS {
+			name, key_tag: zonefile::read_u16(rdata)?, alg: zonefile::read_u8(rdata)?,
+			digest_type: zonefile::read_u8(rdata)?, digest: zonefile::read_hex_rest(rdata)?,
+		})
+	}
+}
+impl CDS {
+	/// Returns true if this is the RFC 8078 "delete DS" sentinel, i.e. `0 0 0 00`.
+	pub fn is_delete(&self) -> bool {
+		self.key_tag == 0 && self.alg == 0 && self.digest_type == 0 && self.digest == [0]
+	}
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// A Child DNSKEY resource record, published by a child zone at its apex to indicate the
+/// [`DnsKey`]s which it wishes its parent to publish [`DS`] records for (see RFC 7344).
+///
+/// The fields are identical to those of a [`DnsKey`] record. The special form with zero flags,
+/// protocol 3, algorithm zero and a single zero public key byte requests that the parent delete
+/// all [`DS`] records for the child (see RFC 8078).
+pub struct CDnsKey {
+	/// The name this record is at.
+	///
+	/// This is the apex of the child zone which is requesting the [`DS`] update.
+	pub name: Name,
+	/// Flags which constrain the usage of this public key.
+	pub flags: u16,
+	/// The protocol this key is used for (protocol `3` is DNSSEC).
+	pub protocol: u8,
+	/// The algorithm which this public key uses to sign data.
+	pub alg: u8,
+	/// The public key itself.
+	#[cfg_attr(feature = "serde", serde(with = "crate::serde_utils::hex"))]
+	pub pubkey: Vec<u8>,
+}
+impl StaticRecord for CDnsKey {
+	const TYPE: u16 = 60;
+	fn name(&self) -> &Name { &self.name }
+	fn json(&self) -> String {
+		let mut out = String::with_capacity(128+self.pubkey.len()*2);
+		write!(&mut out,
+			"{{\"type\":\"cdnskey\",\"name\":\"{}\",\"flags\":{},\"protocol\":{},\"alg\":{},\"pubkey\":\"",
+			json_name(&self.name), self.flags, self.protocol, self.alg
+		).expect("Write to a String shouldn't fail");
+		for c in self.pubkey.iter() {
+			write!(&mut out, "{:02X}", c)
+				.expect("Write to a String shouldn't fail");
+		}
+		out += "\"}";
+		out
+	}
+	fn read_from_data(name: Name, mut data: &[u8], _wire_packet: &[u8]) -> Result<Self, ()> {
+		Ok(CDnsKey {
+			name, flags: read_u16(&mut data)?, protocol: read_u8(&mut data)?,
+			alg: read_u8(&mut data)?, pubkey: data.to_vec(),
+		})
+	}
+	fn write_u16_len_prefixed_data<W: Writer>(&self, out: &mut W) {
+		let len = 2 + 1 + 1 + self.pubkey.len();
+		out.write(&(len as u16).to_be_bytes());
+		out.write(&self.flags.to_be_bytes());
+		out.write(&self.protocol.to_be_bytes());
+		out.write(&self.alg.to_be_bytes());
+		out.write(&self.pubkey);
+	}
+	fn write_presentation_rdata<W: Write>(&self, out: &mut W) -> fmt::Result {
+		write!(out, "{} {} {} ", self.flags, self.protocol, self.alg)?;
+		zonefile::write_base64(out, &self.pubkey)
+	}
+	fn read_from_presentation(name: Name, rdata: &mut &[&str], _origin: Option<&Name>) -> Result<Self, ()> {
+		Ok(CDnsKey {
+			name, flags: zonefile::read_u16(rdata)?, protocol: zonefile::read_u8(rdata)?,
+			alg: zonefile::read_u8(rdata)?, pubkey: zonefile::read_base64_rest(rdata)?,
+		})
+	}
+}
+impl CDnsKey {
+	/// A short (non-cryptographic) digest which can be used to refer to this [`CDnsKey`].
+	///
+	/// This is identical to the [`DnsKey::key_tag`] of the equivalent [`DnsKey`].
+	pub fn key_tag(&self) -> u16 {
+		key_tag(self.flags, self.protocol, self.alg, &self.pubkey)
+	}
+	/// Returns true if this is the RFC 8078 "delete DS" sentinel, i.e. `0 3 0 AA==`.
+	pub fn is_delete(&self) -> bool {
+		self.flags == 0 && self.protocol == 3 && self.alg == 0 && self.pubkey == [0]
+	}
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 /// A Resource Record (set) Signature resource record. This contains a signature over all the
 /// resources records of the given type at the given name.
@@ -572,6 +1120,7 @@ pub struct RRSig {
 	///
 	/// All resources records of this type at the same name as [`self.name`] must be signed by
 	/// this [`RRSig`].
+	#[cfg_attr(feature = "serde", serde(rename = "signed_record_type"))]
 	pub ty: u16,
 	/// The algorithm which is being used to sign.
 	///
@@ -581,6 +1130,7 @@ pub struct RRSig {
 	///
 	/// If this is less than the number of labels in [`self.name`], this signature is covering a
 	/// wildcard entry.
+	#[cfg_attr(feature = "serde", serde(rename = "signed_labels"))]
 	pub labels: u8,
 	/// The TTL of the records which this [`RRSig`] is signing.
 	pub orig_ttl: u32,
@@ -601,6 +1151,7 @@ pub struct RRSig {
 	// appear to be a way to actually do that, so instead we have to link to the trait method.
 	pub key_name: Name,
 	/// The signature itself.
+	#[cfg_attr(feature = "serde", serde(with = "crate::serde_utils::hex"))]
 	pub signature: Vec<u8>,
 }
 impl StaticRecord for RRSig {
@@ -609,8 +1160,8 @@ impl StaticRecord for RRSig {
 	fn json(&self) -> String {
 		let mut out = String::with_capacity(256 + self.signature.len()*2);
 		write!(&mut out,
-			"{{\"type\":\"ds\",\"name\":\"{}\",\"signed_record_type\":{},\"alg\":{},\"signed_labels\":{},\"orig_ttl\":{},\"expiration\"{},\"inception\":{},\"key_tag\":{},\"key_name\":\"{}\",\"signature\":\"",
-			self.name.0, self.ty, self.alg, self.labels, self.orig_ttl, self.expiration, self.inception, self.key_tag, self.key_name.0
+			"{{\"type\":\"rrsig\",\"name\":\"{}\",\"signed_record_type\":{},\"alg\":{},\"signed_labels\":{},\"orig_ttl\":{},\"expiration\":{},\"inception\":{},\"key_tag\":{},\"key_name\":\"{}\",\"signature\":\"",
+			json_name(&self.name), self.ty, self.alg, self.labels, self.orig_ttl, self.expiration, self.inception, self.key_tag, json_name(&self.key_name)
 		).expect("Write to a String shouldn't fail");
 		for c in self.signature.iter() {
 			write!(&mut out, "{:02X}", c)
@@ -642,6 +1193,26 @@ impl StaticRecord for RRSig {
 		write_name(out, &self.key_name);
 		out.write(&self.signature);
 	}
+	fn write_presentation_rdata<W: Write>(&self, out: &mut W) -> fmt::Result {
+		zonefile::write_ty(out, self.ty)?;
+		write!(out, " {} {} {} ", self.alg, self.labels, self.orig_ttl)?;
+		zonefile::write_time(out, self.expiration)?;
+		out.write_char(' ')?;
+		zonefile::write_time(out, self.inception)?;
+		write!(out, " {} ", self.key_tag)?;
+		zonefile::write_name(out, &self.key_name)?;
+		out.write_char(' ')?;
+		zonefile::write_base64(out, &self.signature)
+	}
+	fn read_from_presentation(name: Name, rdata: &mut &[&str], origin: Option<&Name>) -> Result<Self, ()> {
+		Ok(RRSig {
+			name, ty: zonefile::read_ty(rdata)?, alg: zonefile::read_u8(rdata)?,
+			labels: zonefile::read_u8(rdata)?, orig_ttl: zonefile::read_u32(rdata)?,
+			expiration: zonefile::read_time(rdata)?, inception: zonefile::read_time(rdata)?,
+			key_tag: zonefile::read_u16(rdata)?, key_name: zonefile::read_name(rdata, origin)?,
+			signature: zonefile::read_base64_rest(rdata)?,
+		})
+	}
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -670,6 +1241,26 @@ impl NSecTypeMask {
 		// DNSSEC's bit fields are in wire order, so the high bit is type 0, etc.
 		f & (1 << (7 - (ty % 8))) != 0
 	}
+	/// Gets the set types, in ascending order.
+	pub(crate) fn types(&self) -> Vec<u16> {
+		let mut types = Vec::new();
+		for (idx, mask) in self.0.iter().enumerate() {
+			if *mask == 0 { continue; }
+			for b in (0..8).rev() {
+				if *mask & (1 << b) != 0 {
+					types.push(((idx as u16) << 3) | (7 - b));
+				}
+			}
+		}
+		types
+	}
+	fn write_presentation<W: Write>(&self, out: &mut W) -> fmt::Result {
+		for ty in self.types() {
+			out.write_char(' ')?;
+			zonefile::write_ty(out, ty)?;
+		}
+		Ok(())
+	}
 	fn write_json(&self, s: &mut String) {
 		*s += "[";
 		let mut have_written = false;
@@ -693,6 +1284,7 @@ impl NSecTypeMask {
 	}
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 /// A Next Secure Record resource record. This indicates a range of possible names for which there
 /// is no such record.
@@ -713,7 +1305,7 @@ impl StaticRecord for NSec {
 		let mut out = String::with_capacity(256 + self.next_name.len());
 		write!(&mut out,
 			"{{\"type\":\"nsec\",\"name\":\"{}\",\"next_name\":\"{}\",\"types\":",
-			self.name.0, self.next_name.0,
+			json_name(&self.name), json_name(&self.next_name),
 		).expect("Write to a String shouldn't fail");
 		self.types.write_json(&mut out);
 		out += "}";
@@ -724,7 +1316,6 @@ impl StaticRecord for NSec {
 			name, next_name: read_wire_packet_name(&mut data, wire_packet)?,
 			types: NSecTypeMask(read_nsec_types_bitmap(&mut data)?),
 		};
-		debug_assert!(data.is_empty());
 		Ok(res)
 	}
 	fn write_u16_len_prefixed_data<W: Writer>(&self, out: &mut W) {
@@ -733,8 +1324,19 @@ impl StaticRecord for NSec {
 		write_name(out, &self.next_name);
 		write_nsec_types_bitmap(out, &self.types.0);
 	}
+	fn write_presentation_rdata<W: Write>(&self, out: &mut W) -> fmt::Result {
+		zonefile::write_name(out, &self.next_name)?;
+		self.types.write_presentation(out)
+	}
+	fn read_from_presentation(name: Name, rdata: &mut &[&str], origin: Option<&Name>) -> Result<Self, ()> {
+		Ok(NSec {
+			name, next_name: zonefile::read_name(rdata, origin)?,
+			types: NSecTypeMask::from_types(&zonefile::read_types_rest(rdata)?),
+		})
+	}
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 /// A Next Secure Record resource record. This indicates a range of possible names for which there
 /// is no such record.
@@ -769,7 +1371,7 @@ impl StaticRecord for NSec3 {
 		let mut out = String::with_capacity(256);
 		write!(&mut out,
 			"{{\"type\":\"nsec3\",\"name\":\"{}\",\"hash_algo\":{},\"flags\":{},\"hash_iterations\":{},\"salt\":{:?},\"next_name_hash\":{:?},\"types\":",
-			self.name.0, self.hash_algo, self.flags, self.hash_iterations, &self.salt[..], &self.next_name_hash[..]
+			json_name(&self.name), self.hash_algo, self.flags, self.hash_iterations, &self.salt[..], &self.next_name_hash[..]
 		).expect("Write to a String shouldn't fail");
 		self.types.write_json(&mut out);
 		out += "}";
@@ -782,7 +1384,6 @@ impl StaticRecord for NSec3 {
 			next_name_hash: read_u8_len_prefixed_bytes(&mut data)?,
 			types: NSecTypeMask(read_nsec_types_bitmap(&mut data)?),
 		};
-		debug_assert!(data.is_empty());
 		Ok(res)
 	}
 	fn write_u16_len_prefixed_data<W: Writer>(&self, out: &mut W) {
@@ -798,8 +1399,93 @@ impl StaticRecord for NSec3 {
 		out.write(&self.next_name_hash);
 		write_nsec_types_bitmap(out, &self.types.0);
 	}
+	fn write_presentation_rdata<W: Write>(&self, out: &mut W) -> fmt::Result {
+		write!(out, "{} {} {} ", self.hash_algo, self.flags, self.hash_iterations)?;
+		if self.salt.is_empty() {
+			out.write_char('-')?;
+		} else {
+			zonefile::write_hex(out, &self.salt)?;
+		}
+		out.write_char(' ')?;
+		zonefile::write_base32(out, &self.next_name_hash)?;
+		self.types.write_presentation(out)
+	}
+	fn read_from_presentation(name: Name, rdata: &mut &[&str], _origin: Option<&Name>) -> Result<Self, ()> {
+		let hash_algo = zonefile::read_u8(rdata)?;
+		let flags = zonefile::read_u8(rdata)?;
+		let hash_iterations = zonefile::read_u16(rdata)?;
+		let salt = zonefile::read_hex(rdata)?;
+		if salt.len() > 255 { return Err(()); }
+		let next_name_hash = zonefile::read_base32(rdata)?;
+		if next_name_hash.is_empty() || next_name_hash.len() > 255 { return Err(()); }
+		let types = NSecTypeMask::from_types(&zonefile::read_types_rest(rdata)?);
+		Ok(NSec3 { name, hash_algo, flags, hash_iterations, salt, next_name_hash, types })
+	}
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// A Message Digest for DNS Zones resource record, containing a digest over the full contents of
+/// the zone at whose apex it appears (see RFC 8976).
+pub struct ZoneMD {
+	/// The name this record is at.
+	///
+	/// This is the apex of the zone which [`Self::digest`] covers.
+	pub name: Name,
+	/// The serial number of the zone's [`SOA`] record at the time the digest was calculated.
+	pub serial: u32,
+	/// The method used to collate the zone's records for hashing. Currently only 1 (SIMPLE) is
+	/// defined.
+	pub scheme: u8,
+	/// The hash algorithm used to calculate [`Self::digest`], 1 for SHA-384 or 2 for SHA-512.
+	pub hash_alg: u8,
+	/// The digest itself.
+	#[cfg_attr(feature = "serde", serde(with = "crate::serde_utils::hex"))]
+	pub digest: Vec<u8>,
+}
+impl StaticRecord for ZoneMD {
+	const TYPE: u16 = 63;
+	fn name(&self) -> &Name { &self.name }
+	fn json(&self) -> String {
+		let mut out = String::with_capacity(128+self.digest.len()*2);
+		write!(&mut out,
+			"{{\"type\":\"zonemd\",\"name\":\"{}\",\"serial\":{},\"scheme\":{},\"hash_alg\":{},\"digest\":\"",
+			json_name(&self.name), self.serial, self.scheme, self.hash_alg
+		).expect("Write to a String shouldn't fail");
+		for c in self.digest.iter() {
+			write!(&mut out, "{:02X}", c)
+				.expect("Write to a String shouldn't fail");
+		}
+		out += "\"}";
+		out
+	}
+	fn read_from_data(name: Name, mut data: &[u8], _wire_packet: &[u8]) -> Result<Self, ()> {
+		Ok(ZoneMD {
+			name, serial: read_u32(&mut data)?, scheme: read_u8(&mut data)?,
+			hash_alg: read_u8(&mut data)?, digest: data.to_vec(),
+		})
+	}
+	fn write_u16_len_prefixed_data<W: Writer>(&self, out: &mut W) {
+		let len = 4 + 1 + 1 + self.digest.len();
+		out.write(&(len as u16).to_be_bytes());
+		out.write(&self.serial.to_be_bytes());
+		out.write(&self.scheme.to_be_bytes());
+		out.write(&self.hash_alg.to_be_bytes());
+		out.write(&self.digest);
+	}
+	fn write_presentation_rdata<W: Write>(&self, out: &mut W) -> fmt::Result {
+		write!(out, "{} {} {} ", self.serial, self.scheme, self.hash_alg)?;
+		zonefile::write_hex(out, &self.digest)
+	}
+	fn read_from_presentation(name: Name, rdata: &mut &[&str], _origin: Option<&Name>) -> Result<Self, ()> {
+		Ok(ZoneMD {
+			name, serial: zonefile::read_u32(rdata)?, scheme: zonefile::read_u8(rdata)?,
+			hash_alg: zonefile::read_u8(rdata)?, digest: zonefile::read_hex_rest(rdata)?,
+		})
+	}
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 /// An IPv4 Address resource record
 pub struct A {
@@ -814,7 +1500,7 @@ impl StaticRecord for A {
 	const TYPE: u16 = A_TYPE;
 	fn name(&self) -> &Name { &self.name }
 	fn json(&self) -> String {
-		format!("{{\"type\":\"a\",\"name\":\"{}\",\"address\":{:?}}}", self.name.0, self.address)
+		format!("{{\"type\":\"a\",\"name\":\"{}\",\"address\":{:?}}}", json_name(&self.name), self.address)
 	}
 	fn read_from_data(name: Name, data: &[u8], _wire_packet: &[u8]) -> Result<Self, ()> {
 		if data.len() != 4 { return Err(()); }
@@ -826,8 +1512,15 @@ impl StaticRecord for A {
 		out.write(&4u16.to_be_bytes());
 		out.write(&self.address);
 	}
+	fn write_presentation_rdata<W: Write>(&self, out: &mut W) -> fmt::Result {
+		zonefile::write_ipv4(out, &self.address)
+	}
+	fn read_from_presentation(name: Name, rdata: &mut &[&str], _origin: Option<&Name>) -> Result<Self, ()> {
+		Ok(A { name, address: zonefile::read_ipv4(rdata)? })
+	}
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 /// An IPv6 Address resource record
 pub struct AAAA {
@@ -842,7 +1535,7 @@ impl StaticRecord for AAAA {
 	const TYPE: u16 = AAAA_TYPE;
 	fn name(&self) -> &Name { &self.name }
 	fn json(&self) -> String {
-		format!("{{\"type\":\"aaaa\",\"name\":\"{}\",\"address\":{:?}}}", self.name.0, self.address)
+		format!("{{\"type\":\"aaaa\",\"name\":\"{}\",\"address\":{:?}}}", json_name(&self.name), self.address)
 	}
 	fn read_from_data(name: Name, data: &[u8], _wire_packet: &[u8]) -> Result<Self, ()> {
 		if data.len() != 16 { return Err(()); }
@@ -854,8 +1547,15 @@ impl StaticRecord for AAAA {
 		out.write(&16u16.to_be_bytes());
 		out.write(&self.address);
 	}
+	fn write_presentation_rdata<W: Write>(&self, out: &mut W) -> fmt::Result {
+		zonefile::write_ipv6(out, &self.address)
+	}
+	fn read_from_presentation(name: Name, rdata: &mut &[&str], _origin: Option<&Name>) -> Result<Self, ()> {
+		Ok(AAAA { name, address: zonefile::read_ipv6(rdata)? })
+	}
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 /// A Name Server resource record, which indicates the server responsible for handling queries for
 /// a zone.
@@ -867,21 +1567,141 @@ pub struct NS {
 	pub name: Name,
 	/// The name of the server which is responsible for handling queries for the [`self.name`]
 	/// zone.
+	#[cfg_attr(feature = "serde", serde(rename = "ns"))]
 	pub name_server: Name,
 }
 impl StaticRecord for NS {
 	const TYPE: u16 = 2;
 	fn name(&self) -> &Name { &self.name }
 	fn json(&self) -> String {
-		format!("{{\"type\":\"ns\",\"name\":\"{}\",\"ns\":\"{}\"}}", self.name.0, self.name_server.0)
+		format!("{{\"type\":\"ns\",\"name\":\"{}\",\"ns\":\"{}\"}}", json_name(&self.name), json_name(&self.name_server))
 	}
 	fn read_from_data(name: Name, mut data: &[u8], wire_packet: &[u8]) -> Result<Self, ()> {
-		let res = NS { name, name_server: read_wire_packet_name(&mut data, wire_packet)? };
-		debug_assert!(data.is_empty());
-		Ok(res)
+		Ok(NS { name, name_server: read_wire_packet_name(&mut data, wire_packet)? })
 	}
 	fn write_u16_len_prefixed_data<W: Writer>(&self, out: &mut W) {
 		out.write(&name_len(&self.name_server).to_be_bytes());
 		write_name(out, &self.name_server);
 	}
+	fn write_presentation_rdata<W: Write>(&self, out: &mut W) -> fmt::Result {
+		zonefile::write_name(out, &self.name_server)
+	}
+	fn read_from_presentation(name: Name, rdata: &mut &[&str], origin: Option<&Name>) -> Result<Self, ()> {
+		Ok(NS { name, name_server: zonefile::read_name(rdata, origin)? })
+	}
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// A Start of Authority resource record, which appears at the apex of every zone and describes
+/// the zone's primary name server, administrative contact and timers.
+pub struct SOA {
+	/// The name this record is at.
+	///
+	/// This is the apex of the zone which this record describes.
+	pub name: Name,
+	/// The name of the primary name server for the zone.
+	pub mname: Name,
+	/// The mailbox of the person responsible for the zone, with the `@` replaced by a `.`.
+	pub rname: Name,
+	/// The version number of the zone, which is incremented on each change.
+	pub serial: u32,
+	/// The number of seconds after which secondary name servers should refresh the zone.
+	pub refresh: u32,
+	/// The number of seconds after which secondary name servers should retry a failed refresh.
+	pub retry: u32,
+	/// The number of seconds after which secondary name servers should stop serving the zone if
+	/// they cannot refresh it.
+	pub expire: u32,
+	/// The TTL which should be used when caching negative responses for names in the zone.
+	pub minimum: u32,
+}
+impl StaticRecord for SOA {
+	const TYPE: u16 = 6;
+	fn name(&self) -> &Name { &self.name }
+	fn json(&self) -> String {
+		format!("{{\"type\":\"soa\",\"name\":\"{}\",\"mname\":\"{}\",\"rname\":\"{}\",\"serial\":{},\"refresh\":{},\"retry\":{},\"expire\":{},\"minimum\":{}}}",
+			json_name(&self.name), json_name(&self.mname), json_name(&self.rname), self.serial, self.refresh, self.retry,
+			self.expire, self.minimum)
+	}
+	fn read_from_data(name: Name, mut data: &[u8], wire_packet: &[u8]) -> Result<Self, ()> {
+		let res = SOA {
+			name, mname: read_wire_packet_name(&mut data, wire_packet)?,
+			rname: read_wire_packet_name(&mut data, wire_packet)?,
+			serial: read_u32(&mut data)?, refresh: read_u32(&mut data)?, retry: read_u32(&mut data)?,
+			expire: read_u32(&mut data)?, minimum: read_u32(&mut data)?,
+		};
+		Ok(res)
+	}
+	fn write_u16_len_prefixed_data<W: Writer>(&self, out: &mut W) {
+		let len = name_len(&self.mname) + name_len(&self.rname) + 4*5;
+		out.write(&len.to_be_bytes());
+		write_name(out, &self.mname);
+		write_name(out, &self.rname);
+		out.write(&self.serial.to_be_bytes());
+		out.write(&self.refresh.to_be_bytes());
+		out.write(&self.retry.to_be_bytes());
+		out.write(&self.expire.to_be_bytes());
+		out.write(&self.minimum.to_be_bytes());
+	}
+	fn write_presentation_rdata<W: Write>(&self, out: &mut W) -> fmt::Result {
+		zonefile::write_name(out, &self.mname)?;
+		out.write_char(' ')?;
+		zonefile::write_name(out, &self.rname)?;
+		write!(out, " {} {} {} {} {}", self.serial, self.refresh, self.retry, self.expire, self.minimum)
+	}
+	fn read_from_presentation(name: Name, rdata: &mut &[&str], origin: Option<&Name>) -> Result<Self, ()> {
+		Ok(SOA {
+			name, mname: zonefile::read_name(rdata, origin)?, rname: zonefile::read_name(rdata, origin)?,
+			serial: zonefile::read_u32(rdata)?, refresh: zonefile::read_u32(rdata)?,
+			retry: zonefile::read_u32(rdata)?, expire: zonefile::read_u32(rdata)?,
+			minimum: zonefile::read_u32(rdata)?,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn name(s: &str) -> Name { s.try_into().unwrap() }
+
+	#[test]
+	fn test_name_hierarchy() {
+		let n = name("a\\.b.Example.com.");
+		let labels: Vec<_> = n.iter_labels().collect();
+		assert_eq!(labels, [&b"a.b"[..], b"example", b"com"]);
+		assert_eq!(n.iter_labels().len(), 3);
+		assert_eq!(n.iter_labels().next_back().unwrap(), &b"com"[..]);
+		assert_eq!(name(".").iter_labels().len(), 0);
+
+		assert_eq!(n.parent().unwrap(), name("example.com."));
+		assert_eq!(name("com.").parent().unwrap(), name("."));
+		assert!(name(".").parent().is_none());
+
+		assert!(n.is_subdomain_of(&name("example.com.")));
+		assert!(n.is_subdomain_of(&n));
+		assert!(n.is_subdomain_of(&name(".")));
+		assert!(!name("badexample.com.").is_subdomain_of(&name("example.com.")));
+		assert!(!name("b.example.com.").is_subdomain_of(&n));
+		assert!(!name("example.com.").is_subdomain_of(&n));
+
+		assert_eq!(n.common_ancestor(&name("b.example.com.")), name("example.com."));
+		assert_eq!(name("badexample.com.").common_ancestor(&name("example.com.")), name("com."));
+		assert_eq!(name("example.com.").common_ancestor(&name("example.org.")), name("."));
+		assert_eq!(n.common_ancestor(&n), n);
+	}
+
+	#[test]
+	fn test_canonical_cmp() {
+		// RFC 4034 section 6.1's example
+		let v: Vec<Name> = ["example.", "a.example.", "yljkjljk.a.example.", "Z.a.example.",
+			"zABC.a.EXAMPLE.", "z.example.", "\\001.z.example.", "*.z.example.", "\\200.z.example."]
+			.iter().map(|s| name(s)).collect();
+		let mut sorted = v.clone();
+		sorted.reverse();
+		sorted.sort_unstable_by(Name::canonical_cmp);
+		assert_eq!(sorted, v);
+		assert_eq!(name("example.").canonical_cmp(&name("EXAMPLE.")), Ordering::Equal);
+	}
 }
@@ -2,8 +2,12 @@
 
 use alloc::vec::Vec;
 use alloc::string::String;
+use alloc::borrow::ToOwned;
+
+use core::cmp;
 
 use crate::rr::*;
+use crate::message::{Edns, OPT_TYPE};
 use crate::query::QueryBuf;
 
 pub(crate) fn read_u8(inp: &mut &[u8]) -> Result<u8, ()> {
@@ -74,7 +78,7 @@ pub(crate) fn read_nsec_types_bitmap(inp: &mut &[u8]) -> Result<[u8; 8192], ()>
 	Ok(res)
 }
 
-fn do_read_wire_packet_labels(inp: &mut &[u8], wire_packet: &[u8], name: &mut String, recursion_limit: usize) -> Result<(), ()> {
+fn do_read_wire_packet_labels(inp: &mut &[u8], wire_packet: &[u8], name: &mut String, wire_len: &mut usize, recursion_limit: usize) -> Result<(), ()> {
 	loop {
 		let len = read_u8(inp)? as usize;
 		if len == 0 {
@@ -83,20 +87,21 @@ fn do_read_wire_packet_labels(inp: &mut &[u8], wire_packet: &[u8], name: &mut St
 		} else if len >= 0xc0 && recursion_limit > 0 {
 			let offs = ((len & !0xc0) << 8) | read_u8(inp)? as usize;
 			if offs >= wire_packet.len() { return Err(()); }
-			do_read_wire_packet_labels(&mut &wire_packet[offs..], wire_packet, name, recursion_limit - 1)?;
+			do_read_wire_packet_labels(&mut &wire_packet[offs..], wire_packet, name, wire_len, recursion_limit - 1)?;
 			break;
 		}
 		if inp.len() <= len { return Err(()); }
-		*name += core::str::from_utf8(&inp[..len]).map_err(|_| ())?;
+		escape_label(name, &inp[..len]);
 		*name += ".";
 		*inp = &inp[len..];
-		if name.len() > 255 { return Err(()); }
+		*wire_len += 1 + len;
+		if *wire_len > 255 { return Err(()); }
 	}
 	Ok(())
 }
 
 fn read_wire_packet_labels(inp: &mut &[u8], wire_packet: &[u8], name: &mut String) -> Result<(), ()> {
-	do_read_wire_packet_labels(inp, wire_packet, name, 255)
+	do_read_wire_packet_labels(inp, wire_packet, name, &mut 0, 255)
 }
 
 pub(crate) fn read_wire_packet_name(inp: &mut &[u8], wire_packet: &[u8]) -> Result<Name, ()> {
@@ -111,26 +116,20 @@ impl Writer for QueryBuf { fn write(&mut self, buf: &[u8]) { self.extend_from_sl
 #[cfg(feature = "validation")]
 impl Writer for crate::crypto::hash::Hasher { fn write(&mut self, buf: &[u8]) { self.update(buf); } }
 pub(crate) fn write_name<W: Writer>(out: &mut W, name: &str) {
-	let canonical_name = name.to_ascii_lowercase();
-	if canonical_name == "." {
-		out.write(&[0]);
-	} else {
-		for label in canonical_name.split('.') {
-			out.write(&(label.len() as u8).to_be_bytes());
-			out.write(label.as_bytes());
-		}
+	for label in split_labels(name) {
+		let mut label = unescape_label(label);
+		if label.iter().any(|c| c.is_ascii_uppercase()) { label.to_mut().make_ascii_lowercase(); }
+		out.write(&(label.len() as u8).to_be_bytes());
+		out.write(&label);
 	}
+	out.write(&[0]);
 }
 pub(crate) fn name_len(name: &Name) -> u16 {
-	if name.as_str() == "." {
-		1
-	} else {
-		let mut res = 0;
-		for label in name.split('.') {
-			res += 1 + label.len();
-		}
-		res as u16
+	let mut res = 1;
+	for label in split_labels(name) {
+		res += 1 + unescape_label(label).len();
 	}
+	res as u16
 }
 
 pub(crate) fn parse_wire_packet_rr(inp: &mut &[u8], wire_packet: &[u8]) -> Result<(RR, u32), ()> {
@@ -148,15 +147,19 @@ pub(crate) fn parse_wire_packet_rr(inp: &mut &[u8], wire_packet: &[u8]) -> Resul
 		A::TYPE => RR::A(A::read_from_data(name, data, wire_packet)?),
 		AAAA::TYPE => RR::AAAA(AAAA::read_from_data(name, data, wire_packet)?),
 		NS::TYPE => RR::NS(NS::read_from_data(name, data, wire_packet)?),
+		SOA::TYPE => RR::SOA(SOA::read_from_data(name, data, wire_packet)?),
 		Txt::TYPE => RR::Txt(Txt::read_from_data(name, data, wire_packet)?),
 		CName::TYPE => RR::CName(CName::read_from_data(name, data, wire_packet)?),
 		DName::TYPE => RR::DName(DName::read_from_data(name, data, wire_packet)?),
 		TLSA::TYPE => RR::TLSA(TLSA::read_from_data(name, data, wire_packet)?),
 		DnsKey::TYPE => RR::DnsKey(DnsKey::read_from_data(name, data, wire_packet)?),
 		DS::TYPE => RR::DS(DS::read_from_data(name, data, wire_packet)?),
+		CDS::TYPE => RR::CDS(CDS::read_from_data(name, data, wire_packet)?),
+		CDnsKey::TYPE => RR::CDnsKey(CDnsKey::read_from_data(name, data, wire_packet)?),
 		RRSig::TYPE => RR::RRSig(RRSig::read_from_data(name, data, wire_packet)?),
 		NSec::TYPE => RR::NSec(NSec::read_from_data(name, data, wire_packet)?),
 		NSec3::TYPE => RR::NSec3(NSec3::read_from_data(name, data, wire_packet)?),
+		ZoneMD::TYPE => RR::ZoneMD(ZoneMD::read_from_data(name, data, wire_packet)?),
 		_ => return Err(()),
 	};
 	Ok((rr, ttl))
@@ -169,7 +172,8 @@ pub(crate) fn parse_rr(inp: &mut &[u8]) -> Result<RR, ()> {
 /// Parse a stream of [`RR`]s from the format described in [RFC 9102](https://www.rfc-editor.org/rfc/rfc9102.html).
 ///
 /// Note that this is only the series of `AuthenticationChain` records, and does not read the
-/// `ExtSupportLifetime` field at the start of a `DnssecChainExtension`.
+/// `ExtSupportLifetime` field at the start of a `DnssecChainExtension`. See
+/// [`parse_dnssec_chain_extension`] to read a full `DnssecChainExtension`.
 pub fn parse_rr_stream(mut inp: &[u8]) -> Result<Vec<RR>, ()> {
 	let mut res = Vec::with_capacity(32);
 	while !inp.is_empty() {
@@ -178,6 +182,202 @@ pub fn parse_rr_stream(mut inp: &[u8]) -> Result<Vec<RR>, ()> {
 	Ok(res)
 }
 
+/// Parse a stream of [`RR`]s from the format described in [RFC 9102](https://www.rfc-editor.org/rfc/rfc9102.html),
+/// returning each record alongside its TTL.
+///
+/// This is identical to [`parse_rr_stream`] but retains the TTLs, which are generally meaningless
+/// in proofs but are required to, e.g., calculate a zone digest over a full zone's records.
+pub fn parse_rr_stream_with_ttls(mut inp: &[u8]) -> Result<Vec<(RR, u32)>, ()> {
+	let mut res = Vec::with_capacity(32);
+	while !inp.is_empty() {
+		res.push(parse_wire_packet_rr(&mut inp, &[])?);
+	}
+	Ok(res)
+}
+
+/// Checks that the variable-length fields of `rr` are the length required by its algorithm or
+/// digest type, where we know it.
+fn check_strict_rdata_len(rr: &RR) -> Result<(), ()> {
+	fn digest_len(digest_type: u8) -> Option<usize> {
+		match digest_type { 1 => Some(20), 2 => Some(32), 4 => Some(48), _ => None }
+	}
+	fn pubkey_len(alg: u8) -> Option<usize> {
+		match alg { 13 => Some(64), 14 => Some(96), 15 => Some(32), 16 => Some(57), _ => None }
+	}
+	fn signature_len(alg: u8) -> Option<usize> {
+		match alg { 13 => Some(64), 14 => Some(96), 15 => Some(64), 16 => Some(114), _ => None }
+	}
+	let (expected_len, len) = match rr {
+		RR::TLSA(tlsa) => match tlsa.data_ty {
+			1 => (Some(32), tlsa.data.len()),
+			2 => (Some(64), tlsa.data.len()),
+			_ => (None, 0),
+		},
+		RR::DS(ds) => (digest_len(ds.digest_type), ds.digest.len()),
+		RR::CDS(cds) => (digest_len(cds.digest_type), cds.digest.len()),
+		RR::DnsKey(dnskey) => (pubkey_len(dnskey.alg), dnskey.pubkey.len()),
+		RR::CDnsKey(cdnskey) => (pubkey_len(cdnskey.alg), cdnskey.pubkey.len()),
+		RR::RRSig(rrsig) => (signature_len(rrsig.alg), rrsig.signature.len()),
+		RR::ZoneMD(zonemd) => match zonemd.hash_alg {
+			1 => (Some(48), zonemd.digest.len()),
+			2 => (Some(64), zonemd.digest.len()),
+			_ => (None, 0),
+		},
+		_ => (None, 0),
+	};
+	match expected_len {
+		Some(expected_len) if expected_len != len => Err(()),
+		_ => Ok(()),
+	}
+}
+
+/// Parse a stream of [`RR`]s from the format described in [RFC 9102](https://www.rfc-editor.org/rfc/rfc9102.html),
+/// rejecting anything but the canonical encoding.
+///
+/// Unlike [`parse_rr_stream`], this fails if any name is compressed or contains uppercase
+/// letters, any RDATA has trailing bytes or is otherwise not encoded exactly as [`write_rr`]
+/// would encode it (e.g. TXT data split into chunks of less than 255 bytes or an NSEC type bitmap
+/// with empty windows), any key, signature or digest is the wrong length for its algorithm, or
+/// any record appears more than once (with any TTL).
+///
+/// Thus, any two parties which accept a proof with this method will agree on its exact bytes.
+pub fn parse_rr_stream_strict(mut inp: &[u8]) -> Result<Vec<RR>, ()> {
+	let mut res = Vec::with_capacity(32);
+	let mut reencoded = Vec::new();
+	while !inp.is_empty() {
+		let start = inp;
+		let (rr, ttl) = parse_wire_packet_rr(&mut inp, &[])?;
+		let rr_bytes = &start[..start.len() - inp.len()];
+		reencoded.clear();
+		write_rr(&rr, ttl, &mut reencoded);
+		if reencoded != rr_bytes { return Err(()); }
+		check_strict_rdata_len(&rr)?;
+		res.push(rr);
+	}
+	let mut sorted = res.iter().collect::<Vec<_>>();
+	sorted.sort_unstable();
+	if sorted.windows(2).any(|rrs| rrs[0] == rrs[1]) { return Err(()); }
+	Ok(res)
+}
+
+/// Gets the length of the record at the start of `inp`, or `None` if more bytes are needed to
+/// tell.
+fn rr_len(inp: &[u8]) -> Result<Option<usize>, ()> {
+	let mut pos = 0;
+	loop {
+		let label_len = match inp.get(pos) { Some(len) => *len as usize, None => return Ok(None) };
+		pos += 1 + label_len;
+		if label_len == 0 { break; }
+		if label_len > 63 || pos > 255 { return Err(()); }
+	}
+	// Skip the type, class and TTL to read the data length.
+	match inp.get(pos + 8..pos + 10) {
+		Some(len) => Ok(Some(pos + 10 + u16::from_be_bytes([len[0], len[1]]) as usize)),
+		None => Ok(None),
+	}
+}
+
+/// The most bytes we may need to buffer before we know the length of a record - a maximum-length
+/// name followed by the type, class, TTL and data length.
+const MAX_RR_HEADER_LEN: usize = 255 + 10;
+
+/// A push-based parser for a stream of [`RR`]s in the format described in
+/// [RFC 9102](https://www.rfc-editor.org/rfc/rfc9102.html), for when the stream arrives in chunks.
+///
+/// Only the bytes of a record which is split across chunks are buffered, the rest of each chunk
+/// is parsed in place.
+///
+/// Once a chunk fails to parse the stream is corrupt, and the parser should be discarded.
+pub struct RRStreamParser {
+	partial: Vec<u8>,
+}
+
+impl RRStreamParser {
+	/// Creates a new parser, expecting the first record at the start of the next chunk.
+	pub fn new() -> Self {
+		Self { partial: Vec::new() }
+	}
+
+	/// Provides the next chunk of the stream, returning an iterator over the records which can be
+	/// parsed so far.
+	///
+	/// If the iterator is dropped before it is exhausted, any remaining bytes are buffered and
+	/// their records will be returned after the next call to `push`.
+	pub fn push<'p, 'c>(&'p mut self, chunk: &'c [u8]) -> PushedRRs<'p, 'c> {
+		PushedRRs { parser: self, chunk }
+	}
+
+	/// Completes parsing, failing if the stream ended part-way through a record.
+	pub fn finish(self) -> Result<(), ()> {
+		if self.partial.is_empty() { Ok(()) } else { Err(()) }
+	}
+}
+
+impl Default for RRStreamParser {
+	fn default() -> Self { Self::new() }
+}
+
+/// An iterator over the [`RR`]s which could be parsed after a call to [`RRStreamParser::push`].
+///
+/// Yields `Err(())` if the stream is invalid.
+pub struct PushedRRs<'p, 'c> {
+	parser: &'p mut RRStreamParser,
+	chunk: &'c [u8],
+}
+
+impl<'p, 'c> PushedRRs<'p, 'c> {
+	fn fail(&mut self) -> Option<Result<RR, ()>> {
+		self.chunk = &[];
+		self.parser.partial.clear();
+		Some(Err(()))
+	}
+}
+
+impl<'p, 'c> Iterator for PushedRRs<'p, 'c> {
+	type Item = Result<RR, ()>;
+	fn next(&mut self) -> Option<Result<RR, ()>> {
+		if self.parser.partial.is_empty() {
+			let len = match rr_len(self.chunk) {
+				Ok(Some(len)) if len <= self.chunk.len() => len,
+				Ok(_) => {
+					self.parser.partial.extend_from_slice(self.chunk);
+					self.chunk = &[];
+					return None;
+				},
+				Err(()) => return self.fail(),
+			};
+			let res = parse_rr(&mut &self.chunk[..len]);
+			self.chunk = &self.chunk[len..];
+			if res.is_err() { return self.fail(); }
+			return Some(res);
+		}
+		loop {
+			let partial = &mut self.parser.partial;
+			let take = match rr_len(partial) {
+				Ok(Some(len)) if len <= partial.len() => {
+					let res = parse_rr(&mut &partial[..len]);
+					partial.drain(..len);
+					if res.is_err() { return self.fail(); }
+					return Some(res);
+				},
+				Ok(Some(len)) => len - partial.len(),
+				Ok(None) => MAX_RR_HEADER_LEN.saturating_sub(partial.len()).max(1),
+				Err(()) => return self.fail(),
+			};
+			if self.chunk.is_empty() { return None; }
+			let take = cmp::min(take, self.chunk.len());
+			partial.extend_from_slice(&self.chunk[..take]);
+			self.chunk = &self.chunk[take..];
+		}
+	}
+}
+
+impl<'p, 'c> Drop for PushedRRs<'p, 'c> {
+	fn drop(&mut self) {
+		self.parser.partial.extend_from_slice(self.chunk);
+	}
+}
+
 /// Writes the given resource record in its wire encoding to the given `Vec`.
 ///
 /// An [RFC 9102](https://www.rfc-editor.org/rfc/rfc9102.html) `AuthenticationChain` is simply a
@@ -189,3 +389,511 @@ pub fn write_rr<RR: Record>(rr: &RR, ttl: u32, out: &mut Vec<u8>) {
 	out.extend_from_slice(&ttl.to_be_bytes());
 	rr.write_u16_len_prefixed_data(out);
 }
+
+/// Writes the data which `sig` signs over the given records, as described in RFC 4034 section
+/// 3.1.8.1, sorting `records` into canonical order.
+pub(crate) fn write_rrsig_signing_data<R: WriteableRecord, W: Writer>(sig: &RRSig, records: &mut [&R], out: &mut W)
+-> Result<(), ()> {
+	for record in records.iter() {
+		if sig.ty != record.ty() { return Err(()); }
+	}
+	out.write(&sig.ty.to_be_bytes());
+	out.write(&sig.alg.to_be_bytes());
+	out.write(&sig.labels.to_be_bytes());
+	out.write(&sig.orig_ttl.to_be_bytes());
+	out.write(&sig.expiration.to_be_bytes());
+	out.write(&sig.inception.to_be_bytes());
+	out.write(&sig.key_tag.to_be_bytes());
+	write_name(out, &sig.key_name);
+
+	records.sort_unstable();
+
+	for record in records.iter() {
+		let record_labels = record.name().labels() as usize;
+		let labels = sig.labels.into();
+		// For NSec types, the name should already match the wildcard, so we don't do any
+		// filtering here. This is relied upon in `verify_rr_stream` to check whether an
+		// NSec record is matching via wildcard (as otherwise we'd allow a resolver to
+		// change the name out from under us and change the wildcard to something else).
+		if record.ty() != NSec::TYPE && record_labels != labels {
+			if record_labels < labels { return Err(()); }
+			let signed_name = record.name().trailing_n_labels(sig.labels);
+			debug_assert!(signed_name.is_some());
+			if let Some(name) = signed_name {
+				out.write(b"\x01*");
+				write_name(out, name);
+			} else { return Err(()); }
+		} else {
+			write_name(out, record.name());
+		}
+		out.write(&record.ty().to_be_bytes());
+		out.write(&1u16.to_be_bytes()); // The INternet class
+		out.write(&sig.orig_ttl.to_be_bytes());
+		record.serialize_u16_len_prefixed(out);
+	}
+	Ok(())
+}
+
+struct FnWriter<F: FnMut(&[u8])>(F);
+impl<F: FnMut(&[u8])> Writer for FnWriter<F> { fn write(&mut self, buf: &[u8]) { (self.0)(buf) } }
+
+/// Gets the exact data which the given [`RRSig`] signs over the given set of records, as
+/// described in RFC 4034 section 3.1.8.1.
+///
+/// The records are sorted into canonical order, owner names are replaced with the wildcard they
+/// were synthesized from (based on [`RRSig::labels`]) and TTLs are replaced with
+/// [`RRSig::orig_ttl`], exactly as done when validating signatures in the `validation` module.
+///
+/// Fails if `records` is empty, the records are not all of the type [`RRSig::ty`] with the same
+/// name, or a record has fewer labels than [`RRSig::labels`].
+pub fn rrsig_signing_data(sig: &RRSig, records: &[RR]) -> Result<Vec<u8>, ()> {
+	let mut res = Vec::new();
+	stream_rrsig_signing_data(sig, records, |data| res.extend_from_slice(data))?;
+	Ok(res)
+}
+
+/// Streams the exact data which the given [`RRSig`] signs over the given set of records to
+/// `out`, e.g. to feed it directly into a hash function.
+///
+/// See [`rrsig_signing_data`] for details. Note that if this fails some data may have been
+/// written to `out` already.
+pub fn stream_rrsig_signing_data<F: FnMut(&[u8])>(sig: &RRSig, records: &[RR], out: F) -> Result<(), ()> {
+	let first_name = records.first().ok_or(())?.name();
+	if records.iter().any(|rr| rr.name() != first_name) { return Err(()); }
+	let mut records = records.iter().collect::<Vec<_>>();
+	write_rrsig_signing_data(sig, &mut records, &mut FnWriter(out))
+}
+
+/// The TLS extension type of the `dnssec_chain` extension, as assigned by IANA per RFC 9102.
+pub const DNSSEC_CHAIN_TLS_EXTENSION_TYPE: u16 = 59;
+
+/// Parse a full [RFC 9102](https://www.rfc-editor.org/rfc/rfc9102.html) `DnssecChainExtension`,
+/// i.e. the `extension_data` of a TLS `dnssec_chain` extension.
+///
+/// Returns the `ExtSupportLifetime` (in hours) and the records in the `AuthenticationChain`.
+///
+/// Fails if the `AuthenticationChain` is empty, as [`write_dnssec_chain_tls_extension`] does.
+pub fn parse_dnssec_chain_extension(mut inp: &[u8]) -> Result<(u16, Vec<RR>), ()> {
+	let ext_support_lifetime = read_u16(&mut inp)?;
+	if inp.is_empty() { return Err(()); }
+	Ok((ext_support_lifetime, parse_rr_stream(inp)?))
+}
+
+/// Writes a full [RFC 9102](https://www.rfc-editor.org/rfc/rfc9102.html) `DnssecChainExtension`
+/// to the given `Vec`.
+///
+/// `authentication_chain` is a series of records in wire encoding, as built by the methods in
+/// [`crate::query`], and `ext_support_lifetime` is the number of hours for which the server
+/// commits to continue serving the extension.
+pub fn write_dnssec_chain_extension(ext_support_lifetime: u16, authentication_chain: &[u8], out: &mut Vec<u8>) {
+	out.extend_from_slice(&ext_support_lifetime.to_be_bytes());
+	out.extend_from_slice(authentication_chain);
+}
+
+/// Writes a full TLS `dnssec_chain` extension, including the extension type and length, to the
+/// given `Vec`, allowing a TLS server to staple a DANE proof for its TLSA records.
+///
+/// Fails if the extension would be too long to fit in a TLS extension or if the
+/// `authentication_chain` is empty.
+pub fn write_dnssec_chain_tls_extension(ext_support_lifetime: u16, authentication_chain: &[u8], out: &mut Vec<u8>) -> Result<(), ()> {
+	if authentication_chain.is_empty() { return Err(()); }
+	let len: u16 = (authentication_chain.len() + 2).try_into().map_err(|_| ())?;
+	out.extend_from_slice(&DNSSEC_CHAIN_TLS_EXTENSION_TYPE.to_be_bytes());
+	out.extend_from_slice(&len.to_be_bytes());
+	write_dnssec_chain_extension(ext_support_lifetime, authentication_chain, out);
+	Ok(())
+}
+
+/// Writes a full TLS `dnssec_chain` extension for a server which has no TLSA records, in which
+/// case the `authentication_chain` must instead prove their non-existence using [`NSec`] or
+/// [`NSec3`] records.
+///
+/// Fails if the `authentication_chain` contains any [`TLSA`] records or no [`NSec`] or [`NSec3`]
+/// records, or if [`write_dnssec_chain_tls_extension`] would fail.
+pub fn write_dnssec_chain_denial_tls_extension(ext_support_lifetime: u16, authentication_chain: &[u8], out: &mut Vec<u8>) -> Result<(), ()> {
+	let rrs = parse_rr_stream(authentication_chain)?;
+	if rrs.iter().any(|rr| matches!(rr, RR::TLSA(_))) { return Err(()); }
+	if !rrs.iter().any(|rr| matches!(rr, RR::NSec(_) | RR::NSec3(_))) { return Err(()); }
+	write_dnssec_chain_tls_extension(ext_support_lifetime, authentication_chain, out)
+}
+
+/// The first byte of an encoded [`ProofEnvelope`].
+///
+/// This can never be the first byte of a raw `AuthenticationChain`, as labels are at most 63 bytes
+/// and compression is not permitted, allowing the two to be told apart.
+const PROOF_ENVELOPE_MARKER: u8 = 0xff;
+
+/// The version of the [`ProofEnvelope`] encoding written by [`ProofEnvelope::encode`].
+pub const PROOF_ENVELOPE_VERSION: u8 = 1;
+
+/// A self-describing wrapper around an [RFC 9102](https://www.rfc-editor.org/rfc/rfc9102.html)
+/// `AuthenticationChain`, describing what the proof is for and when it was built.
+///
+/// Version 1 is encoded as a `0xff` marker byte, the version byte, the query name (in
+/// uncompressed wire form), the query type (u16), the creation time (u64), the TTL (u32) and
+/// finally the `AuthenticationChain` itself, with all integers in big-endian.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProofEnvelope {
+	/// The name which the proof was built for.
+	pub query_name: Name,
+	/// The type of the records which the proof was built for.
+	pub query_type: u16,
+	/// The time at which the proof was built, in seconds since the UNIX epoch.
+	pub created_at: u64,
+	/// The TTL for which the proof may be cached, as returned by
+	/// [`crate::query::ProofBuilder::finish_proof`].
+	pub ttl: u32,
+	/// The series of records in the proof.
+	pub authentication_chain: Vec<u8>,
+}
+
+impl ProofEnvelope {
+	/// Encodes this envelope.
+	pub fn encode(&self) -> Vec<u8> {
+		let mut res = Vec::with_capacity(2 + 255 + 2 + 8 + 4 + self.authentication_chain.len());
+		res.push(PROOF_ENVELOPE_MARKER);
+		res.push(PROOF_ENVELOPE_VERSION);
+		write_name(&mut res, &self.query_name);
+		res.extend_from_slice(&self.query_type.to_be_bytes());
+		res.extend_from_slice(&self.created_at.to_be_bytes());
+		res.extend_from_slice(&self.ttl.to_be_bytes());
+		res.extend_from_slice(&self.authentication_chain);
+		res
+	}
+
+	/// Decodes an envelope, failing if it is malformed or of an unknown version.
+	///
+	/// Note that the `authentication_chain` is not parsed, see [`ProofEnvelope::rrs`].
+	pub fn decode(mut inp: &[u8]) -> Result<Self, ()> {
+		if read_u8(&mut inp)? != PROOF_ENVELOPE_MARKER { return Err(()); }
+		if read_u8(&mut inp)? != PROOF_ENVELOPE_VERSION { return Err(()); }
+		let query_name = read_wire_packet_name(&mut inp, &[])?;
+		let query_type = read_u16(&mut inp)?;
+		let created_at = (read_u32(&mut inp)? as u64) << 32 | read_u32(&mut inp)? as u64;
+		let ttl = read_u32(&mut inp)?;
+		Ok(ProofEnvelope { query_name, query_type, created_at, ttl, authentication_chain: inp.to_vec() })
+	}
+
+	/// Parses the records in the `authentication_chain`.
+	pub fn rrs(&self) -> Result<Vec<RR>, ()> {
+		parse_rr_stream(&self.authentication_chain)
+	}
+}
+
+/// Writes a full DNS message, compressing names as described in RFC 1035 section 4.1.4.
+///
+/// This is useful for building DNS responses, but must not be used for proofs or for data which
+/// is to be signed or hashed, which always use the uncompressed encoding from [`write_rr`].
+///
+/// The question, answer, authority and additional sections must be written in that order, with
+/// the counts in the header updated as records are added.
+pub struct MessageWriter {
+	buf: Vec<u8>,
+	/// The offsets at which each name suffix we've written starts, for use in compression.
+	name_offsets: Vec<(String, u16)>,
+	/// The index of the section we're currently writing, with 0 being the question section.
+	section: usize,
+}
+
+impl MessageWriter {
+	/// Starts a new message with the given transaction ID and flags.
+	pub fn new(id: u16, flags: u16) -> Self {
+		let mut buf = Vec::with_capacity(512);
+		buf.extend_from_slice(&id.to_be_bytes());
+		buf.extend_from_slice(&flags.to_be_bytes());
+		buf.extend_from_slice(&[0; 8]);
+		Self { buf, name_offsets: Vec::new(), section: 0 }
+	}
+
+	fn start_record(&mut self, section: usize) -> Result<(), ()> {
+		if section < self.section { return Err(()); }
+		self.section = section;
+		let count_pos = 4 + section * 2;
+		let count = u16::from_be_bytes([self.buf[count_pos], self.buf[count_pos + 1]]);
+		let count = count.checked_add(1).ok_or(())?;
+		self.buf[count_pos..count_pos + 2].copy_from_slice(&count.to_be_bytes());
+		Ok(())
+	}
+
+	fn write_name(&mut self, name: &Name) {
+		let labels = split_labels(name);
+		for (idx, label) in labels.iter().enumerate() {
+			let suffix = name.trailing_n_labels((labels.len() - idx) as u8)
+				.expect("We can't ask for more labels than the name has");
+			if let Some((_, offset)) = self.name_offsets.iter().find(|(n, _)| n == suffix) {
+				self.buf.extend_from_slice(&(0xc000 | *offset).to_be_bytes());
+				return;
+			}
+			// Pointers only have 14 bits of offset, so we can't point to anything later.
+			if self.buf.len() < 0x4000 {
+				self.name_offsets.push((suffix.to_owned(), self.buf.len() as u16));
+			}
+			let label = unescape_label(label);
+			self.buf.push(label.len() as u8);
+			self.buf.extend_from_slice(&label);
+		}
+		self.buf.push(0);
+	}
+
+	fn write_record(&mut self, section: usize, rr: &RR, ttl: u32) -> Result<(), ()> {
+		self.start_record(section)?;
+		self.write_name(rr.name());
+		self.buf.extend_from_slice(&rr.ty().to_be_bytes());
+		self.buf.extend_from_slice(&1u16.to_be_bytes()); // The INternet class
+		self.buf.extend_from_slice(&ttl.to_be_bytes());
+		// Per RFC 3597 section 4, only RDATA of the types defined in RFC 1035 may be compressed.
+		let len_pos = self.buf.len();
+		match rr {
+			RR::NS(ns) => {
+				self.buf.extend_from_slice(&[0; 2]);
+				self.write_name(&ns.name_server);
+			},
+			RR::CName(cname) => {
+				self.buf.extend_from_slice(&[0; 2]);
+				self.write_name(&cname.canonical_name);
+			},
+			RR::SOA(soa) => {
+				self.buf.extend_from_slice(&[0; 2]);
+				self.write_name(&soa.mname);
+				self.write_name(&soa.rname);
+				for field in [soa.serial, soa.refresh, soa.retry, soa.expire, soa.minimum] {
+					self.buf.extend_from_slice(&field.to_be_bytes());
+				}
+			},
+			_ => {
+				Record::write_u16_len_prefixed_data(rr, &mut self.buf);
+				return Ok(());
+			},
+		}
+		let len = (self.buf.len() - len_pos - 2) as u16;
+		self.buf[len_pos..len_pos + 2].copy_from_slice(&len.to_be_bytes());
+		Ok(())
+	}
+
+	/// Writes a question for the given name and type (in the INternet class).
+	///
+	/// Fails if a record has already been written to a later section.
+	pub fn write_question(&mut self, name: &Name, ty: u16) -> Result<(), ()> {
+		self.start_record(0)?;
+		self.write_name(name);
+		self.buf.extend_from_slice(&ty.to_be_bytes());
+		self.buf.extend_from_slice(&1u16.to_be_bytes()); // The INternet class
+		Ok(())
+	}
+
+	/// Writes a record to the answer section.
+	///
+	/// Fails if a record has already been written to a later section.
+	pub fn write_answer(&mut self, rr: &RR, ttl: u32) -> Result<(), ()> {
+		self.write_record(1, rr, ttl)
+	}
+
+	/// Writes a record to the authority section.
+	///
+	/// Fails if a record has already been written to a later section.
+	pub fn write_authority(&mut self, rr: &RR, ttl: u32) -> Result<(), ()> {
+		self.write_record(2, rr, ttl)
+	}
+
+	/// Writes a record to the additional section.
+	pub fn write_additional(&mut self, rr: &RR, ttl: u32) -> Result<(), ()> {
+		self.write_record(3, rr, ttl)
+	}
+
+	/// Writes an EDNS OPT pseudo-record to the additional section.
+	///
+	/// `extended_rcode` is the upper 8 bits of the 12-bit response code, the lower 4 bits being
+	/// part of the flags passed to [`MessageWriter::new`].
+	pub fn write_edns(&mut self, edns: &Edns, extended_rcode: u8) -> Result<(), ()> {
+		self.start_record(3)?;
+		self.buf.push(0); // The root name
+		self.buf.extend_from_slice(&OPT_TYPE.to_be_bytes());
+		self.buf.extend_from_slice(&edns.udp_payload_size.to_be_bytes());
+		self.buf.extend_from_slice(&[extended_rcode, edns.version]);
+		let edns_flags: u16 = if edns.dnssec_ok { 0x8000 } else { 0 };
+		self.buf.extend_from_slice(&edns_flags.to_be_bytes());
+		let len = edns.options.iter().map(|opt| 4 + opt.data.len()).sum::<usize>();
+		self.buf.extend_from_slice(&u16::try_from(len).map_err(|_| ())?.to_be_bytes());
+		for option in edns.options.iter() {
+			self.buf.extend_from_slice(&option.code.to_be_bytes());
+			self.buf.extend_from_slice(&(option.data.len() as u16).to_be_bytes());
+			self.buf.extend_from_slice(&option.data);
+		}
+		Ok(())
+	}
+
+	/// Gets the message written so far.
+	pub fn as_bytes(&self) -> &[u8] { &self.buf }
+
+	/// Gets the full message.
+	pub fn into_vec(self) -> Vec<u8> { self.buf }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use alloc::vec;
+
+	#[test]
+	fn test_rrsig_signing_data() {
+		let rrsig = match "a.example.com. IN RRSIG A 13 2 3600 20240101000000 20231201000000 1234 example.com. AAAA".parse().unwrap() {
+			RR::RRSig(rrsig) => rrsig,
+			_ => panic!(),
+		};
+		let records: Vec<RR> = ["a.example.com. IN A 192.0.2.2", "a.example.com. IN A 192.0.2.1"]
+			.iter().map(|s| s.parse().unwrap()).collect();
+		let data = rrsig_signing_data(&rrsig, &records).unwrap();
+
+		let mut expected = Vec::new();
+		expected.extend_from_slice(&[0, 1, 13, 2, 0, 0, 0x0e, 0x10]);
+		expected.extend_from_slice(&rrsig.expiration.to_be_bytes());
+		expected.extend_from_slice(&rrsig.inception.to_be_bytes());
+		expected.extend_from_slice(&1234u16.to_be_bytes());
+		expected.extend_from_slice(b"\x07example\x03com\x00");
+		// The records are sorted, with the name replaced with the wildcard the signature covers.
+		for last_octet in [1, 2] {
+			expected.extend_from_slice(b"\x01*\x07example\x03com\x00\x00\x01\x00\x01\x00\x00\x0e\x10\x00\x04");
+			expected.extend_from_slice(&[192, 0, 2, last_octet]);
+		}
+		assert_eq!(data, expected);
+
+		let mut streamed = Vec::new();
+		stream_rrsig_signing_data(&rrsig, &records, |d| streamed.extend_from_slice(d)).unwrap();
+		assert_eq!(streamed, expected);
+
+		let mut non_wildcard = rrsig.clone();
+		non_wildcard.labels = 3;
+		let data = rrsig_signing_data(&non_wildcard, &records).unwrap();
+		assert_eq!(&data[31..48], b"\x01a\x07example\x03com\x00\x00\x01");
+
+		assert!(rrsig_signing_data(&rrsig, &[]).is_err());
+		let mut mixed_names = records.clone();
+		mixed_names.push("b.example.com. IN A 192.0.2.3".parse().unwrap());
+		assert!(rrsig_signing_data(&rrsig, &mixed_names).is_err());
+		let mut mixed_types = records.clone();
+		mixed_types.push("a.example.com. IN AAAA ::1".parse().unwrap());
+		assert!(rrsig_signing_data(&rrsig, &mixed_types).is_err());
+		let mut too_many_labels = rrsig.clone();
+		too_many_labels.labels = 4;
+		assert!(rrsig_signing_data(&too_many_labels, &records).is_err());
+	}
+
+	#[test]
+	fn test_proof_envelope() {
+		let rr: RR = "example.com. IN A 192.0.2.1".parse().unwrap();
+		let mut chain = Vec::new();
+		write_rr(&rr, 3600, &mut chain);
+		let envelope = ProofEnvelope {
+			query_name: "example.com.".try_into().unwrap(), query_type: A::TYPE,
+			created_at: 0x1_0000_0001, ttl: 3600, authentication_chain: chain.clone(),
+		};
+		let encoded = envelope.encode();
+		assert_eq!(&encoded[..17], b"\xff\x01\x07example\x03com\x00\x00\x01");
+		assert_eq!(&encoded[17..29], &[0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0x0e, 0x10]);
+		assert_eq!(&encoded[29..], &chain[..]);
+		assert_eq!(ProofEnvelope::decode(&encoded).unwrap(), envelope);
+		assert_eq!(envelope.rrs().unwrap(), vec![rr]);
+
+		// A raw AuthenticationChain, an unknown version or a truncated envelope are rejected.
+		assert!(ProofEnvelope::decode(&chain).is_err());
+		let mut future_version = encoded.clone();
+		future_version[1] = 2;
+		assert!(ProofEnvelope::decode(&future_version).is_err());
+		assert!(ProofEnvelope::decode(&encoded[..28]).is_err());
+	}
+
+	#[test]
+	fn test_streaming_parser() {
+		let records: Vec<RR> = [
+			"example.com. IN A 192.0.2.1",
+			"example.com. IN TXT \"hello world\"",
+			"a.very.long.name.with.lots.of.labels.in.it.example.com. IN NS ns1.example.com.",
+			"com. IN DNSKEY 257 3 13 tx8EZRAd2+K/DJRV0S+hbBzaRPS/G6JVNBitHzqpsGlz8huE61Ms9ANe6NSDLKJtiTBqfTJWDAywEp1FCsEINQ==",
+			". IN DS 19718 13 2 8ACBB0CD28F41250A80A491389424D341522D946B0DA0C0291F2D3D771D7805A",
+		].iter().map(|s| s.parse().unwrap()).collect();
+		let mut stream = Vec::new();
+		for rr in records.iter() { write_rr(rr, 0, &mut stream); }
+
+		for chunk_len in [1, 2, 7, 64, 1000] {
+			let mut parser = RRStreamParser::new();
+			let mut parsed = Vec::new();
+			for chunk in stream.chunks(chunk_len) {
+				for rr in parser.push(chunk) { parsed.push(rr.unwrap()); }
+			}
+			parser.finish().unwrap();
+			assert_eq!(parsed, records);
+		}
+
+		// Dropping the iterator early leaves the remaining records for the next push.
+		let mut parser = RRStreamParser::new();
+		assert_eq!(parser.push(&stream).next().unwrap().unwrap(), records[0]);
+		let parsed: Vec<RR> = parser.push(&[]).map(|rr| rr.unwrap()).collect();
+		assert_eq!(parsed, records[1..]);
+		parser.finish().unwrap();
+
+		// A truncated stream fails to finish
+		let mut parser = RRStreamParser::new();
+		assert_eq!(parser.push(&stream[..stream.len() - 1]).count(), records.len() - 1);
+		assert!(parser.finish().is_err());
+
+		// Invalid records are reported
+		let mut parser = RRStreamParser::new();
+		assert!(parser.push(&[64]).next().unwrap().is_err());
+		let mut bad_stream = stream.clone();
+		bad_stream[13] = 0xff; // Unsupported record type for the first record
+		let mut parser = RRStreamParser::new();
+		for chunk in bad_stream.chunks(3) {
+			if let Some(res) = parser.push(chunk).next() {
+				assert!(res.is_err());
+				break;
+			}
+		}
+	}
+
+	#[test]
+	fn test_compressed_message() {
+		let name = |s: &str| -> Name { s.try_into().unwrap() };
+		let records: Vec<RR> = [
+			"www.example.com. IN CNAME example.com.",
+			"example.com. IN A 192.0.2.1",
+			"example.com. IN TXT \"hello\"",
+			"example.com. IN NS ns1.example.com.",
+			"example.com. IN SOA ns1.example.com. admin.example.com. 1 2 3 4 5",
+			"example.com. IN DNAME example.net.",
+		].iter().map(|s| s.parse().unwrap()).collect();
+
+		let mut writer = MessageWriter::new(0x1234, 0x8180);
+		writer.write_question(&name("www.example.com."), CName::TYPE).unwrap();
+		writer.write_answer(&records[0], 300).unwrap();
+		writer.write_answer(&records[1], 300).unwrap();
+		writer.write_answer(&records[2], 300).unwrap();
+		writer.write_authority(&records[3], 300).unwrap();
+		writer.write_authority(&records[4], 300).unwrap();
+		writer.write_additional(&records[5], 300).unwrap();
+		assert!(writer.write_answer(&records[1], 300).is_err());
+		assert!(writer.write_question(&name("example.com."), A::TYPE).is_err());
+		let msg = writer.into_vec();
+
+		let mut uncompressed_len = 12 + name_len(&name("www.example.com.")) as usize + 4;
+		let mut uncompressed = Vec::new();
+		for rr in records.iter() { write_rr(rr, 300, &mut uncompressed); }
+		uncompressed_len += uncompressed.len();
+		assert!(msg.len() < uncompressed_len);
+
+		assert_eq!(&msg[..12], &[0x12, 0x34, 0x81, 0x80, 0, 1, 0, 3, 0, 2, 0, 1]);
+		// The question name is written in full, and is then pointed to by the first answer.
+		assert_eq!(&msg[12..29], b"\x03www\x07example\x03com\x00");
+		assert_eq!(&msg[33..35], &[0xc0, 12]);
+		// The canonical name is a pointer to "example.com." in the question.
+		assert_eq!(&msg[43..47], &[0, 2, 0xc0, 16]);
+
+		let mut read = &msg[33..];
+		for rr in records.iter() {
+			let (parsed, ttl) = parse_wire_packet_rr(&mut read, &msg).unwrap();
+			assert_eq!(&parsed, rr);
+			assert_eq!(ttl, 300);
+		}
+		assert!(read.is_empty());
+	}
+}
@@ -0,0 +1,194 @@
+//! Helpers for the `serde` feature, keeping the serialized form of records identical to the
+//! hand-written JSON in [`crate::rr::RR::json`].
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use core::fmt;
+
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::ser::{SerializeSeq, Serializer};
+use serde::{Deserialize, Serialize};
+
+use crate::rr::{Name, NSecTypeMask, RR};
+use crate::zonefile;
+
+impl Serialize for Name {
+	fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+		ser.serialize_str(self.as_str())
+	}
+}
+impl<'de> Deserialize<'de> for Name {
+	fn deserialize<D: Deserializer<'de>>(de: D) -> Result<Name, D::Error> {
+		let s = String::deserialize(de)?;
+		Name::try_from(s).map_err(|()| de::Error::custom("invalid domain name"))
+	}
+}
+
+impl Serialize for NSecTypeMask {
+	fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+		let types = self.types();
+		let mut seq = ser.serialize_seq(Some(types.len()))?;
+		for ty in types {
+			match RR::ty_to_rr_name(ty) {
+				Some(name) => seq.serialize_element(name)?,
+				None => seq.serialize_element(&ty)?,
+			}
+		}
+		seq.end()
+	}
+}
+impl<'de> Deserialize<'de> for NSecTypeMask {
+	fn deserialize<D: Deserializer<'de>>(de: D) -> Result<NSecTypeMask, D::Error> {
+		/// A single type in the mask, written either by name or as a number.
+		struct Ty(u16);
+		impl<'de> Deserialize<'de> for Ty {
+			fn deserialize<D: Deserializer<'de>>(de: D) -> Result<Ty, D::Error> {
+				struct TyVisitor;
+				impl<'de> Visitor<'de> for TyVisitor {
+					type Value = Ty;
+					fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+						f.write_str("a record type name or number")
+					}
+					fn visit_u64<E: de::Error>(self, v: u64) -> Result<Ty, E> {
+						v.try_into().map(Ty).map_err(|_| E::custom("record type out of range"))
+					}
+					fn visit_i64<E: de::Error>(self, v: i64) -> Result<Ty, E> {
+						v.try_into().map(Ty).map_err(|_| E::custom("record type out of range"))
+					}
+					fn visit_str<E: de::Error>(self, v: &str) -> Result<Ty, E> {
+						RR::rr_name_to_ty(v).map(Ty).ok_or_else(|| E::custom("unknown record type"))
+					}
+				}
+				de.deserialize_any(TyVisitor)
+			}
+		}
+		let types = Vec::<Ty>::deserialize(de)?;
+		Ok(NSecTypeMask::from_types(&types.iter().map(|ty| ty.0).collect::<Vec<_>>()))
+	}
+}
+
+/// (De)serializes a byte array as an uppercase hex string.
+pub(crate) mod hex {
+	use super::*;
+
+	pub(crate) fn serialize<S: Serializer>(data: &[u8], ser: S) -> Result<S::Ok, S::Error> {
+		let mut s = String::with_capacity(data.len() * 2);
+		zonefile::write_hex(&mut s, data).expect("Write to a String shouldn't fail");
+		ser.serialize_str(&s)
+	}
+	pub(crate) fn deserialize<'de, D: Deserializer<'de>>(de: D) -> Result<Vec<u8>, D::Error> {
+		let s = String::deserialize(de)?;
+		crate::hex::decode(&s).map_err(|()| de::Error::custom("invalid hex string"))
+	}
+}
+
+/// (De)serializes [`crate::rr::Txt`] data as a string if it is valid UTF-8, otherwise as an
+/// array of bytes.
+pub(crate) mod txt {
+	use super::*;
+
+	pub(crate) fn serialize<S: Serializer>(data: &[u8], ser: S) -> Result<S::Ok, S::Error> {
+		match core::str::from_utf8(data) {
+			Ok(s) => ser.serialize_str(s),
+			Err(_) => data.serialize(ser),
+		}
+	}
+	pub(crate) fn deserialize<'de, D: Deserializer<'de>>(de: D) -> Result<Vec<u8>, D::Error> {
+		struct TxtVisitor;
+		impl<'de> Visitor<'de> for TxtVisitor {
+			type Value = Vec<u8>;
+			fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+				f.write_str("a string or an array of bytes")
+			}
+			fn visit_str<E: de::Error>(self, v: &str) -> Result<Vec<u8>, E> {
+				Ok(v.as_bytes().to_vec())
+			}
+			fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Vec<u8>, E> {
+				Ok(v.to_vec())
+			}
+			fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+				let mut res = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+				while let Some(b) = seq.next_element()? { res.push(b); }
+				Ok(res)
+			}
+		}
+		de.deserialize_any(TxtVisitor)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use alloc::string::ToString;
+
+	#[test]
+	fn test_matches_json() {
+		let records = [
+			"example.com. IN A 192.0.2.1",
+			"example.com. IN AAAA 2001:db8::1",
+			"example.com. IN NS ns1.example.com.",
+			"example.com. IN SOA ns1.example.com. admin.example.com. 2018031900 1800 900 604800 86400",
+			"example.com. IN TXT \"hello world\"",
+			"_443._tcp.example.com. IN TLSA 3 1 1 8BD1DA95272F7FA4FFB24137FC0ED03AAE67E5C4D8B3C50734E1050A7920B922",
+			"a.example.com. IN CNAME b.example.com.",
+			"a.example.com. IN DNAME b.example.com.",
+			"com. IN DNSKEY 257 3 13 tx8EZRAd2+K/DJRV0S+hbBzaRPS/G6JVNBitHzqpsGlz8huE61Ms9ANe6NSDLKJtiTBqfTJWDAywEp1FCsEINQ==",
+			"com. IN DS 19718 13 2 8ACBB0CD28F41250A80A491389424D341522D946B0DA0C0291F2D3D771D7805A",
+			"com. IN CDS 0 0 0 00",
+			"com. IN CDNSKEY 0 3 0 AA==",
+			"com. IN RRSIG DNSKEY 13 1 86400 20240313150235 20240227145735 19718 com. lF2B9nXZn0CgytrHH6xB0NTva4G/aWvg/ypnSxJ8+ZXlvR0C4974yB+nd2ZWzWMICs/oPYMKoQHqxVjnGyu8nA==",
+			"*.example.com. IN RRSIG TXT 13 2 30 21060207062815 19700101000000 1 example.com. AA==",
+			"a.example. IN NSEC d.example. A NS SOA RRSIG NSEC DNSKEY TYPE1234",
+			"2vptu5timamqttgl4luu9kg21e0aor3s.example. IN NSEC3 1 1 12 AABBCCDD 2T7B4G4VSA5SMI47K61MV5BV1A22BOJR A RRSIG",
+			"example. IN ZONEMD 2018031900 1 1 C68090D90A7AED716BC459F9340E3D7C1370D4D24B7E2FC3A1DDC0B9A87153B9A9713B3C9AE5CC27777F98B8E730044C",
+		];
+		for record in records {
+			let rr: RR = record.parse().unwrap();
+			let value = serde_json::to_value(&rr).unwrap();
+			let json: serde_json::Value = serde_json::from_str(&rr.json()).unwrap();
+			if let RR::NSec(_) | RR::NSec3(_) = rr {
+				// The hand-written JSON lists types within each byte of the mask in descending
+				// order, so compare types as sets.
+				let mut value_types = value["types"].as_array().unwrap().clone();
+				let mut json_types = json["types"].as_array().unwrap().clone();
+				value_types.sort_by_key(|ty| ty.to_string());
+				json_types.sort_by_key(|ty| ty.to_string());
+				assert_eq!(value_types, json_types);
+				let (mut value, mut json) = (value.clone(), json);
+				value.as_object_mut().unwrap().remove("types");
+				json.as_object_mut().unwrap().remove("types");
+				assert_eq!(value, json);
+			} else {
+				assert_eq!(value, json);
+			}
+			assert_eq!(serde_json::from_value::<RR>(value).unwrap(), rr);
+		}
+	}
+
+	#[test]
+	fn test_non_utf8_txt() {
+		let rr: RR = "example.com. IN TXT \"\\255\\000\\\"\"".parse().unwrap();
+		let json = serde_json::to_string(&rr).unwrap();
+		assert_eq!(json, "{\"type\":\"txt\",\"name\":\"example.com.\",\"contents\":[255,0,34]}");
+		assert_eq!(serde_json::from_str::<RR>(&json).unwrap(), rr);
+
+		let rr: RR = "example.com. IN TXT \"quote\\\"d\"".parse().unwrap();
+		let json = serde_json::to_string(&rr).unwrap();
+		assert_eq!(json, "{\"type\":\"txt\",\"name\":\"example.com.\",\"contents\":\"quote\\\"d\"}");
+		assert_eq!(serde_json::from_str::<RR>(&json).unwrap(), rr);
+	}
+
+	#[test]
+	fn test_bad_json() {
+		assert!(serde_json::from_str::<Name>("\"no-trailing-dot\"").is_err());
+		assert!(serde_json::from_str::<RR>("{\"type\":\"mx\",\"name\":\"example.com.\"}").is_err());
+		assert!(serde_json::from_str::<RR>(
+			"{\"type\":\"ds\",\"name\":\"com.\",\"key_tag\":1,\"alg\":1,\"digest_type\":1,\"digest\":\"ABC\"}"
+		).is_err());
+		assert!(serde_json::from_str::<NSecTypeMask>("[\"A\", 65536]").is_err());
+		assert_eq!(serde_json::from_str::<NSecTypeMask>("[\"A\", 1234]").unwrap(),
+			NSecTypeMask::from_types(&[1, 1234]));
+	}
+}
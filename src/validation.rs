@@ -8,7 +8,7 @@ use core::cmp::{self, Ordering};
 use crate::base32;
 use crate::crypto;
 use crate::rr::*;
-use crate::ser::write_name;
+use crate::ser::{write_name, write_rrsig_signing_data};
 use crate::MAX_PROOF_STEPS;
 
 /// Gets the trusted root anchors
@@ -32,7 +32,7 @@ pub fn root_hints() -> Vec<DS> {
 	res
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 /// An error when validating DNSSEC signatures or other data
 pub enum ValidationError {
 	/// An algorithm used in signing was not supported.
@@ -72,40 +72,8 @@ where Keys: IntoIterator<Item = &'a DnsKey> {
 				_ => return Err(ValidationError::UnsupportedAlgorithm),
 			};
 
-			hash_ctx.update(&sig.ty.to_be_bytes());
-			hash_ctx.update(&sig.alg.to_be_bytes());
-			hash_ctx.update(&sig.labels.to_be_bytes());
-			hash_ctx.update(&sig.orig_ttl.to_be_bytes());
-			hash_ctx.update(&sig.expiration.to_be_bytes());
-			hash_ctx.update(&sig.inception.to_be_bytes());
-			hash_ctx.update(&sig.key_tag.to_be_bytes());
-			write_name(&mut hash_ctx, &sig.key_name);
-
-			records.sort_unstable();
-
-			for record in records.iter() {
-				let record_labels = record.name().labels() as usize;
-				let labels = sig.labels.into();
-				// For NSec types, the name should already match the wildcard, so we don't do any
-				// filtering here. This is relied upon in `verify_rr_stream` to check whether an
-				// NSec record is matching via wildcard (as otherwise we'd allow a resolver to
-				// change the name out from under us and change the wildcard to something else).
-				if record.ty() != NSec::TYPE && record_labels != labels {
-					if record_labels < labels { return Err(ValidationError::Invalid); }
-					let signed_name = record.name().trailing_n_labels(sig.labels);
-					debug_assert!(signed_name.is_some());
-					if let Some(name) = signed_name {
-						hash_ctx.update(b"\x01*");
-						write_name(&mut hash_ctx, name);
-					} else { return Err(ValidationError::Invalid); }
-				} else {
-					write_name(&mut hash_ctx, record.name());
-				}
-				hash_ctx.update(&record.ty().to_be_bytes());
-				hash_ctx.update(&1u16.to_be_bytes()); // The INternet class
-				hash_ctx.update(&sig.orig_ttl.to_be_bytes());
-				record.serialize_u16_len_prefixed(&mut hash_ctx);
-			}
+			write_rrsig_signing_data(sig, &mut records, &mut hash_ctx)
+				.map_err(|()| ValidationError::Invalid)?;
 
 			let hash = hash_ctx.finish();
 			let sig_validation = match sig.alg {
@@ -139,6 +107,17 @@ where Keys: IntoIterator<Item = &'a DnsKey> {
 	Err(ValidationError::Invalid)
 }
 
+/// Computes the digest of a [`DnsKey`] (or [`CDnsKey`]) as it appears in a [`DS`] record.
+fn dnskey_digest(mut ctx: crypto::hash::Hasher, name: &Name, flags: u16, protocol: u8, alg: u8,
+	pubkey: &[u8]) -> crypto::hash::HashResult {
+	write_name(&mut ctx, name);
+	ctx.update(&flags.to_be_bytes());
+	ctx.update(&protocol.to_be_bytes());
+	ctx.update(&alg.to_be_bytes());
+	ctx.update(pubkey);
+	ctx.finish()
+}
+
 /// Verify [`RRSig`]s over [`DnsKey`], returning a reference to the [`RRSig`] that matched, if any.
 fn verify_dnskeys<'r, 'd, RI, R, DI, D>(sigs: RI, dses: DI, records: Vec<&DnsKey>)
 -> Result<&'r RRSig, ValidationError>
@@ -165,18 +144,14 @@ where RI: IntoIterator<IntoIter = R>, R: Iterator<Item = &'r RRSig>,
 		for ds in dses.clone() {
 			if ds.alg != dnskey.alg { continue; }
 			if dnskey.key_tag() == ds.key_tag {
-				let mut ctx = match ds.digest_type {
+				let ctx = match ds.digest_type {
 					1 if trust_sha1 => crypto::hash::Hasher::sha1(),
 					2 => crypto::hash::Hasher::sha256(),
 					4 => crypto::hash::Hasher::sha384(),
 					_ => continue,
 				};
-				write_name(&mut ctx, &dnskey.name);
-				ctx.update(&dnskey.flags.to_be_bytes());
-				ctx.update(&dnskey.protocol.to_be_bytes());
-				ctx.update(&dnskey.alg.to_be_bytes());
-				ctx.update(&dnskey.pubkey);
-				let hash = ctx.finish();
+				let hash = dnskey_digest(ctx, &dnskey.name, dnskey.flags, dnskey.protocol,
+					dnskey.alg, &dnskey.pubkey);
 				if hash.as_ref() == ds.digest {
 					validated_dnskeys.push(*dnskey);
 					break;
@@ -249,6 +224,65 @@ pub struct VerifiedRRStream<'a> {
 	pub max_cache_ttl: u32,
 }
 
+/// An owned version of [`VerifiedRRStream`], e.g. for storing or serializing verification
+/// results after the original [`RR`]s have been dropped.
+///
+/// The same caveats around [`Self::valid_from`] and [`Self::expires`] apply here.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedVerifiedRRStream {
+	/// See [`VerifiedRRStream::valid_from`].
+	pub valid_from: u64,
+	/// See [`VerifiedRRStream::expires`].
+	pub expires: u64,
+	/// See [`VerifiedRRStream::max_cache_ttl`].
+	pub max_cache_ttl: u32,
+	/// See [`VerifiedRRStream::verified_rrs`].
+	pub verified_rrs: Vec<RR>,
+}
+impl From<VerifiedRRStream<'_>> for OwnedVerifiedRRStream {
+	fn from(stream: VerifiedRRStream<'_>) -> Self {
+		OwnedVerifiedRRStream {
+			valid_from: stream.valid_from, expires: stream.expires,
+			max_cache_ttl: stream.max_cache_ttl,
+			verified_rrs: stream.verified_rrs.into_iter().cloned().collect(),
+		}
+	}
+}
+
+/// The change to a child zone's [`DS`] set which the child requested by publishing [`CDS`] and/or
+/// [`CDnsKey`] records, as returned by [`VerifiedRRStream::ds_update`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DSUpdate {
+	/// The parent should replace the child's [`DS`] set with the given records.
+	Replace(Vec<DS>),
+	/// The child requested that all of its [`DS`] records be removed from the parent, turning off
+	/// DNSSEC for the child zone (see RFC 8078 section 4).
+	Delete,
+}
+
+/// Gets the hasher for a [`DS`] (or [`CDS`]) digest type, if we support (and trust) it. As in
+/// [`verify_dnskeys`], SHA-1 digests are only trusted if no stronger digests are present.
+fn ds_digest_hasher(digest_type: u8, trust_sha1: bool) -> Option<crypto::hash::Hasher> {
+	match digest_type {
+		1 if trust_sha1 => Some(crypto::hash::Hasher::sha1()),
+		2 => Some(crypto::hash::Hasher::sha256()),
+		4 => Some(crypto::hash::Hasher::sha384()),
+		_ => None,
+	}
+}
+
+/// Checks if the given [`CDS`] refers to the given [`CDnsKey`]. A [`CDS`] with a
+/// [`CDS::digest_type`] we don't support (or trust) never matches.
+fn cds_matches_cdnskey(cds: &CDS, cdnskey: &CDnsKey, trust_sha1: bool) -> bool {
+	if cds.key_tag != cdnskey.key_tag() || cds.alg != cdnskey.alg { return false; }
+	if let Some(ctx) = ds_digest_hasher(cds.digest_type, trust_sha1) {
+		let hash = dnskey_digest(ctx, &cdnskey.name, cdnskey.flags, cdnskey.protocol,
+			cdnskey.alg, &cdnskey.pubkey);
+		hash.as_ref() == cds.digest
+	} else { false }
+}
+
 fn resolve_time(time: u32) -> u64 {
 	// RFC 2065 was published in January 1997, so we arbitrarily use that as a cutoff and assume
 	// any timestamps before then are actually past 2106 instead.
@@ -260,37 +294,6 @@ fn resolve_time(time: u32) -> u64 {
 	}
 }
 
-fn nsec_ord(a: &str, b: &str) -> Ordering {
-	let mut a_label_iter = a.rsplit('.');
-	let mut b_label_iter = b.rsplit('.');
-	loop {
-		match (a_label_iter.next(), b_label_iter.next()) {
-			(Some(_), None) => return Ordering::Greater,
-			(None, Some(_)) => return Ordering::Less,
-			(Some(a_label), Some(b_label)) => {
-				let mut a_bytes = a_label.bytes();
-				let mut b_bytes = b_label.bytes();
-				loop {
-					match (a_bytes.next(), b_bytes.next()) {
-						(Some(_), None) => return Ordering::Greater,
-						(None, Some(_)) => return Ordering::Less,
-						(Some(mut a), Some(mut b)) => {
-							if a.is_ascii_uppercase() {
-								a += b'a' - b'A';
-							}
-							if b.is_ascii_uppercase() {
-								b += b'a' - b'A';
-							}
-							if a != b { return a.cmp(&b); }
-						},
-						(None, None) => break,
-					}
-				}
-			},
-			(None, None) => return Ordering::Equal,
-		}
-	}
-}
 fn nsec_ord_extra<T, U>(a: &(&str, T, U), b: &(&str, T, U)) -> Ordering {
 	nsec_ord(a.0, b.0)
 }
@@ -307,6 +310,20 @@ fn rfc4034_sort_test() {
 	assert_eq!(sorted, v);
 }
 
+#[cfg(test)]
+#[test]
+fn rfc4034_escaped_sort_test() {
+	// The same example as above, but using escaped names as in the RFC itself
+	let v: Vec<Name> = ["example.", "a.example.", "yljkjljk.a.example.", "Z.a.example.",
+		"zABC.a.EXAMPLE.", "z.example.", "\\001.z.example.", "*.z.example.", "\\200.z.example."]
+		.iter().map(|s| (*s).try_into().unwrap()).collect();
+	let mut sorted = v.clone();
+	sorted.sort_unstable_by(|a, b| nsec_ord(a, b));
+	assert_eq!(sorted, v);
+	assert_eq!(v[6].as_str(), "\\001.z.example.");
+	assert_eq!(v[8].as_str(), "\\200.z.example.");
+}
+
 /// Verifies the given set of resource records.
 ///
 /// Given a set of arbitrary records, this attempts to validate DNSSEC data from the [`root_hints`]
@@ -318,8 +335,26 @@ fn rfc4034_sort_test() {
 /// You MUST check that the current UNIX time is between [`VerifiedRRStream::valid_from`] and
 /// [`VerifiedRRStream::expires`].
 pub fn verify_rr_stream<'a>(inp: &'a [RR]) -> Result<VerifiedRRStream<'a>, ValidationError> {
+	verify_rr_stream_with_anchors(inp, &root_hints())
+}
+
+/// Verifies the given set of resource records, as [`verify_rr_stream`] does, but starting from
+/// the given root trust `anchors` rather than the [`root_hints`].
+pub(crate) fn verify_rr_stream_with_anchors<'a>(inp: &'a [RR], anchors: &[DS])
+-> Result<VerifiedRRStream<'a>, ValidationError> {
+	verify_rr_stream_with_denials(inp, anchors).map(|(stream, _)| stream)
+}
+
+/// Verified [`NSec`] and [`NSec3`] records alongside the zone which signed them.
+type DenialRecords<'a> = Vec<(&'a RR, &'a Name)>;
+
+/// Verifies the given set of resource records, starting from the given root trust `anchors`,
+/// returning the [`VerifiedRRStream`] as well as the verified [`NSec`] and [`NSec3`] records.
+fn verify_rr_stream_with_denials<'a>(inp: &'a [RR], anchors: &[DS])
+-> Result<(VerifiedRRStream<'a>, DenialRecords<'a>), ValidationError> {
 	let mut zone = ".";
 	let mut res = Vec::new();
+	let mut denial_rrs = Vec::new();
 	let mut rrs_needing_non_existence_proofs = Vec::new();
 	let mut pending_ds_sets = Vec::with_capacity(1);
 	let mut latest_inception = 0;
@@ -347,9 +382,8 @@ pub fn verify_rr_stream<'a>(inp: &'a [RR]) -> Result<VerifiedRRStream<'a>, Valid
 		let dnskeys = inp.iter()
 			.filter_map(|rr| if let RR::DnsKey(dnskey) = rr { Some(dnskey) } else { None })
 			.filter(move |dnskey| dnskey.name.as_str() == zone);
-		let root_hints = root_hints();
 		let verified_dnskey_rrsig = if zone == "." {
-			verify_dnskeys(dnskey_rrsigs, &root_hints, dnskeys.clone().collect())?
+			verify_dnskeys(dnskey_rrsigs, anchors, dnskeys.clone().collect())?
 		} else {
 			debug_assert!(next_ds_set.is_some());
 			if next_ds_set.is_none() { break 'next_zone; }
@@ -368,7 +402,7 @@ pub fn verify_rr_stream<'a>(inp: &'a [RR]) -> Result<VerifiedRRStream<'a>, Valid
 				return Err(ValidationError::ValidationCountLimited);
 			}
 
-			if !rrsig.name.ends_with(zone) { return Err(ValidationError::Invalid); }
+			if !name_in_zone(&rrsig.name, zone) { return Err(ValidationError::Invalid); }
 			let signed_records = inp.iter()
 				.filter(|rr| rr.name() == &rrsig.name && rr.ty() == rrsig.ty);
 			match verify_rrsig(rrsig, dnskeys.clone(), signed_records.clone().collect()) {
@@ -418,6 +452,9 @@ pub fn verify_rr_stream<'a>(inp: &'a [RR]) -> Result<VerifiedRRStream<'a>, Valid
 					}
 					for record in signed_records {
 						if !res.contains(&record) { res.push(record); }
+						if rrsig.ty == NSec::TYPE || rrsig.ty == NSec3::TYPE {
+							denial_rrs.push((record, &rrsig.key_name));
+						}
 					}
 				},
 			}
@@ -432,7 +469,7 @@ pub fn verify_rr_stream<'a>(inp: &'a [RR]) -> Result<VerifiedRRStream<'a>, Valid
 	'proof_search_loop: while let Some((name, zone, ty)) = rrs_needing_non_existence_proofs.pop() {
 		let nsec_search = res.iter()
 			.filter_map(|rr| if let RR::NSec(nsec) = rr { Some(nsec) } else { None })
-			.filter(|nsec| nsec.name.ends_with(zone.as_str()));
+			.filter(|nsec| nsec.name.is_subdomain_of(zone));
 		for nsec in nsec_search {
 			let name_matches = nsec.name.as_str() == name;
 			let name_contained = nsec_ord(&nsec.name,  name) != Ordering::Greater &&
@@ -445,7 +482,7 @@ pub fn verify_rr_stream<'a>(inp: &'a [RR]) -> Result<VerifiedRRStream<'a>, Valid
 		}
 		let nsec3_search = res.iter()
 			.filter_map(|rr| if let RR::NSec3(nsec3) = rr { Some(nsec3) } else { None })
-			.filter(|nsec3| nsec3.name.ends_with(zone.as_str()));
+			.filter(|nsec3| nsec3.name.is_subdomain_of(zone));
 
 		// Because we will only ever have two entries, a Vec is simpler than a map here.
 		let mut nsec3params_to_name_hash = Vec::new();
@@ -460,16 +497,8 @@ pub fn verify_rr_stream<'a>(inp: &'a [RR]) -> Result<VerifiedRRStream<'a>, Valid
 				.any(|(iterations, salt, _)| *iterations == nsec3.hash_iterations && *salt == &nsec3.salt)
 			{ continue; }
 
-			let mut hasher = crypto::hash::Hasher::sha1();
-			write_name(&mut hasher, name);
-			hasher.update(&nsec3.salt);
-			for _ in 0..nsec3.hash_iterations {
-				let res = hasher.finish();
-				hasher = crypto::hash::Hasher::sha1();
-				hasher.update(res.as_ref());
-				hasher.update(&nsec3.salt);
-			}
-			nsec3params_to_name_hash.push((nsec3.hash_iterations, &nsec3.salt, hasher.finish()));
+			let hash = nsec3_hash(name, nsec3.hash_iterations, &nsec3.salt);
+			nsec3params_to_name_hash.push((nsec3.hash_iterations, &nsec3.salt, hash));
 
 			if nsec3params_to_name_hash.len() >= 2 {
 				// We only allow for up to two sets of hash_iterations/salt per zone. Beyond that
@@ -512,12 +541,361 @@ pub fn verify_rr_stream<'a>(inp: &'a [RR]) -> Result<VerifiedRRStream<'a>, Valid
 
 	res.retain(|rr| rr.ty() != NSec::TYPE && rr.ty() != NSec3::TYPE);
 
-	Ok(VerifiedRRStream {
+	Ok((VerifiedRRStream {
 		verified_rrs: res, valid_from: latest_inception, expires: earliest_expiry,
 		max_cache_ttl: min_ttl,
+	}, denial_rrs))
+}
+
+/// Checks that a type mask (from an [`NSec`] or [`NSec3`] record whose owner matched a name) shows
+/// that no records of type `ty` exist at the name.
+fn type_mask_denies(types: &NSecTypeMask, ty: u16) -> bool {
+	// A parent zone's NSEC/NSEC3 record at a delegation can only speak to the records the parent
+	// is authoritative for, i.e. the DS records.
+	let is_delegation = types.contains_type(NS::TYPE) && !types.contains_type(SOA::TYPE);
+	if is_delegation && ty != DS::TYPE { return false; }
+	// If a CNAME exists, the records would be found at its target instead.
+	if ty != CName::TYPE && types.contains_type(CName::TYPE) { return false; }
+	!types.contains_type(ty)
+}
+
+/// Checks that a type mask shows that names below its owner are not part of the zone, because
+/// the owner is a delegation or a DNAME.
+fn type_mask_excludes_descendants(types: &NSecTypeMask) -> bool {
+	(types.contains_type(NS::TYPE) && !types.contains_type(SOA::TYPE)) || types.contains_type(DName::TYPE)
+}
+
+/// Gets the `*.` wildcard name below `name`.
+fn wildcard_of(name: &Name) -> Option<Name> {
+	let wildcard = if name.as_str() == "." { "*.".to_owned() } else { "*.".to_owned() + name.as_str() };
+	wildcard.try_into().ok()
+}
+
+/// Checks that an [`NSec`] record in `zone` covers `name`, i.e. proves that it does not exist.
+fn nsec_covers(nsec: &NSec, zone: &Name, name: &Name) -> bool {
+	let after_start = nsec_ord(&nsec.name, name) == Ordering::Less;
+	// The last NSEC record in a zone points back to the zone's apex.
+	if nsec.next_name == *zone { after_start } else {
+		after_start && nsec_ord(&nsec.next_name, name) == Ordering::Greater
+	}
+}
+
+/// Checks that the given [`NSec`] records from `zone` prove that no records of type `ty` exist at
+/// `name`, as described in RFC 4035 section 5.4.
+fn nsecs_deny(nsecs: &[&NSec], zone: &Name, name: &Name, ty: u16) -> bool {
+	if let Some(nsec) = nsecs.iter().find(|nsec| nsec.name == *name) {
+		return type_mask_denies(&nsec.types, ty);
+	}
+
+	let covering = if let Some(nsec) = nsecs.iter().find(|nsec| nsec_covers(nsec, zone, name)) {
+		nsec
+	} else { return false };
+	// Names below a delegation or DNAME aren't part of this zone, so can't be denied by it.
+	if covering.name != *zone && name.is_subdomain_of(&covering.name) &&
+		type_mask_excludes_descendants(&covering.types)
+	{
+		return false;
+	}
+
+	// The name doesn't exist, but it may still be matched by a wildcard at its closest encloser.
+	let owner_ancestor = name.common_ancestor(&covering.name);
+	let next_ancestor = name.common_ancestor(&covering.next_name);
+	let closest_encloser =
+		if owner_ancestor.labels() > next_ancestor.labels() { owner_ancestor } else { next_ancestor };
+	let wildcard = if let Some(wildcard) = wildcard_of(&closest_encloser) { wildcard } else { return false };
+	nsecs.iter().any(|nsec| {
+		(nsec.name == wildcard && type_mask_denies(&nsec.types, ty)) || nsec_covers(nsec, zone, &wildcard)
 	})
 }
 
+/// Gets the [`NSec3`] hash of `name` with the given parameters.
+fn nsec3_hash(name: &str, iterations: u16, salt: &[u8]) -> crypto::hash::HashResult {
+	let mut hasher = crypto::hash::Hasher::sha1();
+	write_name(&mut hasher, name);
+	hasher.update(salt);
+	for _ in 0..iterations {
+		let res = hasher.finish();
+		hasher = crypto::hash::Hasher::sha1();
+		hasher.update(res.as_ref());
+		hasher.update(salt);
+	}
+	hasher.finish()
+}
+
+/// Checks that the given [`NSec3`] records from `zone` prove that no records of type `ty` exist at
+/// `name`, as described in RFC 5155 sections 8.4 through 8.7.
+fn nsec3s_deny(nsec3s: &[&NSec3], zone: &Name, name: &Name, ty: u16) -> bool {
+	// A zone only uses one set of NSEC3 parameters at a time, so we only consider records with the
+	// same parameters as the first one we support.
+	let params = nsec3s.iter()
+		.find(|nsec3| nsec3.hash_algo == 1 && nsec3.hash_iterations <= 2500);
+	let (iterations, salt) = if let Some(nsec3) = params {
+		(nsec3.hash_iterations, &nsec3.salt)
+	} else { return false };
+	let nsec3s: Vec<(&NSec3, Vec<u8>)> = nsec3s.iter()
+		.filter(|nsec3| nsec3.hash_algo == 1 && nsec3.hash_iterations == iterations && nsec3.salt == *salt)
+		// Opt-out NSEC3 records (or those with unknown flags) can't prove non-existence.
+		.filter(|nsec3| nsec3.flags == 0 && nsec3.next_name_hash.len() == 20)
+		.filter_map(|nsec3| {
+			let (start_hash_base32, _) = nsec3.name.split_once('.')?;
+			let start_hash = base32::decode(start_hash_base32).ok()?;
+			if start_hash.len() == 20 { Some((*nsec3, start_hash)) } else { None }
+		})
+		.collect();
+	let hash = |name: &Name| nsec3_hash(name.as_str(), iterations, salt);
+	let find_match = |hash: &[u8]| nsec3s.iter()
+		.find(|(_, start_hash)| &start_hash[..] == hash).map(|(nsec3, _)| *nsec3);
+	let covers = |hash: &[u8]| nsec3s.iter().any(|(nsec3, start_hash)| {
+		let next_hash = &nsec3.next_name_hash[..];
+		if start_hash[..] < *next_hash {
+			&start_hash[..] < hash && hash < next_hash
+		} else {
+			// The last NSEC3 record in the hash order wraps around to the first.
+			&start_hash[..] < hash || hash < next_hash
+		}
+	});
+
+	if let Some(nsec3) = find_match(hash(name).as_ref()) {
+		return type_mask_denies(&nsec3.types, ty);
+	}
+
+	// Otherwise, we need a closest encloser proof - the closest ancestor of the name which does
+	// exist, a covered "next closer" name below it, and a covered wildcard at the closest encloser
+	// (or, if the wildcard exists, proof that it doesn't have records of the given type).
+	let mut next_closer = name.clone();
+	while let Some(closest_encloser) = next_closer.parent() {
+		if !closest_encloser.is_subdomain_of(zone) { return false; }
+		if let Some(nsec3) = find_match(hash(&closest_encloser).as_ref()) {
+			if type_mask_excludes_descendants(&nsec3.types) { return false; }
+			if !covers(hash(&next_closer).as_ref()) { return false; }
+			let wildcard = if let Some(wildcard) = wildcard_of(&closest_encloser) { wildcard } else { return false };
+			let wildcard_hash = hash(&wildcard);
+			return covers(wildcard_hash.as_ref()) ||
+				find_match(wildcard_hash.as_ref()).map_or(false, |nsec3| type_mask_denies(&nsec3.types, ty));
+		}
+		next_closer = closest_encloser;
+	}
+	false
+}
+
+/// Verifies the given set of resource records, as [`verify_rr_stream`] does, and checks that the
+/// [`NSec`] or [`NSec3`] records contained prove that no records of type `ty` exist at `name`
+/// (either because `name` does not exist at all or because it has no records of type `ty`).
+///
+/// The returned [`VerifiedRRStream`] contains any other records which could be verified, e.g. the
+/// zone's [`SOA`]. As with [`verify_rr_stream`], you MUST check that the current UNIX time is
+/// between [`VerifiedRRStream::valid_from`] and [`VerifiedRRStream::expires`].
+pub fn verify_non_existence<'a>(inp: &'a [RR], name: &Name, ty: u16)
+-> Result<VerifiedRRStream<'a>, ValidationError> {
+	verify_non_existence_with_anchors(inp, name, ty, &root_hints())
+}
+
+/// Verifies that no records of type `ty` exist at `name`, as [`verify_non_existence`] does, but
+/// starting from the given root trust `anchors` rather than the [`root_hints`].
+pub(crate) fn verify_non_existence_with_anchors<'a>(inp: &'a [RR], name: &Name, ty: u16, anchors: &[DS])
+-> Result<VerifiedRRStream<'a>, ValidationError> {
+	let (stream, denial_rrs) = verify_rr_stream_with_denials(inp, anchors)?;
+	if stream.verified_rrs.iter().any(|rr| rr.name() == name && (rr.ty() == ty || rr.ty() == CName::TYPE)) {
+		return Err(ValidationError::Invalid);
+	}
+
+	// The denial has to come from the zone which is authoritative for the name, which is the
+	// closest one we have signed records from (though DS records live in the parent zone).
+	let zone = denial_rrs.iter()
+		.map(|(_, zone)| *zone)
+		.filter(|zone| name.is_subdomain_of(zone) && (ty != DS::TYPE || *zone != name))
+		.max_by_key(|zone| zone.labels())
+		.ok_or(ValidationError::Invalid)?;
+	let nsecs: Vec<&NSec> = denial_rrs.iter()
+		.filter(|(_, signer)| *signer == zone)
+		.filter_map(|(rr, _)| if let RR::NSec(nsec) = rr { Some(nsec) } else { None })
+		.collect();
+	let nsec3s: Vec<&NSec3> = denial_rrs.iter()
+		.filter(|(_, signer)| *signer == zone)
+		.filter_map(|(rr, _)| if let RR::NSec3(nsec3) = rr { Some(nsec3) } else { None })
+		.collect();
+	if nsecs_deny(&nsecs, zone, name, ty) || nsec3s_deny(&nsec3s, zone, name, ty) {
+		Ok(stream)
+	} else {
+		Err(ValidationError::Invalid)
+	}
+}
+
+/// Checks the signatures in a proof as it is being built, allowing a proof builder to give up as
+/// soon as it is handed bogus data rather than only once the full proof is verified.
+///
+/// Each [`RRSig`] is checked as soon as its signer's [`DnsKey`]s are available, and each zone's
+/// [`DnsKey`]s are checked against its [`DS`] records once their signatures have been checked.
+/// This does not replace [`verify_rr_stream`], which still needs to be called on the final proof.
+#[derive(Clone)]
+pub(crate) struct IncrementalValidator {
+	records: Vec<RR>,
+	checked_rrsigs: Vec<RRSig>,
+	checked_zones: Vec<Name>,
+	/// The number of signature checks made, limited to [`MAX_PROOF_STEPS`] as in
+	/// [`verify_rr_stream`].
+	validations: usize,
+}
+
+impl IncrementalValidator {
+	pub(crate) fn new() -> Self {
+		IncrementalValidator {
+			records: Vec::new(), checked_rrsigs: Vec::new(), checked_zones: Vec::new(), validations: 0,
+		}
+	}
+
+	/// Adds records to the proof, checking any signatures which can now be checked.
+	///
+	/// On failure, returns the zone which provided bogus data.
+	pub(crate) fn add_records(&mut self, rrs: Vec<RR>) -> Result<(), (Name, ValidationError)> {
+		for rr in rrs {
+			if !self.records.contains(&rr) { self.records.push(rr); }
+		}
+
+		// RRSigs over DnsKeys are checked alongside the DS records below, all others are checked
+		// with the signer's DnsKeys as soon as we have them.
+		let rrsigs: Vec<RRSig> = self.records.iter()
+			.filter_map(|rr| if let RR::RRSig(sig) = rr { Some(sig) } else { None })
+			.filter(|rrsig| rrsig.ty != DnsKey::TYPE && !self.checked_rrsigs.contains(rrsig))
+			.cloned().collect();
+		for rrsig in rrsigs {
+			let zone = &rrsig.key_name;
+			let dnskeys = self.records.iter()
+				.filter_map(|rr| if let RR::DnsKey(dnskey) = rr { Some(dnskey) } else { None })
+				.filter(|dnskey| dnskey.name == *zone);
+			if dnskeys.clone().next().is_none() { continue; }
+
+			self.validations += 1;
+			if self.validations > MAX_PROOF_STEPS {
+				return Err((zone.clone(), ValidationError::ValidationCountLimited));
+			}
+			if !name_in_zone(&rrsig.name, zone.as_str()) {
+				return Err((zone.clone(), ValidationError::Invalid));
+			}
+			let signed_records = self.records.iter()
+				.filter(|rr| rr.name() == &rrsig.name && rr.ty() == rrsig.ty);
+			match verify_rrsig(&rrsig, dnskeys, signed_records.collect()) {
+				Ok(())|Err(ValidationError::UnsupportedAlgorithm) => {},
+				Err(e) => return Err((zone.clone(), e)),
+			}
+			self.checked_rrsigs.push(rrsig);
+		}
+
+		let mut zones: Vec<Name> = Vec::new();
+		for rr in self.records.iter() {
+			if let RR::DnsKey(dnskey) = rr {
+				if !self.checked_zones.contains(&dnskey.name) && !zones.contains(&dnskey.name) {
+					zones.push(dnskey.name.clone());
+				}
+			}
+		}
+		for zone in zones {
+			let root_hints = root_hints();
+			let dses: Vec<&DS> = if zone.as_str() == "." {
+				root_hints.iter().collect()
+			} else {
+				let ds_checked = self.checked_rrsigs.iter()
+					.any(|rrsig| rrsig.name == zone && rrsig.ty == DS::TYPE);
+				if !ds_checked { continue; }
+				self.records.iter()
+					.filter_map(|rr| if let RR::DS(ds) = rr { Some(ds) } else { None })
+					.filter(|ds| ds.name == zone)
+					.collect()
+			};
+			let dnskey_rrsigs = self.records.iter()
+				.filter_map(|rr| if let RR::RRSig(sig) = rr { Some(sig) } else { None })
+				.filter(|rrsig| rrsig.name == zone && rrsig.ty == DnsKey::TYPE);
+			// Until we have a signature over the DnsKeys there's nothing to check.
+			if dnskey_rrsigs.clone().next().is_none() { continue; }
+			let dnskeys = self.records.iter()
+				.filter_map(|rr| if let RR::DnsKey(dnskey) = rr { Some(dnskey) } else { None })
+				.filter(|dnskey| dnskey.name == zone)
+				.collect();
+
+			self.validations += 1;
+			if self.validations > MAX_PROOF_STEPS {
+				return Err((zone, ValidationError::ValidationCountLimited));
+			}
+			match verify_dnskeys(dnskey_rrsigs, dses, dnskeys) {
+				Ok(_)|Err(ValidationError::UnsupportedAlgorithm) => {},
+				Err(e) => return Err((zone, e)),
+			}
+			self.checked_zones.push(zone);
+		}
+		Ok(())
+	}
+}
+
+/// Verifies that a [`ZoneMD`] record matches the full contents of the zone at its apex, as
+/// described in RFC 8976.
+///
+/// `zone` must contain every record in the zone (including its [`SOA`], any [`RRSig`]s,
+/// [`NSec`]s, glue, etc) alongside each record's TTL, as TTLs are included in the digest, e.g. as
+/// parsed with [`crate::ser::parse_rr_stream_with_ttls`]. Records which are not at or below the
+/// [`ZoneMD`]'s name are ignored. Note that zones which contain record types not supported by
+/// this crate cannot be represented, and thus cannot be verified.
+///
+/// Only the SIMPLE scheme with SHA-384 or SHA-512 is supported, with other schemes or hash
+/// algorithms resulting in [`ValidationError::UnsupportedAlgorithm`].
+///
+/// The [`ZoneMD`] passed here MUST itself have been validated, e.g. by looking it up in the
+/// [`VerifiedRRStream::verified_rrs`] returned by [`verify_rr_stream`], before trusting the
+/// contents of the zone.
+pub fn verify_zonemd(zone: &[(RR, u32)], zonemd: &ZoneMD) -> Result<(), ValidationError> {
+	let mut hash_ctx = match (zonemd.scheme, zonemd.hash_alg) {
+		(1, 1) => crypto::hash::Hasher::sha384(),
+		(1, 2) => crypto::hash::Hasher::sha512(),
+		_ => return Err(ValidationError::UnsupportedAlgorithm),
+	};
+	let apex = &zonemd.name;
+
+	let soa_serial = zone.iter()
+		.filter_map(|(rr, _)| if let RR::SOA(soa) = rr { Some(soa) } else { None })
+		.find(|soa| &soa.name == apex)
+		.map(|soa| soa.serial)
+		.ok_or(ValidationError::Invalid)?;
+	if soa_serial != zonemd.serial { return Err(ValidationError::Invalid); }
+
+	let mut records = Vec::with_capacity(zone.len());
+	for (rr, ttl) in zone {
+		if !name_in_zone(rr.name(), apex) { continue; }
+		if rr.name() == apex {
+			// The apex ZONEMD records and their signatures are not included in the digest.
+			match rr {
+				RR::ZoneMD(_) => continue,
+				RR::RRSig(rrsig) if rrsig.ty == ZoneMD::TYPE => continue,
+				_ => {},
+			}
+		}
+		let mut rdata = Vec::new();
+		Record::write_u16_len_prefixed_data(rr, &mut rdata);
+		records.push((rr.name(), Record::ty(rr), *ttl, rdata));
+	}
+
+	// Sort in RFC 4034 canonical order, comparing RDATA without its length prefix, and drop any
+	// duplicate records.
+	records.sort_unstable_by(|a, b| {
+		nsec_ord(a.0, b.0)
+			.then_with(|| a.1.cmp(&b.1))
+			.then_with(|| a.3[2..].cmp(&b.3[2..]))
+	});
+	records.dedup_by(|a, b| a.0 == b.0 && a.1 == b.1 && a.3 == b.3);
+
+	for (name, ty, ttl, rdata) in records {
+		write_name(&mut hash_ctx, name);
+		hash_ctx.update(&ty.to_be_bytes());
+		hash_ctx.update(&1u16.to_be_bytes()); // The INternet class
+		hash_ctx.update(&ttl.to_be_bytes());
+		hash_ctx.update(&rdata);
+	}
+
+	if hash_ctx.finish().as_ref() == zonemd.digest {
+		Ok(())
+	} else {
+		Err(ValidationError::Invalid)
+	}
+}
+
 impl<'a> VerifiedRRStream<'a> {
 	/// Given a name, resolve any [`CName`] records and return any verified records which were
 	/// pointed to by the original name.
@@ -541,7 +919,7 @@ impl<'a> VerifiedRRStream<'a> {
 			}
 
 			let mut dname_search = self.verified_rrs.iter()
-				.filter(|rr| name.ends_with(&**rr.name()))
+				.filter(|rr| name_in_zone(name, rr.name()))
 				.filter_map(|rr| if let RR::DName(dn) = rr { Some(dn) } else { None });
 			if let Some(dname) = dname_search.next() {
 				let prefix = name.strip_suffix(&*dname.name).expect("We just filtered for this");
@@ -559,6 +937,187 @@ impl<'a> VerifiedRRStream<'a> {
 			return self.verified_rrs.iter().filter(|rr| rr.name() == name).copied().collect();
 		}
 	}
+
+	/// Given the apex of a child zone, gets the [`DS`] records which the child has requested its
+	/// parent publish via [`CDS`] and/or [`CDnsKey`] records (see RFC 7344 and RFC 8078).
+	///
+	/// Because [`verify_rr_stream`] only includes records signed by a [`DnsKey`] it validated,
+	/// any [`CDS`] or [`CDnsKey`] records considered here were signed by a key in the zone's
+	/// current [`DnsKey`] set.
+	///
+	/// If only [`CDnsKey`] records are present, [`DS`] records using SHA-256 digests are built
+	/// from them. If both [`CDS`] and [`CDnsKey`] records are present they must refer to the same
+	/// set of keys, and the [`CDS`] records are returned as-is. [`CDS`] records with digest types
+	/// we don't support, or using SHA-1 alongside stronger digests, are ignored.
+	///
+	/// Returns `Ok(None)` if the child has not published any [`CDS`] or [`CDnsKey`] records,
+	/// [`ValidationError::UnsupportedAlgorithm`] if none of the [`CDS`] records use a digest type
+	/// we support and [`ValidationError::Invalid`] if the published records are inconsistent.
+	///
+	/// You MUST still check that the current UNIX time is between
+	/// [`VerifiedRRStream::valid_from`] and [`VerifiedRRStream::expires`] before
+	/// acting on the returned update.
+	pub fn ds_update(&self, zone: &Name) -> Result<Option<DSUpdate>, ValidationError> {
+		let cdses = self.verified_rrs.iter()
+			.filter(|rr| rr.name() == zone)
+			.filter_map(|rr| if let RR::CDS(cds) = rr { Some(cds) } else { None });
+		let cdnskeys = self.verified_rrs.iter()
+			.filter(|rr| rr.name() == zone)
+			.filter_map(|rr| if let RR::CDnsKey(cdnskey) = rr { Some(cdnskey) } else { None });
+		let cds_count = cdses.clone().count();
+		let cdnskey_count = cdnskeys.clone().count();
+		if cds_count == 0 && cdnskey_count == 0 { return Ok(None); }
+
+		// A delete request must be the only record in its set, and if both a CDS and CDNSKEY set
+		// are present they must both request deletion.
+		let cds_delete = cdses.clone().any(|cds| cds.is_delete());
+		let cdnskey_delete = cdnskeys.clone().any(|cdnskey| cdnskey.is_delete());
+		if cds_delete || cdnskey_delete {
+			if cds_delete && cds_count != 1 { return Err(ValidationError::Invalid); }
+			if cdnskey_delete && cdnskey_count != 1 { return Err(ValidationError::Invalid); }
+			if cds_count != 0 && !cds_delete { return Err(ValidationError::Invalid); }
+			if cdnskey_count != 0 && !cdnskey_delete { return Err(ValidationError::Invalid); }
+			return Ok(Some(DSUpdate::Delete));
+		}
+
+		let mut dses = Vec::with_capacity(cmp::max(cds_count, cdnskey_count));
+		if cds_count != 0 {
+			// We can't check (and the parent shouldn't publish) digests of a type we don't
+			// support, or SHA-1 digests alongside stronger ones, so we drop them.
+			let trust_sha1 = cdses.clone().all(|cds| cds.digest_type != 2 && cds.digest_type != 4);
+			let cdses = cdses.filter(|cds| ds_digest_hasher(cds.digest_type, trust_sha1).is_some());
+			if cdses.clone().next().is_none() { return Err(ValidationError::UnsupportedAlgorithm); }
+			if cdnskey_count != 0 {
+				for cds in cdses.clone() {
+					if !cdnskeys.clone().any(|cdnskey| cds_matches_cdnskey(cds, cdnskey, trust_sha1)) {
+						return Err(ValidationError::Invalid);
+					}
+				}
+				for cdnskey in cdnskeys {
+					if !cdses.clone().any(|cds| cds_matches_cdnskey(cds, cdnskey, trust_sha1)) {
+						return Err(ValidationError::Invalid);
+					}
+				}
+			}
+			for cds in cdses {
+				dses.push(DS {
+					name: cds.name.clone(), key_tag: cds.key_tag, alg: cds.alg,
+					digest_type: cds.digest_type, digest: cds.digest.clone(),
+				});
+			}
+		} else {
+			for cdnskey in cdnskeys {
+				let digest = dnskey_digest(crypto::hash::Hasher::sha256(), &cdnskey.name,
+					cdnskey.flags, cdnskey.protocol, cdnskey.alg, &cdnskey.pubkey);
+				dses.push(DS {
+					name: cdnskey.name.clone(), key_tag: cdnskey.key_tag(), alg: cdnskey.alg,
+					digest_type: 2, digest: digest.as_ref().to_vec(),
+				});
+			}
+		}
+		dses.sort_unstable();
+		dses.dedup();
+		Ok(Some(DSUpdate::Replace(dses)))
+	}
+}
+
+/// Utilities to build signed zones for tests, chaining to a test root key which is only trusted
+/// if the [`test_utils::test_anchors`] are passed in explicitly.
+#[cfg(test)]
+pub(crate) mod test_utils {
+	use super::*;
+
+	use rand::RngCore;
+
+	/// An ECDSA P-256 private key used as the root key in tests, letting us build signed proofs
+	/// for cases we don't have real-world data for.
+	const TEST_ROOT_PRIVATE_KEY: [u8; 32] =
+		hex_lit::hex!("c635b1d574c01e2c3cc1ee48882095c0bafbfb30f67c1213d3f3ea718344bc8b");
+
+	/// An ECDSA P-256 key for a test zone, which can sign records.
+	pub(crate) struct TestZoneKey {
+		private_key: [u8; 32],
+		pub(crate) dnskey: DnsKey,
+	}
+
+	impl TestZoneKey {
+		fn from_private_key(zone: &str, private_key: [u8; 32]) -> Self {
+			let dnskey = DnsKey {
+				name: zone.try_into().unwrap(), flags: 257, protocol: 3, alg: 13,
+				pubkey: crypto::secp256r1::public_key(&private_key).unwrap(),
+			};
+			TestZoneKey { private_key, dnskey }
+		}
+
+		/// The test root key, trusted by the [`test_anchors`].
+		pub(crate) fn root() -> Self {
+			Self::from_private_key(".", TEST_ROOT_PRIVATE_KEY)
+		}
+
+		pub(crate) fn generate(zone: &str) -> Self {
+			let mut private_key = [0; 32];
+			rand::rngs::OsRng.fill_bytes(&mut private_key);
+			Self::from_private_key(zone, private_key)
+		}
+
+		/// Signs the given records, which must all have the same name and type.
+		pub(crate) fn sign(&self, records: &[RR]) -> RR {
+			let name = records[0].name();
+			let mut sig = RRSig {
+				name: name.clone(), ty: records[0].ty(), alg: 13, labels: name.labels(),
+				orig_ttl: 3600, expiration: 2000000000, inception: 1700000000,
+				key_tag: self.dnskey.key_tag(), key_name: self.dnskey.name.clone(), signature: Vec::new(),
+			};
+			let data = crate::ser::rrsig_signing_data(&sig, records).unwrap();
+			let mut hasher = crypto::hash::Hasher::sha256();
+			hasher.update(&data);
+			let mut nonce = [0; 32];
+			rand::rngs::OsRng.fill_bytes(&mut nonce);
+			sig.signature =
+				crypto::secp256r1::sign_ecdsa(&self.private_key, &nonce, hasher.finish().as_ref()).unwrap();
+			sig.into()
+		}
+
+		/// Gets this zone's [`DnsKey`] and its signature over it.
+		pub(crate) fn dnskey_rrs(&self) -> Vec<RR> {
+			let dnskey: RR = self.dnskey.clone().into();
+			let sig = self.sign(core::slice::from_ref(&dnskey));
+			vec![dnskey, sig]
+		}
+
+		/// Gets the [`DS`] for the `child` zone and this zone's signature over it.
+		pub(crate) fn delegate(&self, child: &TestZoneKey) -> Vec<RR> {
+			let ds: RR = ds_for(&child.dnskey).into();
+			let sig = self.sign(core::slice::from_ref(&ds));
+			vec![ds, sig]
+		}
+	}
+
+	fn ds_for(dnskey: &DnsKey) -> DS {
+		let digest = dnskey_digest(crypto::hash::Hasher::sha256(), &dnskey.name, dnskey.flags,
+			dnskey.protocol, dnskey.alg, &dnskey.pubkey);
+		DS {
+			name: dnskey.name.clone(), key_tag: dnskey.key_tag(), alg: dnskey.alg, digest_type: 2,
+			digest: digest.as_ref().to_vec(),
+		}
+	}
+
+	/// Gets the trust anchor for the [`TestZoneKey::root`] key, to be passed to
+	/// [`verify_rr_stream_with_anchors`] or [`verify_non_existence_with_anchors`].
+	pub(crate) fn test_anchors() -> Vec<DS> {
+		vec![ds_for(&TestZoneKey::root().dnskey)]
+	}
+
+	/// Builds a proof chain from the test root to the given zone (a child of the root), returning
+	/// the zone's key alongside the chain.
+	pub(crate) fn test_zone_chain(zone: &str) -> (TestZoneKey, Vec<RR>) {
+		let root = TestZoneKey::root();
+		let key = TestZoneKey::generate(zone);
+		let mut rrs = root.dnskey_rrs();
+		rrs.extend(root.delegate(&key));
+		rrs.extend(key.dnskey_rrs());
+		(key, rrs)
+	}
 }
 
 #[cfg(test)]
@@ -569,10 +1128,11 @@ mod tests {
 
 	use alloc::borrow::ToOwned;
 
-	use crate::ser::{parse_rr_stream, write_rr};
+	use crate::ser::*;
 
 	use hex_conservative::FromHex;
 	use rand::seq::SliceRandom;
+	use super::test_utils::*;
 
 	fn root_dnskey() -> (Vec<DnsKey>, Vec<RR>) {
 		let dnskeys = vec![DnsKey {
@@ -967,6 +1527,60 @@ mod tests {
 		(txt_resp, txt_rrsig, nsec, nsec_rrsig)
 	}
 
+	#[test]
+	fn check_test_zone_chain() {
+		// Check our test-only public key derivation against the root key's known public key
+		assert_eq!(TestZoneKey::root().dnskey.pubkey, Vec::from_hex("b560fb737d349790e0fd26b35552ddf35fa9b1936215b31d0b97de6c2ca0350b2c6aa4939122337da2e1eb33a643321896a4f1161b05a72872bcaa853cc2e9a4").unwrap());
+
+		let (key, mut rrs) = test_zone_chain("example.");
+		let txt: RR = Txt { name: "example.".try_into().unwrap(), data: b"test".to_vec() }.into();
+		rrs.push(txt.clone());
+		rrs.push(key.sign(core::slice::from_ref(&txt)));
+		assert_eq!(verify_rr_stream_with_anchors(&rrs, &test_anchors()).unwrap().verified_rrs, [&txt]);
+		// The test root is only trusted when its anchor is passed in explicitly
+		assert_eq!(verify_rr_stream(&rrs).unwrap_err(), ValidationError::Invalid);
+
+		// A root key we don't trust can't sign anything
+		let untrusted_root = TestZoneKey::generate(".");
+		let untrusted_key = TestZoneKey::generate("example.");
+		let mut rrs = untrusted_root.dnskey_rrs();
+		rrs.extend(untrusted_root.delegate(&untrusted_key));
+		rrs.extend(untrusted_key.dnskey_rrs());
+		rrs.push(txt.clone());
+		rrs.push(untrusted_key.sign(&[txt]));
+		assert_eq!(verify_rr_stream_with_anchors(&rrs, &test_anchors()).unwrap_err(), ValidationError::Invalid);
+	}
+
+	#[test]
+	fn check_sibling_suffix_zone_proof() {
+		// badexample. shares a string suffix with example. but is not a subdomain of it. Check that
+		// the label-wise Name ordering and hierarchy checks still validate each zone's records and
+		// don't let one zone sign for the other.
+		let (key, mut rrs) = test_zone_chain("example.");
+		let (bad_key, bad_chain) = test_zone_chain("badexample.");
+		rrs.extend(bad_chain.into_iter().skip(2)); // Skip the duplicate root DNSKEY and RRSig
+		let txt: RR = Txt { name: "example.".try_into().unwrap(), data: b"good".to_vec() }.into();
+		let bad_txt: RR = Txt { name: "badexample.".try_into().unwrap(), data: b"bad".to_vec() }.into();
+		rrs.push(txt.clone());
+		rrs.push(key.sign(core::slice::from_ref(&txt)));
+		rrs.push(bad_txt.clone());
+		rrs.push(bad_key.sign(core::slice::from_ref(&bad_txt)));
+		rrs.shuffle(&mut rand::rngs::OsRng);
+
+		let verified_rrs = verify_rr_stream_with_anchors(&rrs, &test_anchors()).unwrap();
+		let mut verified = verified_rrs.verified_rrs.clone();
+		verified.sort();
+		assert_eq!(verified, [&txt, &bad_txt]);
+		assert_eq!(verified_rrs.resolve_name(&"example.".try_into().unwrap()), [&txt]);
+		assert_eq!(verified_rrs.resolve_name(&"badexample.".try_into().unwrap()), [&bad_txt]);
+
+		// example.'s key cannot sign for badexample.
+		let (key, mut rrs) = test_zone_chain("example.");
+		rrs.push(bad_txt.clone());
+		rrs.push(key.sign(core::slice::from_ref(&bad_txt)));
+		assert_eq!(verify_rr_stream_with_anchors(&rrs, &test_anchors()).unwrap_err(), ValidationError::Invalid);
+	}
+
 	#[test]
 	fn check_txt_record_a() {
 		let dnskeys = mattcorallo_dnskey().0;
@@ -997,6 +1611,26 @@ mod tests {
 		assert_eq!(verified_rrs.max_cache_ttl, 3600); // The TXT record had the shortest TTL
 	}
 
+	#[cfg(feature = "serde")]
+	#[test]
+	fn check_verified_stream_serde_round_trip() {
+		let mut rrs = root_dnskey().1;
+		rrs.extend(com_dnskey().1);
+		rrs.extend(mattcorallo_dnskey().1);
+		let (txt, txt_rrsig) = mattcorallo_txt_record();
+		rrs.extend([RR::Txt(txt), RR::RRSig(txt_rrsig.clone())]);
+
+		let verified_rrs: OwnedVerifiedRRStream = verify_rr_stream(&rrs).unwrap().into();
+		let json = serde_json::to_string(&verified_rrs).unwrap();
+		assert_eq!(serde_json::from_str::<OwnedVerifiedRRStream>(&json).unwrap(), verified_rrs);
+
+		// RRSigs aren't included in the verified records, so check them separately
+		let json = serde_json::to_string(&RR::RRSig(txt_rrsig.clone())).unwrap();
+		assert_eq!(serde_json::from_str::<RR>(&json).unwrap(), RR::RRSig(txt_rrsig.clone()));
+		assert_eq!(serde_json::from_str::<RRSig>(&serde_json::to_string(&txt_rrsig).unwrap()).unwrap(),
+			txt_rrsig);
+	}
+
 	#[test]
 	fn check_txt_record_b() {
 		let dnskeys = bitcoin_ninja_dnskey().0;
@@ -1181,6 +1815,191 @@ mod tests {
 		check_proof("a.z", true).unwrap_err();
 	}
 
+	#[test]
+	fn check_nsec_non_existence_proof() {
+		let check_proof = |name: &str, ty: u16| -> Result<(), ()> {
+			let mut rr_stream = Vec::new();
+			for rr in root_dnskey().1 { write_rr(&rr, 1, &mut rr_stream); }
+			for rr in ninja_dnskey().1 { write_rr(&rr, 1, &mut rr_stream); }
+			for rr in bitcoin_ninja_dnskey().1 { write_rr(&rr, 1, &mut rr_stream); }
+			for rr in bitcoin_ninja_nsec_dnskey().1 { write_rr(&rr, 1, &mut rr_stream); }
+			let (_, _, nsec, nsec_rrsig) = bitcoin_ninja_nsec_wildcard_record("a");
+			for rr in [RR::NSec(nsec), RR::RRSig(nsec_rrsig)] { write_rr(&rr, 1, &mut rr_stream); }
+			let (_, _, nsec, nsec_rrsig) = bitcoin_ninja_nsec_post_override_wildcard_record("a");
+			for rr in [RR::NSec(nsec), RR::RRSig(nsec_rrsig)] { write_rr(&rr, 1, &mut rr_stream); }
+
+			let mut rrs = parse_rr_stream(&rr_stream).unwrap();
+			rrs.shuffle(&mut rand::rngs::OsRng);
+			let name: Name =
+				(name.to_owned() + "wildcard_test.nsec_tests.dnssec_proof_tests.bitcoin.ninja.").try_into().unwrap();
+			verify_non_existence(&rrs, &name, ty).map_err(|_| ())?;
+			Ok(())
+		};
+		// override and the wildcard exist, but only have TXT records
+		check_proof("override.", A::TYPE).unwrap();
+		check_proof("override.", Txt::TYPE).unwrap_err();
+		check_proof("*.", A::TYPE).unwrap();
+		check_proof("*.", Txt::TYPE).unwrap_err();
+		// zzz doesn't exist, but would be matched by the wildcard, which only has TXT records
+		check_proof("zzz.", A::TYPE).unwrap();
+		check_proof("zzz.", Txt::TYPE).unwrap_err();
+		// Names below override don't exist and aren't matched by any wildcard
+		check_proof("a.override.", Txt::TYPE).unwrap();
+		// We don't have a proof for names before the wildcard
+		check_proof("", A::TYPE).unwrap_err();
+		check_proof("a.a.", Txt::TYPE).unwrap_err();
+	}
+
+	#[test]
+	fn check_nsec3_non_existence() {
+		let zone: Name = "bitcoin.ninja.".try_into().unwrap();
+		let salt = Vec::from_hex("059855BD1077A2EB").unwrap();
+		let nsec3 = |hash: &str, next_hash: &str, types: &[u16]| NSec3 {
+			name: (hash.to_owned() + ".bitcoin.ninja.").try_into().unwrap(),
+			hash_algo: 1, flags: 0, hash_iterations: 0, salt: salt.clone(),
+			next_name_hash: crate::base32::decode(next_hash).unwrap(),
+			types: NSecTypeMask::from_types(types),
+		};
+		// wildcard_test.dnssec_proof_tests.bitcoin.ninja. hashes to DD3MT23L63OIHQPIMA5O2NULSVIGIJ3N,
+		// *.wildcard_test.dnssec_proof_tests.bitcoin.ninja. to UVCMNDD3TI1MV0KT1EEO9GAOH8LQPIN7 and
+		// cname.wildcard_test.dnssec_proof_tests.bitcoin.ninja. to CVL5S7S54RGTR1J4VB8FPF2K2Q7TC3AK.
+		let ce = nsec3("DD3MT23L63OIHQPIMA5O2NULSVIGIJ3N", "E0000000000000000000000000000000", &[A::TYPE, RRSig::TYPE]);
+		let next_closer = nsec3("CJQF7LFU6EV77K9M2O6IIH56KBFNSHIN", "DD3MT23L63OIHQPIMA5O2NULSVIGIJ3N", &[A::TYPE]);
+		let wildcard = nsec3("UVCMNDD3TI1MV0KT1EEO9GAOH8LQPIN7", "V0000000000000000000000000000000", &[Txt::TYPE, RRSig::TYPE]);
+		let wildcard_cover = nsec3("U0000000000000000000000000000000", "V0000000000000000000000000000000", &[A::TYPE]);
+
+		let name: Name = "wildcard_test.dnssec_proof_tests.bitcoin.ninja.".try_into().unwrap();
+		assert!(nsec3s_deny(&[&ce], &zone, &name, Txt::TYPE));
+		assert!(!nsec3s_deny(&[&ce], &zone, &name, A::TYPE));
+		assert!(!nsec3s_deny(&[&next_closer], &zone, &name, Txt::TYPE));
+
+		let name: Name = "cname.wildcard_test.dnssec_proof_tests.bitcoin.ninja.".try_into().unwrap();
+		assert!(nsec3s_deny(&[&ce, &next_closer, &wildcard], &zone, &name, A::TYPE));
+		assert!(!nsec3s_deny(&[&ce, &next_closer, &wildcard], &zone, &name, Txt::TYPE));
+		assert!(nsec3s_deny(&[&ce, &next_closer, &wildcard_cover], &zone, &name, Txt::TYPE));
+		// Each of the closest encloser, next closer and wildcard proofs is required
+		assert!(!nsec3s_deny(&[&next_closer, &wildcard_cover], &zone, &name, Txt::TYPE));
+		assert!(!nsec3s_deny(&[&ce, &wildcard_cover], &zone, &name, Txt::TYPE));
+		assert!(!nsec3s_deny(&[&ce, &next_closer], &zone, &name, Txt::TYPE));
+
+		// Opt-out NSEC3s can't be used to prove non-existence
+		let mut opt_out = next_closer.clone();
+		opt_out.flags = 1;
+		assert!(!nsec3s_deny(&[&ce, &opt_out, &wildcard_cover], &zone, &name, Txt::TYPE));
+
+		// Names below a delegation aren't part of the zone
+		let ce = nsec3("DD3MT23L63OIHQPIMA5O2NULSVIGIJ3N", "E0000000000000000000000000000000", &[NS::TYPE]);
+		assert!(!nsec3s_deny(&[&ce, &next_closer, &wildcard_cover], &zone, &name, Txt::TYPE));
+	}
+
+	#[test]
+	fn check_signed_nsec3_non_existence_proof() {
+		// A test zone containing `example.`, `a.example.` and `*.a.example.`, with a full, signed
+		// NSEC3 chain over them.
+		let (key, chain) = test_zone_chain("example.");
+		let salt = vec![0xab, 0xcd];
+		let names: [(&str, &[u16]); 3] = [
+			("example.", &[DnsKey::TYPE, RRSig::TYPE]),
+			("a.example.", &[Txt::TYPE, RRSig::TYPE]),
+			("*.a.example.", &[Txt::TYPE, RRSig::TYPE]),
+		];
+		let mut hashes: Vec<(Vec<u8>, &[u16])> = names.iter()
+			.map(|(name, types)| (nsec3_hash(name, 1, &salt).as_ref().to_vec(), *types)).collect();
+		hashes.sort_unstable();
+		let nsec3s: Vec<(Vec<u8>, Vec<RR>)> = hashes.iter().enumerate().map(|(idx, (hash, types))| {
+			let nsec3: RR = NSec3 {
+				name: (crate::base32::encode(hash) + ".example.").try_into().unwrap(),
+				hash_algo: 1, flags: 0, hash_iterations: 1, salt: salt.clone(),
+				next_name_hash: hashes[(idx + 1) % hashes.len()].0.clone(),
+				types: NSecTypeMask::from_types(types),
+			}.into();
+			let sig = key.sign(core::slice::from_ref(&nsec3));
+			(hash.clone(), vec![nsec3, sig])
+		}).collect();
+
+		// Builds a proof with all the NSEC3 records except the one matching or covering `omit`.
+		let check_proof = |name: &str, ty: u16, omit: Option<&str>| -> Result<(), ()> {
+			let omit_hash = omit.map(|omit| nsec3_hash(omit, 1, &salt).as_ref().to_vec());
+			let omit_idx = omit_hash.map(|omit_hash| {
+				nsec3s.iter().rposition(|(hash, _)| *hash <= omit_hash).unwrap_or(nsec3s.len() - 1)
+			});
+			let mut rrs = chain.clone();
+			for (idx, (_, nsec3_rrs)) in nsec3s.iter().enumerate() {
+				if Some(idx) != omit_idx { rrs.extend_from_slice(nsec3_rrs); }
+			}
+			rrs.shuffle(&mut rand::rngs::OsRng);
+			verify_non_existence_with_anchors(&rrs, &name.try_into().unwrap(), ty, &test_anchors())
+				.map_err(|_| ())?;
+			Ok(())
+		};
+
+		// a.example. exists, but only has TXT records
+		check_proof("a.example.", A::TYPE, None).unwrap();
+		check_proof("a.example.", Txt::TYPE, None).unwrap_err();
+		check_proof("a.example.", A::TYPE, Some("a.example.")).unwrap_err();
+
+		// b.example. doesn't exist: its closest encloser is example., b.example. is the covered next
+		// closer name and there is no *.example. wildcard.
+		check_proof("b.example.", Txt::TYPE, None).unwrap();
+		check_proof("c.b.example.", Txt::TYPE, None).unwrap();
+		check_proof("b.example.", Txt::TYPE, Some("example.")).unwrap_err();
+		check_proof("b.example.", Txt::TYPE, Some("b.example.")).unwrap_err();
+		check_proof("b.example.", Txt::TYPE, Some("*.example.")).unwrap_err();
+
+		// b.a.example. doesn't exist, but is matched by the *.a.example. wildcard, which only has
+		// TXT records.
+		check_proof("b.a.example.", A::TYPE, None).unwrap();
+		check_proof("b.a.example.", Txt::TYPE, None).unwrap_err();
+		check_proof("b.a.example.", A::TYPE, Some("a.example.")).unwrap_err();
+		check_proof("b.a.example.", A::TYPE, Some("b.a.example.")).unwrap_err();
+		check_proof("b.a.example.", A::TYPE, Some("*.a.example.")).unwrap_err();
+
+		// Names outside the zone can't be denied by it
+		check_proof("b.example2.", Txt::TYPE, None).unwrap_err();
+	}
+
+	#[test]
+	fn check_incremental_validation() {
+		let (txt, txt_rrsig) = bitcoin_ninja_txt_record();
+		let mut validator = IncrementalValidator::new();
+		// Nothing can be checked until we have the signer's keys
+		validator.add_records(vec![txt.clone().into(), txt_rrsig.clone().into()]).unwrap();
+		assert!(validator.checked_rrsigs.is_empty());
+		validator.add_records(bitcoin_ninja_dnskey().1).unwrap();
+		assert_eq!(validator.checked_rrsigs.len(), 1);
+		// Once ninja.'s keys arrive, bitcoin.ninja.'s DS records, and thus its keys, can be checked
+		validator.add_records(ninja_dnskey().1).unwrap();
+		assert_eq!(validator.checked_zones, ["bitcoin.ninja.".try_into().unwrap()]);
+		validator.add_records(root_dnskey().1).unwrap();
+		assert_eq!(validator.checked_zones.len(), 3);
+
+		// A bad signature is caught as soon as the signer's keys arrive
+		let mut bad_txt = txt.clone();
+		bad_txt.data = b"bogus".to_vec();
+		let mut validator = IncrementalValidator::new();
+		validator.add_records(vec![bad_txt.into(), txt_rrsig.clone().into()]).unwrap();
+		assert_eq!(validator.add_records(bitcoin_ninja_dnskey().1),
+			Err(("bitcoin.ninja.".try_into().unwrap(), ValidationError::Invalid)));
+
+		// As are DS records which don't match the zone's keys
+		let mut ninja_rrs = ninja_dnskey().1;
+		ninja_rrs.retain(|rr| !matches!(rr, RR::DnsKey(dnskey) if dnskey.flags == 257));
+		let mut validator = IncrementalValidator::new();
+		validator.add_records(root_dnskey().1).unwrap();
+		assert_eq!(validator.add_records(ninja_rrs),
+			Err(("ninja.".try_into().unwrap(), ValidationError::Invalid)));
+
+		// Or DS records which don't match their signature
+		let mut ninja_rrs = ninja_dnskey().1;
+		for rr in ninja_rrs.iter_mut() {
+			if let RR::DS(ds) = rr { ds.digest[0] ^= 1; }
+		}
+		let mut validator = IncrementalValidator::new();
+		validator.add_records(ninja_rrs).unwrap();
+		assert_eq!(validator.add_records(root_dnskey().1),
+			Err((".".try_into().unwrap(), ValidationError::Invalid)));
+	}
+
 	#[test]
 	fn check_txt_sort_order() {
 		let mut rr_stream = Vec::new();
@@ -1202,6 +2021,168 @@ mod tests {
 		assert_eq!(verified_txts, txts);
 	}
 
+	#[test]
+	fn check_ds_update() {
+		let com_ksk = com_dnskey().0.pop().unwrap();
+		assert_eq!(com_ksk.key_tag(), 19718);
+		let cdnskey = CDnsKey {
+			name: com_ksk.name.clone(), flags: com_ksk.flags, protocol: com_ksk.protocol,
+			alg: com_ksk.alg, pubkey: com_ksk.pubkey.clone(),
+		};
+		let cds = CDS {
+			name: "com.".try_into().unwrap(), key_tag: 19718, alg: 13, digest_type: 2,
+			digest: Vec::from_hex("8ACBB0CD28F41250A80A491389424D341522D946B0DA0C0291F2D3D771D7805A").unwrap(),
+		};
+		let expected_ds = DS {
+			name: cds.name.clone(), key_tag: cds.key_tag, alg: cds.alg,
+			digest_type: cds.digest_type, digest: cds.digest.clone(),
+		};
+		let cds_delete = CDS {
+			name: "com.".try_into().unwrap(), key_tag: 0, alg: 0, digest_type: 0, digest: vec![0],
+		};
+		let cdnskey_delete = CDnsKey {
+			name: "com.".try_into().unwrap(), flags: 0, protocol: 3, alg: 0, pubkey: vec![0],
+		};
+		assert!(cds_delete.is_delete());
+		assert!(cdnskey_delete.is_delete());
+
+		let mut rr_stream = Vec::new();
+		for rr in [RR::CDS(cds.clone()), RR::CDnsKey(cdnskey.clone())] { write_rr(&rr, 1, &mut rr_stream); }
+		assert_eq!(parse_rr_stream(&rr_stream).unwrap(), [RR::CDS(cds.clone()), RR::CDnsKey(cdnskey.clone())]);
+
+		let check = |rrs: &[RR]| {
+			let verified = VerifiedRRStream {
+				verified_rrs: rrs.iter().collect(), valid_from: 0, expires: u64::MAX, max_cache_ttl: 0,
+			};
+			verified.ds_update(&"com.".try_into().unwrap())
+		};
+		assert_eq!(check(&[]), Ok(None));
+		assert_eq!(check(&[cdnskey.clone().into()]), Ok(Some(DSUpdate::Replace(vec![expected_ds.clone()]))));
+		assert_eq!(check(&[cds.clone().into()]), Ok(Some(DSUpdate::Replace(vec![expected_ds.clone()]))));
+		assert_eq!(check(&[cds.clone().into(), cdnskey.clone().into()]),
+			Ok(Some(DSUpdate::Replace(vec![expected_ds]))));
+
+		let mut bad_cds = cds.clone();
+		bad_cds.digest[0] ^= 1;
+		assert_eq!(check(&[bad_cds.into(), cdnskey.clone().into()]), Err(ValidationError::Invalid));
+
+		assert_eq!(check(&[cds_delete.clone().into()]), Ok(Some(DSUpdate::Delete)));
+		assert_eq!(check(&[cdnskey_delete.clone().into()]), Ok(Some(DSUpdate::Delete)));
+		assert_eq!(check(&[cds_delete.clone().into(), cdnskey_delete.clone().into()]),
+			Ok(Some(DSUpdate::Delete)));
+		assert_eq!(check(&[cds_delete.into(), cds.into()]), Err(ValidationError::Invalid));
+		assert_eq!(check(&[cdnskey_delete.into(), cdnskey.into()]), Err(ValidationError::Invalid));
+	}
+
+	#[test]
+	fn check_signed_ds_update() {
+		let zone: Name = "example.".try_into().unwrap();
+		let new_key = TestZoneKey::generate("example.").dnskey;
+		let cdnskey = CDnsKey {
+			name: zone.clone(), flags: new_key.flags, protocol: new_key.protocol, alg: new_key.alg,
+			pubkey: new_key.pubkey.clone(),
+		};
+		let cds = |digest_type: u8, digest: &[u8]| -> RR {
+			CDS {
+				name: zone.clone(), key_tag: new_key.key_tag(), alg: new_key.alg, digest_type,
+				digest: digest.to_vec(),
+			}.into()
+		};
+		let key_digest = |hasher| dnskey_digest(hasher, &zone, new_key.flags, new_key.protocol,
+			new_key.alg, &new_key.pubkey).as_ref().to_vec();
+		let sha1_digest = key_digest(crypto::hash::Hasher::sha1());
+		let sha256_digest = key_digest(crypto::hash::Hasher::sha256());
+		let mut bogus_digest = sha1_digest.clone();
+		bogus_digest[0] ^= 1;
+		let ds = |digest_type: u8, digest: &[u8]| DS {
+			name: zone.clone(), key_tag: new_key.key_tag(), alg: new_key.alg, digest_type,
+			digest: digest.to_vec(),
+		};
+
+		let check = |cdses: &[RR], with_cdnskey: bool| {
+			let (key, mut rrs) = test_zone_chain("example.");
+			if !cdses.is_empty() {
+				rrs.extend_from_slice(cdses);
+				rrs.push(key.sign(cdses));
+			}
+			if with_cdnskey {
+				rrs.push(cdnskey.clone().into());
+				rrs.push(key.sign(&[cdnskey.clone().into()]));
+			}
+			let verified_rrs = verify_rr_stream_with_anchors(&rrs, &test_anchors()).unwrap();
+			assert_eq!(verified_rrs.verified_rrs.len(), cdses.len() + with_cdnskey as usize);
+			verified_rrs.ds_update(&zone)
+		};
+
+		let expected = Ok(Some(DSUpdate::Replace(vec![ds(2, &sha256_digest)])));
+		assert_eq!(check(&[], true), expected);
+		assert_eq!(check(&[cds(2, &sha256_digest)], false), expected);
+		assert_eq!(check(&[cds(2, &sha256_digest)], true), expected);
+
+		// SHA-1 digests are only used if no stronger digests are present, matching DS validation
+		assert_eq!(check(&[cds(1, &sha1_digest)], true),
+			Ok(Some(DSUpdate::Replace(vec![ds(1, &sha1_digest)]))));
+		assert_eq!(check(&[cds(1, &bogus_digest)], true), Err(ValidationError::Invalid));
+		assert_eq!(check(&[cds(1, &bogus_digest), cds(2, &sha256_digest)], true), expected);
+
+		// Digest types we don't support can't be checked, so are never used
+		assert_eq!(check(&[cds(3, &sha256_digest), cds(2, &sha256_digest)], true), expected);
+		assert_eq!(check(&[cds(3, &sha256_digest), cds(2, &sha256_digest)], false), expected);
+		assert_eq!(check(&[cds(3, &sha256_digest)], true), Err(ValidationError::UnsupportedAlgorithm));
+		assert_eq!(check(&[cds(3, &sha256_digest)], false), Err(ValidationError::UnsupportedAlgorithm));
+	}
+
+	#[test]
+	fn rfc8976_simple_zone_test() {
+		// Test verify_zonemd using the simple EXAMPLE zone from RFC 8976 appendix A.1
+		let zone: Vec<(RR, u32)> = vec![
+			(SOA {
+				name: "example.".try_into().unwrap(), mname: "ns1.example.".try_into().unwrap(),
+				rname: "admin.example.".try_into().unwrap(), serial: 2018031900, refresh: 1800,
+				retry: 900, expire: 604800, minimum: 86400,
+			}.into(), 86400),
+			(NS { name: "example.".try_into().unwrap(), name_server: "ns1.example.".try_into().unwrap() }.into(), 86400),
+			(NS { name: "example.".try_into().unwrap(), name_server: "ns2.example.".try_into().unwrap() }.into(), 86400),
+			(A { name: "ns1.example.".try_into().unwrap(), address: [203, 0, 113, 63] }.into(), 3600),
+			(AAAA {
+				name: "ns2.example.".try_into().unwrap(),
+				address: [0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x63],
+			}.into(), 3600),
+		];
+		let zonemd = ZoneMD {
+			name: "example.".try_into().unwrap(), serial: 2018031900, scheme: 1, hash_alg: 1,
+			digest: Vec::from_hex("c68090d90a7aed716bc459f9340e3d7c1370d4d24b7e2fc3a1ddc0b9a87153b9a9713b3c9ae5cc27777f98b8e730044c").unwrap(),
+		};
+
+		let mut rr_stream = Vec::new();
+		for (rr, ttl) in zone.iter() { write_rr(rr, *ttl, &mut rr_stream); }
+		write_rr(&zonemd, 86400, &mut rr_stream);
+		let mut parsed_zone = crate::ser::parse_rr_stream_with_ttls(&rr_stream).unwrap();
+		assert_eq!(parsed_zone.last(), Some(&(zonemd.clone().into(), 86400)));
+		parsed_zone.shuffle(&mut rand::rngs::OsRng);
+		verify_zonemd(&parsed_zone, &zonemd).unwrap();
+
+		// Out-of-zone and duplicate records are ignored
+		parsed_zone.push(parsed_zone[0].clone());
+		parsed_zone.push((A { name: "badexample.".try_into().unwrap(), address: [1, 2, 3, 4] }.into(), 1));
+		verify_zonemd(&parsed_zone, &zonemd).unwrap();
+
+		// But changes to in-zone records are not
+		let mut changed_zone = parsed_zone.clone();
+		changed_zone.push((A { name: "ns3.example.".try_into().unwrap(), address: [1, 2, 3, 4] }.into(), 1));
+		assert_eq!(verify_zonemd(&changed_zone, &zonemd), Err(ValidationError::Invalid));
+		let mut changed_zone = parsed_zone.clone();
+		for (_, ttl) in changed_zone.iter_mut() { *ttl += 1; }
+		assert_eq!(verify_zonemd(&changed_zone, &zonemd), Err(ValidationError::Invalid));
+
+		let mut bad_serial = zonemd.clone();
+		bad_serial.serial += 1;
+		assert_eq!(verify_zonemd(&parsed_zone, &bad_serial), Err(ValidationError::Invalid));
+		let mut bad_alg = zonemd.clone();
+		bad_alg.hash_alg = 3;
+		assert_eq!(verify_zonemd(&parsed_zone, &bad_alg), Err(ValidationError::UnsupportedAlgorithm));
+	}
+
 	#[test]
 	fn rfc9102_parse_test() {
 		// Note that this is the `AuthenticationChain` field only, and ignores the
@@ -1219,4 +2200,131 @@ let rfc9102_test_vector = Vec::from_hex("045f343433045f74637003777777076578616d7
 			assert_eq!(tlsa.data, Vec::from_hex("8bd1da95272f7fa4ffb24137fc0ed03aae67e5c4d8b3c50734e1050a7920b922").unwrap());
 		} else { panic!(); }
 	}
+
+	#[test]
+	fn dnssec_chain_extension_test() {
+		let mut chain = Vec::new();
+		for rr in root_dnskey().1 { write_rr(&rr, 1, &mut chain); }
+		for rr in com_dnskey().1 { write_rr(&rr, 1, &mut chain); }
+		for rr in mattcorallo_dnskey().1 { write_rr(&rr, 1, &mut chain); }
+		let (txt, txt_rrsig) = mattcorallo_txt_record();
+		for rr in [RR::Txt(txt.clone()), RR::RRSig(txt_rrsig)] { write_rr(&rr, 1, &mut chain); }
+		let rrs = parse_rr_stream(&chain).unwrap();
+
+		let mut extension = Vec::new();
+		write_dnssec_chain_extension(0x1234, &chain, &mut extension);
+		assert_eq!(&extension[..2], &[0x12, 0x34]);
+		assert_eq!(&extension[2..], &chain[..]);
+		let (lifetime, parsed_rrs) = parse_dnssec_chain_extension(&extension).unwrap();
+		assert_eq!(lifetime, 0x1234);
+		assert_eq!(parsed_rrs, rrs);
+		assert_eq!(verify_rr_stream(&parsed_rrs).unwrap().verified_rrs, [&RR::Txt(txt)]);
+
+		// The full TLS extension is prefixed with its type and length
+		let mut tls_extension = Vec::new();
+		write_dnssec_chain_tls_extension(0x1234, &chain, &mut tls_extension).unwrap();
+		assert_eq!(&tls_extension[..4], &[0, 59, ((chain.len() + 2) >> 8) as u8, (chain.len() + 2) as u8]);
+		assert_eq!(&tls_extension[4..], &extension[..]);
+		assert!(write_dnssec_chain_tls_extension(0, &[0; 65534], &mut Vec::new()).is_err());
+
+		// A denial extension requires NSEC or NSEC3 records and no TLSA records
+		assert!(write_dnssec_chain_denial_tls_extension(0, &chain, &mut Vec::new()).is_err());
+		let tlsa: RR = "_443._tcp.example.com. IN TLSA 3 1 1 8BD1DA95272F7FA4FFB24137FC0ED03AAE67E5C4D8B3C50734E1050A7920B922".parse().unwrap();
+		let nsec: RR = "_443._tcp.example.com. IN NSEC a._443._tcp.example.com. RRSIG NSEC".parse().unwrap();
+		let mut denial_chain = Vec::new();
+		write_rr(&nsec, 3600, &mut denial_chain);
+		let mut denial_extension = Vec::new();
+		write_dnssec_chain_denial_tls_extension(0, &denial_chain, &mut denial_extension).unwrap();
+		assert_eq!(parse_dnssec_chain_extension(&denial_extension[4..]).unwrap(), (0, vec![nsec]));
+		write_rr(&tlsa, 3600, &mut denial_chain);
+		assert!(write_dnssec_chain_denial_tls_extension(0, &denial_chain, &mut Vec::new()).is_err());
+
+		// An extension too short to hold the ExtSupportLifetime, or with an empty
+		// AuthenticationChain, is rejected when both reading and writing
+		assert!(parse_dnssec_chain_extension(&[0]).is_err());
+		assert!(parse_dnssec_chain_extension(&[0, 1]).is_err());
+		assert!(write_dnssec_chain_tls_extension(1, &[], &mut Vec::new()).is_err());
+	}
+
+	#[test]
+	fn strict_proof_parse_test() {
+		let mut chain = Vec::new();
+		for rr in root_dnskey().1 { write_rr(&rr, 1, &mut chain); }
+		for rr in com_dnskey().1 { write_rr(&rr, 1, &mut chain); }
+		for rr in mattcorallo_dnskey().1 { write_rr(&rr, 1, &mut chain); }
+		let (txt, txt_rrsig) = mattcorallo_txt_record();
+		write_rr(&RR::RRSig(txt_rrsig), 1, &mut chain);
+		let rrs = parse_rr_stream(&chain).unwrap();
+		assert_eq!(parse_rr_stream_strict(&chain).unwrap(), rrs);
+
+		let mut txt_bytes = Vec::new();
+		write_rr(&RR::Txt(txt.clone()), 1, &mut txt_bytes);
+		let mut canonical = chain.clone();
+		canonical.extend_from_slice(&txt_bytes);
+		let rrs = parse_rr_stream_strict(&canonical).unwrap();
+		assert_eq!(verify_rr_stream(&rrs).unwrap().verified_rrs, [&RR::Txt(txt.clone())]);
+
+		// Each of these proofs has the same records and validates when parsed leniently, but isn't
+		// canonically encoded and must be rejected by the strict parser.
+		let check_non_canonical = |proof: &[u8]| {
+			let rrs = parse_rr_stream(proof).unwrap();
+			assert_eq!(verify_rr_stream(&rrs).unwrap().verified_rrs, [&RR::Txt(txt.clone())]);
+			assert!(parse_rr_stream_strict(proof).is_err());
+		};
+
+		// An uppercase owner name
+		let mut upper = chain.clone();
+		let mut upper_txt = txt_bytes.clone();
+		upper_txt[1] = b'M';
+		upper.extend_from_slice(&upper_txt);
+		check_non_canonical(&upper);
+
+		// TXT data split into shorter character-strings than required
+		let mut rechunked = chain.clone();
+		write_name(&mut rechunked, &txt.name);
+		rechunked.extend_from_slice(&[0, 16, 0, 1, 0, 0, 0, 1]);
+		let chunks = txt.data.chunks(100);
+		let rdata_len = txt.data.len() + chunks.len();
+		rechunked.extend_from_slice(&(rdata_len as u16).to_be_bytes());
+		for chunk in chunks {
+			rechunked.push(chunk.len() as u8);
+			rechunked.extend_from_slice(chunk);
+		}
+		check_non_canonical(&rechunked);
+
+		// The remaining cases break signatures, so we only check that they parse leniently
+		let check_lenient_only = |proof: &[u8]| {
+			assert!(parse_rr_stream(proof).is_ok());
+			assert!(parse_rr_stream_strict(proof).is_err());
+		};
+
+		// A duplicated record, with a different TTL
+		let mut dup = canonical.clone();
+		write_rr(&RR::Txt(txt.clone()), 42, &mut dup);
+		check_lenient_only(&dup);
+
+		// Trailing bytes after a name in RDATA
+		check_lenient_only(b"\x07example\x03com\x00\x00\x05\x00\x01\x00\x00\x0e\x10\x00\x02\x00\x00");
+
+		// A TLSA SHA-256 hash with a trailing byte
+		let tlsa: RR = "_443._tcp.example.com. IN TLSA 3 1 1 8BD1DA95272F7FA4FFB24137FC0ED03AAE67E5C4D8B3C50734E1050A7920B922".parse().unwrap();
+		let mut tlsa_bytes = Vec::new();
+		write_rr(&tlsa, 3600, &mut tlsa_bytes);
+		let mut long_tlsa = tlsa.clone();
+		assert_eq!(parse_rr_stream_strict(&tlsa_bytes).unwrap(), [tlsa]);
+		if let RR::TLSA(tlsa) = &mut long_tlsa { tlsa.data.push(0); }
+		let mut long_tlsa_bytes = Vec::new();
+		write_rr(&long_tlsa, 3600, &mut long_tlsa_bytes);
+		check_lenient_only(&long_tlsa_bytes);
+
+		// An NSEC type bitmap with an empty window
+		let nsec: RR = "a.example. IN NSEC d.example. A NS SOA RRSIG NSEC DNSKEY".parse().unwrap();
+		let mut nsec_bytes = Vec::new();
+		write_rr(&nsec, 3600, &mut nsec_bytes);
+		assert_eq!(parse_rr_stream_strict(&nsec_bytes).unwrap(), [nsec]);
+		// The RDATA length follows the 11-byte owner name, type, class and TTL.
+		nsec_bytes[20] += 2;
+		nsec_bytes.extend_from_slice(&[1, 0]);
+		check_lenient_only(&nsec_bytes);
+	}
 }
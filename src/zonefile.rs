@@ -0,0 +1,751 @@
+//! Parsing and formatting of resource records in the text "presentation format" described in
+//! RFC 1035 section 5, as used in zone files and by tools such as `dig`.
+//!
+//! Every record type in [`crate::rr`] implements [`core::fmt::Display`], writing the record as it
+//! would appear in a zone file (without a TTL), and [`RR`] implements [`FromStr`], parsing a
+//! single record with an absolute owner name (and optional TTL). Full zone files, including
+//! `$ORIGIN` and `$TTL` directives, relative names and multi-line records, can be parsed with
+//! [`parse_zone`].
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use core::fmt;
+use core::fmt::Write;
+use core::str::FromStr;
+
+use crate::base32;
+use crate::base64;
+use crate::hex;
+use crate::rr::*;
+
+const TYPE_MNEMONICS: [(u16, &str); 46] = [
+	(1, "A"), (2, "NS"), (5, "CNAME"), (6, "SOA"), (12, "PTR"), (13, "HINFO"), (15, "MX"),
+	(16, "TXT"), (17, "RP"), (18, "AFSDB"), (24, "SIG"), (25, "KEY"), (28, "AAAA"), (29, "LOC"),
+	(33, "SRV"), (35, "NAPTR"), (36, "KX"), (37, "CERT"), (39, "DNAME"), (41, "OPT"), (42, "APL"),
+	(43, "DS"), (44, "SSHFP"), (45, "IPSECKEY"), (46, "RRSIG"), (47, "NSEC"), (48, "DNSKEY"),
+	(49, "DHCID"), (50, "NSEC3"), (51, "NSEC3PARAM"), (52, "TLSA"), (53, "SMIMEA"), (55, "HIP"),
+	(59, "CDS"), (60, "CDNSKEY"), (61, "OPENPGPKEY"), (62, "CSYNC"), (63, "ZONEMD"), (64, "SVCB"),
+	(65, "HTTPS"), (99, "SPF"), (108, "EUI48"), (109, "EUI64"), (256, "URI"), (257, "CAA"),
+	(32769, "DLV"),
+];
+
+fn parse_decimal<T: FromStr>(s: &str) -> Result<T, ()> {
+	if s.is_empty() || !s.bytes().all(|c| c.is_ascii_digit()) { return Err(()); }
+	s.parse().map_err(|_| ())
+}
+
+fn parse_ty(s: &str) -> Result<u16, ()> {
+	if let Some((ty, _)) = TYPE_MNEMONICS.iter().find(|(_, name)| name.eq_ignore_ascii_case(s)) {
+		return Ok(*ty);
+	}
+	if s.len() > 4 && s.as_bytes()[..4].eq_ignore_ascii_case(b"TYPE") {
+		return parse_decimal(&s[4..]);
+	}
+	Err(())
+}
+
+/// Parses a TTL, either as a plain number of seconds or using BIND-style unit suffixes (e.g.
+/// `1h30m`).
+fn parse_ttl(s: &str) -> Result<u32, ()> {
+	if let Ok(ttl) = parse_decimal(s) { return Ok(ttl); }
+	let mut total: u32 = 0;
+	let mut cur: u32 = 0;
+	let mut have_digits = false;
+	for c in s.bytes() {
+		if c.is_ascii_digit() {
+			cur = cur.checked_mul(10).and_then(|v| v.checked_add((c - b'0') as u32)).ok_or(())?;
+			have_digits = true;
+		} else {
+			if !have_digits { return Err(()); }
+			let mul = match c.to_ascii_lowercase() {
+				b's' => 1, b'm' => 60, b'h' => 60 * 60, b'd' => 60 * 60 * 24, b'w' => 60 * 60 * 24 * 7,
+				_ => return Err(()),
+			};
+			total = cur.checked_mul(mul).and_then(|v| total.checked_add(v)).ok_or(())?;
+			cur = 0;
+			have_digits = false;
+		}
+	}
+	if have_digits { return Err(()); }
+	Ok(total)
+}
+
+/// Converts a number of days since 1970-01-01 to a (year, month, day) tuple.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+	// See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+	let z = days + 719468;
+	let era = if z >= 0 { z } else { z - 146096 } / 146097;
+	let doe = z - era * 146097;
+	let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let d = doy - (153 * mp + 2) / 5 + 1;
+	let m = if mp < 10 { mp + 3 } else { mp - 9 };
+	(yoe + era * 400 + if m <= 2 { 1 } else { 0 }, m, d)
+}
+
+/// Converts a (year, month, day) tuple to a number of days since 1970-01-01.
+fn days_from_civil(mut y: i64, m: i64, d: i64) -> i64 {
+	// See http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+	if m <= 2 { y -= 1; }
+	let era = if y >= 0 { y } else { y - 399 } / 400;
+	let yoe = y - era * 400;
+	let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d - 1;
+	let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+	era * 146097 + doe - 719468
+}
+
+fn parse_ipv4(s: &str) -> Result<[u8; 4], ()> {
+	let mut res = [0; 4];
+	let mut parts = s.split('.');
+	for byte in res.iter_mut() {
+		let part = parts.next().ok_or(())?;
+		if part.len() > 3 { return Err(()); }
+		*byte = parse_decimal(part)?;
+	}
+	if parts.next().is_some() { return Err(()); }
+	Ok(res)
+}
+
+fn parse_ipv6_groups(s: &str, groups: &mut Vec<u16>) -> Result<(), ()> {
+	if s.is_empty() { return Ok(()); }
+	let mut parts = s.split(':').peekable();
+	while let Some(part) = parts.next() {
+		if parts.peek().is_none() && part.contains('.') {
+			let v4 = parse_ipv4(part)?;
+			groups.push(u16::from_be_bytes([v4[0], v4[1]]));
+			groups.push(u16::from_be_bytes([v4[2], v4[3]]));
+		} else {
+			if part.is_empty() || part.len() > 4 { return Err(()); }
+			groups.push(u16::from_str_radix(part, 16).map_err(|_| ())?);
+		}
+	}
+	Ok(())
+}
+
+fn parse_ipv6(s: &str) -> Result<[u8; 16], ()> {
+	let mut head = Vec::with_capacity(8);
+	let mut tail = Vec::with_capacity(8);
+	if let Some((h, t)) = s.split_once("::") {
+		if t.contains("::") { return Err(()); }
+		if h.contains('.') { return Err(()); }
+		parse_ipv6_groups(h, &mut head)?;
+		parse_ipv6_groups(t, &mut tail)?;
+		if head.len() + tail.len() > 7 { return Err(()); }
+	} else {
+		parse_ipv6_groups(s, &mut head)?;
+		if head.len() != 8 { return Err(()); }
+	}
+	let mut res = [0; 16];
+	for (idx, group) in head.iter().enumerate() {
+		res[idx * 2..idx * 2 + 2].copy_from_slice(&group.to_be_bytes());
+	}
+	for (idx, group) in tail.iter().rev().enumerate() {
+		res[14 - idx * 2..16 - idx * 2].copy_from_slice(&group.to_be_bytes());
+	}
+	Ok(res)
+}
+
+pub(crate) fn write_ipv4<W: Write>(out: &mut W, addr: &[u8; 4]) -> fmt::Result {
+	write!(out, "{}.{}.{}.{}", addr[0], addr[1], addr[2], addr[3])
+}
+
+pub(crate) fn write_ipv6<W: Write>(out: &mut W, addr: &[u8; 16]) -> fmt::Result {
+	let mut groups = [0u16; 8];
+	for (idx, group) in groups.iter_mut().enumerate() {
+		*group = u16::from_be_bytes([addr[idx * 2], addr[idx * 2 + 1]]);
+	}
+	// Per RFC 5952, compress the longest (first, if tied) run of at least two zero groups.
+	let (mut best_start, mut best_len) = (0, 0);
+	let mut idx = 0;
+	while idx < 8 {
+		let run = groups[idx..].iter().take_while(|g| **g == 0).count();
+		if run > best_len { best_start = idx; best_len = run; }
+		idx += cmp_max_1(run);
+	}
+	if best_len < 2 { best_len = 0; best_start = 8; }
+	for (idx, group) in groups.iter().enumerate() {
+		if idx == best_start {
+			out.write_str("::")?;
+		} else if idx > best_start && idx < best_start + best_len {
+			continue;
+		} else {
+			if idx != 0 && idx != best_start + best_len { out.write_char(':')?; }
+			write!(out, "{:x}", group)?;
+		}
+	}
+	Ok(())
+}
+fn cmp_max_1(v: usize) -> usize { if v == 0 { 1 } else { v } }
+
+pub(crate) fn write_hex<W: Write>(out: &mut W, data: &[u8]) -> fmt::Result {
+	for c in data.iter() {
+		write!(out, "{:02X}", c)?;
+	}
+	Ok(())
+}
+
+pub(crate) fn write_base64<W: Write>(out: &mut W, data: &[u8]) -> fmt::Result {
+	out.write_str(&base64::encode(data))
+}
+
+pub(crate) fn write_base32<W: Write>(out: &mut W, data: &[u8]) -> fmt::Result {
+	out.write_str(&base32::encode(data))
+}
+
+/// Writes a UNIX timestamp in the `YYYYMMDDHHmmSS` form used for [`RRSig`] times.
+pub(crate) fn write_time<W: Write>(out: &mut W, time: u32) -> fmt::Result {
+	let time = time as i64;
+	let (y, m, d) = civil_from_days(time / 86400);
+	let secs = time % 86400;
+	write!(out, "{:04}{:02}{:02}{:02}{:02}{:02}", y, m, d, secs / 3600, (secs / 60) % 60, secs % 60)
+}
+
+pub(crate) fn write_ty<W: Write>(out: &mut W, ty: u16) -> fmt::Result {
+	match TYPE_MNEMONICS.iter().find(|(t, _)| *t == ty) {
+		Some((_, name)) => out.write_str(name),
+		None => write!(out, "TYPE{}", ty),
+	}
+}
+
+/// Writes a name, escaping any characters which have special meaning in the presentation format
+/// but not in the text form of a [`Name`].
+pub(crate) fn write_name<W: Write>(out: &mut W, name: &Name) -> fmt::Result {
+	for c in name.chars() {
+		if c == ';' || c == '(' || c == ')' || c == '$' || c == '@' {
+			out.write_char('\\')?;
+		}
+		out.write_char(c)?;
+	}
+	Ok(())
+}
+
+/// Writes arbitrary bytes as a series of quoted character-strings, each at most 255 bytes long.
+pub(crate) fn write_char_strings<W: Write>(out: &mut W, data: &[u8]) -> fmt::Result {
+	if data.is_empty() { return out.write_str("\"\""); }
+	for (idx, chunk) in data.chunks(255).enumerate() {
+		if idx != 0 { out.write_char(' ')?; }
+		out.write_char('"')?;
+		for c in chunk {
+			if *c == b'"' || *c == b'\\' {
+				write!(out, "\\{}", *c as char)?;
+			} else if *c == b' ' || c.is_ascii_graphic() {
+				out.write_char(*c as char)?;
+			} else {
+				write!(out, "\\{:03}", c)?;
+			}
+		}
+		out.write_char('"')?;
+	}
+	Ok(())
+}
+
+/// Unescapes `\X` and `\DDD` sequences in the given (unquoted) text.
+fn unescape(s: &str) -> Result<Vec<u8>, ()> {
+	let mut res = Vec::with_capacity(s.len());
+	let mut bytes = s.bytes();
+	while let Some(c) = bytes.next() {
+		if c != b'\\' { res.push(c); continue; }
+		let c = bytes.next().ok_or(())?;
+		if c.is_ascii_digit() {
+			let d2 = bytes.next().ok_or(())?;
+			let d3 = bytes.next().ok_or(())?;
+			if !d2.is_ascii_digit() || !d3.is_ascii_digit() { return Err(()); }
+			let val = (c - b'0') as u16 * 100 + (d2 - b'0') as u16 * 10 + (d3 - b'0') as u16;
+			res.push(val.try_into().map_err(|_| ())?);
+		} else {
+			res.push(c);
+		}
+	}
+	Ok(res)
+}
+
+pub(crate) fn read_token<'a>(inp: &mut &[&'a str]) -> Result<&'a str, ()> {
+	let res = *inp.first().ok_or(())?;
+	*inp = &inp[1..];
+	Ok(res)
+}
+pub(crate) fn read_u8(inp: &mut &[&str]) -> Result<u8, ()> { parse_decimal(read_token(inp)?) }
+pub(crate) fn read_u16(inp: &mut &[&str]) -> Result<u16, ()> { parse_decimal(read_token(inp)?) }
+pub(crate) fn read_u32(inp: &mut &[&str]) -> Result<u32, ()> { parse_decimal(read_token(inp)?) }
+pub(crate) fn read_ty(inp: &mut &[&str]) -> Result<u16, ()> { parse_ty(read_token(inp)?) }
+pub(crate) fn read_ipv4(inp: &mut &[&str]) -> Result<[u8; 4], ()> { parse_ipv4(read_token(inp)?) }
+pub(crate) fn read_ipv6(inp: &mut &[&str]) -> Result<[u8; 16], ()> { parse_ipv6(read_token(inp)?) }
+
+/// Reads a name, which is resolved relative to `origin` if it does not end in a `.`.
+pub(crate) fn read_name(inp: &mut &[&str], origin: Option<&Name>) -> Result<Name, ()> {
+	let tok = read_token(inp)?;
+	if tok == "@" { return origin.cloned().ok_or(()); }
+	// Note that an escaped trailing dot does not make a name absolute.
+	if let Some(body) = tok.strip_suffix('.') {
+		if (body.len() - body.trim_end_matches('\\').len()) % 2 == 0 {
+			return tok.try_into();
+		}
+	}
+	let origin = origin.ok_or(())?;
+	let mut name = String::with_capacity(tok.len() + 1 + origin.len());
+	name += tok;
+	if origin.as_str() != "." { name.push('.'); }
+	name += origin.as_str();
+	name.try_into()
+}
+
+/// Reads a UNIX timestamp, either as a plain integer or in the `YYYYMMDDHHmmSS` form.
+pub(crate) fn read_time(inp: &mut &[&str]) -> Result<u32, ()> {
+	let tok = read_token(inp)?;
+	// Non-ASCII tokens can't be a valid time, but we must not slice them on a char boundary.
+	if tok.len() != 14 || !tok.is_ascii() { return parse_decimal(tok); }
+	let y: i64 = parse_decimal(&tok[0..4])?;
+	let m: i64 = parse_decimal(&tok[4..6])?;
+	let d: i64 = parse_decimal(&tok[6..8])?;
+	let hour: i64 = parse_decimal(&tok[8..10])?;
+	let min: i64 = parse_decimal(&tok[10..12])?;
+	let sec: i64 = parse_decimal(&tok[12..14])?;
+	if hour > 23 || min > 59 || sec > 59 { return Err(()); }
+	let days = days_from_civil(y, m, d);
+	if civil_from_days(days) != (y, m, d) { return Err(()); }
+	let time = days * 86400 + hour * 3600 + min * 60 + sec;
+	if time < 0 { return Err(()); }
+	// Timestamps use serial number arithmetic, so we simply wrap times after 2106
+	Ok(time as u32)
+}
+
+/// Reads a single hex token, where `-` indicates an empty string.
+pub(crate) fn read_hex(inp: &mut &[&str]) -> Result<Vec<u8>, ()> {
+	let tok = read_token(inp)?;
+	if tok == "-" { return Ok(Vec::new()); }
+	hex::decode(tok)
+}
+
+/// Reads all remaining tokens, which may be split by whitespace, as a single hex string.
+pub(crate) fn read_hex_rest(inp: &mut &[&str]) -> Result<Vec<u8>, ()> {
+	if inp.is_empty() { return Err(()); }
+	let mut s = String::new();
+	while let Ok(tok) = read_token(inp) { s += tok; }
+	hex::decode(&s)
+}
+
+/// Reads all remaining tokens, which may be split by whitespace, as a single base64 string.
+pub(crate) fn read_base64_rest(inp: &mut &[&str]) -> Result<Vec<u8>, ()> {
+	if inp.is_empty() { return Err(()); }
+	let mut s = String::new();
+	while let Ok(tok) = read_token(inp) { s += tok; }
+	base64::decode(&s)
+}
+
+pub(crate) fn read_base32(inp: &mut &[&str]) -> Result<Vec<u8>, ()> {
+	base32::decode(read_token(inp)?)
+}
+
+/// Reads all remaining tokens as a list of record types.
+pub(crate) fn read_types_rest(inp: &mut &[&str]) -> Result<Vec<u16>, ()> {
+	let mut types = Vec::with_capacity(inp.len());
+	while !inp.is_empty() { types.push(read_ty(inp)?); }
+	Ok(types)
+}
+
+/// Reads a single character-string, which may or may not be quoted.
+pub(crate) fn read_char_string(inp: &mut &[&str]) -> Result<Vec<u8>, ()> {
+	let tok = read_token(inp)?;
+	let res = if tok.len() >= 2 && tok.starts_with('"') && tok.ends_with('"') {
+		unescape(&tok[1..tok.len() - 1])?
+	} else {
+		unescape(tok)?
+	};
+	if res.len() > 255 { return Err(()); }
+	Ok(res)
+}
+
+/// A single line (or parenthesized group of lines) in a zone file.
+struct Entry<'a> {
+	/// Whether the line began with whitespace, indicating the previous owner name should be used.
+	owner_omitted: bool,
+	tokens: Vec<&'a str>,
+}
+
+fn is_separator(c: u8) -> bool {
+	c == b' ' || c == b'\t' || c == b'\r' || c == b'\n' || c == b';' || c == b'(' || c == b')'
+}
+
+/// Splits a zone file into [`Entry`]s, stripping comments and handling quotes and parentheses.
+fn lex(zone: &str) -> Result<Vec<Entry<'_>>, ()> {
+	let bytes = zone.as_bytes();
+	let mut entries = Vec::new();
+	let mut tokens = Vec::new();
+	let mut owner_omitted = false;
+	let mut at_line_start = true;
+	let mut paren_depth = 0usize;
+	let mut pos = 0;
+	while pos < bytes.len() {
+		match bytes[pos] {
+			b'\n' => {
+				if paren_depth == 0 {
+					if !tokens.is_empty() {
+						entries.push(Entry { owner_omitted, tokens: core::mem::take(&mut tokens) });
+					}
+					owner_omitted = false;
+					at_line_start = true;
+				}
+				pos += 1;
+			},
+			b' '|b'\t'|b'\r' => {
+				if at_line_start { owner_omitted = true; }
+				pos += 1;
+			},
+			b';' => {
+				while pos < bytes.len() && bytes[pos] != b'\n' { pos += 1; }
+			},
+			b'(' => {
+				paren_depth += 1;
+				at_line_start = false;
+				pos += 1;
+			},
+			b')' => {
+				if paren_depth == 0 { return Err(()); }
+				paren_depth -= 1;
+				pos += 1;
+			},
+			b'"' => {
+				let start = pos;
+				pos += 1;
+				loop {
+					match bytes.get(pos) {
+						None => return Err(()),
+						Some(b'"') => break,
+						Some(b'\\') if bytes.get(pos + 1).map(|c| c.is_ascii()).unwrap_or(false) => pos += 2,
+						Some(_) => pos += 1,
+					}
+				}
+				pos += 1;
+				tokens.push(&zone[start..pos]);
+				at_line_start = false;
+			},
+			_ => {
+				let start = pos;
+				while pos < bytes.len() && !is_separator(bytes[pos]) {
+					if bytes[pos] == b'\\' && bytes.get(pos + 1).map(|c| c.is_ascii()).unwrap_or(false) {
+						pos += 1;
+					}
+					pos += 1;
+				}
+				tokens.push(&zone[start..pos]);
+				at_line_start = false;
+			},
+		}
+	}
+	if paren_depth != 0 { return Err(()); }
+	if !tokens.is_empty() { entries.push(Entry { owner_omitted, tokens }); }
+	Ok(entries)
+}
+
+/// Reads the optional TTL and class (in either order) followed by the type of a record.
+fn read_ttl_class_ty(inp: &mut &[&str]) -> Result<(Option<u32>, u16), ()> {
+	let mut ttl = None;
+	let mut have_class = false;
+	loop {
+		let tok = read_token(inp)?;
+		if tok.eq_ignore_ascii_case("IN") && !have_class {
+			have_class = true;
+		} else if ttl.is_none() && tok.as_bytes()[0].is_ascii_digit() {
+			ttl = Some(parse_ttl(tok)?);
+		} else {
+			return Ok((ttl, parse_ty(tok)?));
+		}
+	}
+}
+
+fn parse_rdata(name: Name, ty: u16, rdata: &mut &[&str], origin: Option<&Name>) -> Result<RR, ()> {
+	let rr = match ty {
+		A::TYPE => RR::A(A::read_from_presentation(name, rdata, origin)?),
+		AAAA::TYPE => RR::AAAA(AAAA::read_from_presentation(name, rdata, origin)?),
+		NS::TYPE => RR::NS(NS::read_from_presentation(name, rdata, origin)?),
+		SOA::TYPE => RR::SOA(SOA::read_from_presentation(name, rdata, origin)?),
+		Txt::TYPE => RR::Txt(Txt::read_from_presentation(name, rdata, origin)?),
+		CName::TYPE => RR::CName(CName::read_from_presentation(name, rdata, origin)?),
+		DName::TYPE => RR::DName(DName::read_from_presentation(name, rdata, origin)?),
+		TLSA::TYPE => RR::TLSA(TLSA::read_from_presentation(name, rdata, origin)?),
+		DnsKey::TYPE => RR::DnsKey(DnsKey::read_from_presentation(name, rdata, origin)?),
+		DS::TYPE => RR::DS(DS::read_from_presentation(name, rdata, origin)?),
+		CDS::TYPE => RR::CDS(CDS::read_from_presentation(name, rdata, origin)?),
+		CDnsKey::TYPE => RR::CDnsKey(CDnsKey::read_from_presentation(name, rdata, origin)?),
+		RRSig::TYPE => RR::RRSig(RRSig::read_from_presentation(name, rdata, origin)?),
+		NSec::TYPE => RR::NSec(NSec::read_from_presentation(name, rdata, origin)?),
+		NSec3::TYPE => RR::NSec3(NSec3::read_from_presentation(name, rdata, origin)?),
+		ZoneMD::TYPE => RR::ZoneMD(ZoneMD::read_from_presentation(name, rdata, origin)?),
+		_ => return Err(()),
+	};
+	if !rdata.is_empty() { return Err(()); }
+	Ok(rr)
+}
+
+/// Parses a zone file in the RFC 1035 presentation format, returning each record and its TTL.
+///
+/// Relative names are resolved against `origin` (if provided) or the most recent `$ORIGIN`
+/// directive. Records without an explicit TTL use the most recent `$TTL` directive, or, failing
+/// that, the TTL of the previous record.
+///
+/// Only the `IN` class and record types supported by this crate can be parsed. `$INCLUDE`
+/// directives and the RFC 3597 generic `\#` RDATA format are not supported.
+pub fn parse_zone(zone: &str, origin: Option<&Name>) -> Result<Vec<(RR, u32)>, ()> {
+	let mut origin = origin.cloned();
+	let mut default_ttl = None;
+	let mut last_ttl = None;
+	let mut last_owner: Option<Name> = None;
+	let mut res = Vec::new();
+	for entry in lex(zone)? {
+		let mut tokens = &entry.tokens[..];
+		if !entry.owner_omitted && tokens[0].starts_with('$') {
+			let directive = read_token(&mut tokens)?;
+			if directive.eq_ignore_ascii_case("$ORIGIN") {
+				origin = Some(read_name(&mut tokens, origin.as_ref())?);
+			} else if directive.eq_ignore_ascii_case("$TTL") {
+				default_ttl = Some(parse_ttl(read_token(&mut tokens)?)?);
+			} else {
+				return Err(());
+			}
+			if !tokens.is_empty() { return Err(()); }
+			continue;
+		}
+
+		let owner = if entry.owner_omitted {
+			last_owner.clone().ok_or(())?
+		} else {
+			read_name(&mut tokens, origin.as_ref())?
+		};
+		let (ttl, ty) = read_ttl_class_ty(&mut tokens)?;
+		if ttl.is_some() { last_ttl = ttl; }
+		let ttl = ttl.or(default_ttl).or(last_ttl).ok_or(())?;
+		res.push((parse_rdata(owner.clone(), ty, &mut tokens, origin.as_ref())?, ttl));
+		last_owner = Some(owner);
+	}
+	Ok(res)
+}
+
+/// Writes the given resource record in the presentation format, followed by a newline, to the
+/// given `String`.
+///
+/// Unlike the [`fmt::Display`] implementation of [`RR`], this includes the record's TTL.
+pub fn write_rr(rr: &RR, ttl: u32, out: &mut String) {
+	rr.write_presentation(Some(ttl), out).expect("Write to a String shouldn't fail");
+	out.push('\n');
+}
+
+impl FromStr for RR {
+	type Err = ();
+	/// Parses a single record in the presentation format, e.g. `example.com. 3600 IN TXT "text"`.
+	///
+	/// The owner name and any names in the record data must be absolute (i.e. end with a `.`),
+	/// and the TTL and class are optional. Any TTL is ignored.
+	fn from_str(s: &str) -> Result<RR, ()> {
+		let entries = lex(s)?;
+		if entries.len() != 1 || entries[0].owner_omitted { return Err(()); }
+		let mut tokens = &entries[0].tokens[..];
+		let name = read_name(&mut tokens, None)?;
+		let (_, ty) = read_ttl_class_ty(&mut tokens)?;
+		parse_rdata(name, ty, &mut tokens, None)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use alloc::string::ToString;
+	use alloc::vec;
+
+	#[test]
+	fn test_ipv6() {
+		let vectors = [
+			("::", "::"), ("::1", "::1"), ("2001:db8::63", "2001:db8::63"),
+			("2001:0DB8:0:0:1:0:0:1", "2001:db8::1:0:0:1"), ("2001:db8:0:1:1:1:1:1", "2001:db8:0:1:1:1:1:1"),
+			("::ffff:192.0.2.1", "::ffff:c000:201"), ("fe80::", "fe80::"),
+			("1:0:0:2:0:0:0:3", "1:0:0:2::3"),
+		];
+		for (input, output) in vectors {
+			let addr = parse_ipv6(input).unwrap();
+			let mut s = String::new();
+			write_ipv6(&mut s, &addr).unwrap();
+			assert_eq!(s, output);
+			assert_eq!(parse_ipv6(&s).unwrap(), addr);
+		}
+		for bad in [":", ":::", "1::2::3", "1:2:3:4:5:6:7", "1:2:3:4:5:6:7:8:9", "12345::", "::g", "1.2.3.4::"] {
+			assert!(parse_ipv6(bad).is_err(), "{}", bad);
+		}
+	}
+
+	#[test]
+	fn test_time() {
+		for (time, s) in [(0, "19700101000000"), (1708387200, "20240220000000"),
+			(1710201600, "20240312000000"), (u32::MAX, "21060207062815"), (951782400, "20000229000000")]
+		{
+			let mut out = String::new();
+			write_time(&mut out, time).unwrap();
+			assert_eq!(out, s);
+			assert_eq!(read_time(&mut &[s][..]), Ok(time));
+		}
+		assert_eq!(read_time(&mut &["1708387200"][..]), Ok(1708387200));
+		assert!(read_time(&mut &["20230229000000"][..]).is_err());
+		assert!(read_time(&mut &["20240220240000"][..]).is_err());
+		assert!(read_time(&mut &["20240\u{e9}1010000"][..]).is_err());
+	}
+
+	#[test]
+	fn test_ttl() {
+		assert_eq!(parse_ttl("3600"), Ok(3600));
+		assert_eq!(parse_ttl("1h30m"), Ok(5400));
+		assert_eq!(parse_ttl("1W1d"), Ok(86400 * 8));
+		assert!(parse_ttl("1h30").is_err());
+		assert!(parse_ttl("h").is_err());
+	}
+
+	#[test]
+	fn test_single_rr_round_trip() {
+		let records = [
+			"example.com. IN A 192.0.2.1",
+			"example.com. IN AAAA 2001:db8::1",
+			"example.com. IN NS ns1.example.com.",
+			"example.com. IN SOA ns1.example.com. admin.example.com. 2018031900 1800 900 604800 86400",
+			"example.com. IN TXT \"hello \\\"world\\\"\\\\ \\010\"",
+			"_443._tcp.example.com. IN TLSA 3 1 1 8BD1DA95272F7FA4FFB24137FC0ED03AAE67E5C4D8B3C50734E1050A7920B922",
+			"a.example.com. IN CNAME b.example.com.",
+			"a.example.com. IN DNAME b.example.com.",
+			"com. IN DNSKEY 257 3 13 tx8EZRAd2+K/DJRV0S+hbBzaRPS/G6JVNBitHzqpsGlz8huE61Ms9ANe6NSDLKJtiTBqfTJWDAywEp1FCsEINQ==",
+			"com. IN DS 19718 13 2 8ACBB0CD28F41250A80A491389424D341522D946B0DA0C0291F2D3D771D7805A",
+			"com. IN CDS 0 0 0 00",
+			"com. IN CDNSKEY 0 3 0 AA==",
+			"com. IN RRSIG DNSKEY 13 1 86400 20240313150235 20240227145735 19718 com. lF2B9nXZn0CgytrHH6xB0NTva4G/aWvg/ypnSxJ8+ZXlvR0C4974yB+nd2ZWzWMICs/oPYMKoQHqxVjnGyu8nA==",
+			"a.example. IN NSEC d.example. A NS SOA MX RRSIG NSEC DNSKEY TYPE1234",
+			"2vptu5timamqttgl4luu9kg21e0aor3s.example. IN NSEC3 1 1 12 AABBCCDD 2T7B4G4VSA5SMI47K61MV5BV1A22BOJR A RRSIG",
+			"example. IN NSEC3 1 0 0 - 2T7B4G4VSA5SMI47K61MV5BV1A22BOJR",
+			"example. IN ZONEMD 2018031900 1 1 C68090D90A7AED716BC459F9340E3D7C1370D4D24B7E2FC3A1DDC0B9A87153B9A9713B3C9AE5CC27777F98B8E730044C",
+		];
+		for record in records {
+			let rr: RR = record.parse().unwrap();
+			assert_eq!(rr.to_string(), record);
+			let mut with_ttl = String::new();
+			write_rr(&rr, 42, &mut with_ttl);
+			assert_eq!(parse_zone(&with_ttl, None).unwrap(), vec![(rr.clone(), 42)]);
+
+			let mut wire = Vec::new();
+			crate::ser::write_rr(&rr, 42, &mut wire);
+			assert_eq!(crate::ser::parse_rr_stream(&wire).unwrap(), vec![rr]);
+		}
+
+		let empty_txt: RR = "example.com. IN TXT \"\"".parse().unwrap();
+		assert_eq!(empty_txt, Txt { name: "example.com.".try_into().unwrap(), data: Vec::new() }.into());
+		assert_eq!(empty_txt.to_string(), "example.com. IN TXT \"\"");
+	}
+
+	#[test]
+	fn test_escaped_names() {
+		let name: Name = "A\\.b\\\\c\\\"\\000\\065\\255\\ .example.".try_into().unwrap();
+		assert_eq!(name.as_str(), "a\\.b\\\\c\\\"\\000a\\255\\032.example.");
+		assert_eq!(name.labels(), 2);
+		assert_eq!(name.trailing_n_labels(1), Some("example."));
+
+		let mut wire = Vec::new();
+		crate::ser::write_name(&mut wire, &name);
+		assert_eq!(wire, b"\x0aa.b\\c\"\x00a\xff \x07example\x00");
+		assert_eq!(crate::ser::name_len(&name) as usize, wire.len());
+		assert_eq!(crate::ser::read_wire_packet_name(&mut &wire[..], &wire).unwrap(), name);
+		let json: serde_json::Value = serde_json::from_str(&RR::from(A { name: name.clone(), address: [0; 4] }).json()).unwrap();
+		assert_eq!(json["name"], name.as_str());
+
+		// Names can be written to and read back from zone files, even with special characters
+		let rr: RR = Txt { name: "a\\;b(c)@.example.".try_into().unwrap(), data: b"txt".to_vec() }.into();
+		assert_eq!(rr.to_string(), "a\\;b\\(c\\)\\@.example. IN TXT \"txt\"");
+		assert_eq!(rr.to_string().parse::<RR>().unwrap(), rr);
+		let records = parse_zone("$ORIGIN example.\na\\. 60 A 192.0.2.1\n", None).unwrap();
+		assert_eq!(records[0].0.name().as_str(), "a\\..example.");
+
+		for bad in ["a..example.", ".a.", "a\\.", "a\\256.", "a\\12.", "a b.", "\"a\".", ""] {
+			assert!(Name::try_from(bad).is_err(), "{}", bad);
+		}
+		assert!(Name::try_from("a".repeat(63) + ".").is_ok());
+		assert!(Name::try_from("a".repeat(64) + ".").is_err());
+		let long = "\\000".repeat(63) + "." + &"a".repeat(63) + "." + &"a".repeat(63) + "." + &"a".repeat(61) + ".";
+		assert!(Name::try_from(long.as_str()).is_ok());
+		assert!(Name::try_from(long + "a.").is_err());
+	}
+
+	#[test]
+	fn test_bad_records() {
+		for record in [
+			"example.com IN A 192.0.2.1", // Relative name with no origin
+			"example.com. IN A 192.0.2.1 extra",
+			"example.com. IN A 192.0.2",
+			"example.com. CH A 192.0.2.1",
+			"example.com. IN MX 10 mail.example.com.", // Unsupported type
+			"example.com. IN DS 19718 13 2 8ACB0",
+			"example.com. IN DNSKEY 257 3 13 tx8EZRAd2+K/DJRV0S+hb",
+			"example.com. IN TXT \"unterminated",
+			"example.com. IN TXT (\"unbalanced\"",
+			"  IN A 192.0.2.1",
+		] {
+			assert!(record.parse::<RR>().is_err(), "{}", record);
+		}
+
+		// A 14-byte time containing multibyte characters is rejected rather than panicking
+		let zone = "example.com. 3600 IN RRSIG A 13 2 3600 20240\u{e9}1010000 20240101000000 1 example.com. AAAA\n";
+		assert_eq!("20240\u{e9}1010000".len(), 14);
+		assert!(parse_zone(zone, None).is_err());
+	}
+
+	#[test]
+	fn test_parse_zone() {
+		let zone = "$ORIGIN example.
+$TTL 86400 ; default TTL
+@     IN  SOA     ns1 admin 2018031900 (
+                  1800 900 604800 86400 )
+              86400  IN  NS      ns1
+              NS      ns2.example.
+      IN  ZONEMD  2018031900 1 1 (
+                                 c68090d90a7aed71
+                                 6bc459f9340e3d7c
+                                 1370d4d24b7e2fc3
+                                 a1ddc0b9a87153b9
+                                 a9713b3c9ae5cc27
+                                 777f98b8e730044c )
+ns1           3600   IN  A       203.0.113.63
+ns2           IN 1h  AAAA    2001:db8::63
+txt TXT \"a;b\" c\\;d ( \"e)\" )
+
+$ORIGIN sub
+a A 192.0.2.1
+";
+		let example: Name = "example.".try_into().unwrap();
+		let records = parse_zone(zone, None).unwrap();
+		assert_eq!(records, vec![
+			(SOA {
+				name: example.clone(), mname: "ns1.example.".try_into().unwrap(),
+				rname: "admin.example.".try_into().unwrap(), serial: 2018031900, refresh: 1800,
+				retry: 900, expire: 604800, minimum: 86400,
+			}.into(), 86400),
+			(NS { name: example.clone(), name_server: "ns1.example.".try_into().unwrap() }.into(), 86400),
+			(NS { name: example.clone(), name_server: "ns2.example.".try_into().unwrap() }.into(), 86400),
+			(ZoneMD {
+				name: example.clone(), serial: 2018031900, scheme: 1, hash_alg: 1,
+				digest: hex::decode("c68090d90a7aed716bc459f9340e3d7c1370d4d24b7e2fc3a1ddc0b9a87153b9a9713b3c9ae5cc27777f98b8e730044c").unwrap(),
+			}.into(), 86400),
+			(A { name: "ns1.example.".try_into().unwrap(), address: [203, 0, 113, 63] }.into(), 3600),
+			(AAAA {
+				name: "ns2.example.".try_into().unwrap(),
+				address: [0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x63],
+			}.into(), 3600),
+			(Txt { name: "txt.example.".try_into().unwrap(), data: b"a;bc;de)".to_vec() }.into(), 86400),
+			(A { name: "a.sub.example.".try_into().unwrap(), address: [192, 0, 2, 1] }.into(), 86400),
+		]);
+
+		// Without a $TTL, a record's TTL is taken from the previous record
+		let records = parse_zone("a.example. 60 A 192.0.2.1\n A 192.0.2.2\n", None).unwrap();
+		assert_eq!(records[1].1, 60);
+		assert!(parse_zone("a.example. A 192.0.2.1\n", None).is_err());
+		// Relative names can be resolved against a provided origin
+		let records = parse_zone("a 60 A 192.0.2.1\n", Some(&example)).unwrap();
+		assert_eq!(records[0].0.name().as_str(), "a.example.");
+		assert!(parse_zone("$INCLUDE other.zone\n", None).is_err());
+		assert!(parse_zone(" 60 A 192.0.2.1\n", None).is_err());
+		assert_eq!(parse_zone("; only a comment\n\n", None).unwrap(), vec![]);
+	}
+}
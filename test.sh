@@ -13,6 +13,8 @@ cargo test --no-default-features --features std
 cargo test --no-default-features --features tokio
 cargo test --no-default-features --features validation
 cargo test --features std,tokio,validation
+cargo test --no-default-features --features serde
+cargo test --features serde
 cargo test --no-default-features --features build_server
 cargo build --lib
 cargo build --lib --features std
@@ -21,7 +23,13 @@ cargo build --lib --features validation
 cargo build --lib --features std,tokio,validation
 cargo build --lib --features std,tokio,validation --release
 cargo build --bin http_proof_gen --features build_server
-cargo doc --features std,tokio,validation
+cargo doc --features std,tokio,validation,serde
+# The dot and doh features require rustc 1.71, and are exempt from our MSRV
+if [ "$RUSTC_MINOR_VERSION" != 63 ]; then
+	cargo test --features std,tokio,validation,dot,doh
+	cargo build --lib --features dot
+	cargo build --lib --features doh
+fi
 cd fuzz
 RUSTFLAGS="$RUSTFLAGS --cfg=fuzzing" RUSTC_BOOTSTRAP=1 cargo build --features stdin_fuzz
 cd ../bench
@@ -3,13 +3,12 @@
 uniffi::include_scaffolding!("interface");
 
 use dnssec_prover::ser::parse_rr_stream;
-use dnssec_prover::validation::{verify_rr_stream, ValidationError};
+use dnssec_prover::validation::{verify_rr_stream, OwnedVerifiedRRStream, ValidationError};
 use dnssec_prover::rr::Name;
 use dnssec_prover::query::ProofBuilder as NativeProofBuilder;
 use dnssec_prover::query::{QueryBuf};
 
 use std::collections::VecDeque;
-use std::fmt::Write;
 use std::sync::{Arc, Mutex};
 
 pub struct ProofBuilder(Mutex<(NativeProofBuilder, VecDeque<QueryBuf>)>);
@@ -83,15 +82,10 @@ fn do_verify_byte_stream(stream: Vec<u8>, name_to_resolve: Name) -> Result<Strin
 	let rrs = parse_rr_stream(&stream).map_err(|()| ValidationError::Invalid)?;
 	let verified_rrs = verify_rr_stream(&rrs)?;
 	let resolved_rrs = verified_rrs.resolve_name(&name_to_resolve);
-	let mut resp = String::new();
-	write!(&mut resp, "{}",
-		format_args!("{{\"valid_from\": {}, \"expires\": {}, \"max_cache_ttl\": {}, \"verified_rrs\": [",
-		verified_rrs.valid_from, verified_rrs.expires, verified_rrs.max_cache_ttl)
-	).expect("Write to a String shouldn't fail");
-	for (idx, rr) in resolved_rrs.iter().enumerate() {
-		write!(&mut resp, "{}{}", if idx != 0 { ", " } else { "" }, rr.json())
-			.expect("Write to a String shouldn't fail");
-	}
-	resp += "]}";
-	Ok(resp)
+	let resp = OwnedVerifiedRRStream {
+		valid_from: verified_rrs.valid_from, expires: verified_rrs.expires,
+		max_cache_ttl: verified_rrs.max_cache_ttl,
+		verified_rrs: resolved_rrs.into_iter().cloned().collect(),
+	};
+	Ok(serde_json::to_string(&resp).expect("Serializing records to JSON shouldn't fail"))
 }